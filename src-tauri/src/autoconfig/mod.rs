@@ -40,8 +40,8 @@ pub async fn lookup_autoconfig(email: &str) -> Result<AutoConfigResult, String>
         format!("https://autoconfig.thunderbird.net/v1.1/{}", domain),
     ];
 
+    // Autoconfig decides which servers we talk to, so it never accepts invalid certificates
     let client = reqwest::Client::builder()
-        .danger_accept_invalid_certs(true)
         .timeout(std::time::Duration::from_secs(10))
         .build()
         .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
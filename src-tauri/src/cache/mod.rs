@@ -1,8 +1,11 @@
-use rusqlite::{Connection, params, OptionalExtension};
+use rusqlite::{Connection, params, OptionalExtension, Row};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 use crate::imap::client::{EmailHeader, Email, Attachment};
+use crate::imap::sort::{HeaderPage, SortKey, SortOptions};
+use crate::imap::sync::{normalize_flags, FolderChanges};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -14,12 +17,26 @@ pub struct CacheStats {
     pub newest_email: Option<String>,
 }
 
+// A cached message of any folder, e.g. one carrying a tag
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FolderHeader {
+    pub folder: String,
+    pub header: EmailHeader,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SyncState {
     pub folder: String,
     pub last_sync: i64,
     pub highest_uid: u32,
+    pub uid_validity: Option<u32>,
+    pub uid_next: Option<u32>,
+    pub highest_modseq: Option<u64>,
+    // Highest UID stored by the sync engine, unset until a sync of the folder completed.
+    // highest_uid is also written by the frontend for the headers it cached itself.
+    pub synced_uid: Option<u32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -50,6 +67,86 @@ fn get_cache_dir() -> Result<PathBuf, String> {
     Ok(data_dir)
 }
 
+// Schema upgrades for cache databases created by older versions
+fn add_column_if_missing(db: &Connection, table: &str, column: &str, definition: &str) -> Result<(), String> {
+    let mut stmt = db.prepare(&format!("PRAGMA table_info({})", table))
+        .map_err(|e| format!("Failed to read table info: {}", e))?;
+
+    let exists = stmt.query_map([], |row| row.get::<_, String>(1))
+        .map_err(|e| format!("Failed to read table info: {}", e))?
+        .filter_map(|name| name.ok())
+        .any(|name| name == column);
+
+    if !exists {
+        db.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])
+            .map_err(|e| format!("Failed to add column {}.{}: {}", table, column, e))?;
+    }
+
+    Ok(())
+}
+
+fn flags_to_column(flags: &[String]) -> String {
+    normalize_flags(flags.to_vec()).join(" ")
+}
+
+fn flags_from_column(flags: Option<String>, is_read: bool) -> Vec<String> {
+    match flags {
+        Some(flags) => flags.split_whitespace().map(|f| f.to_string()).collect(),
+        // Rows cached before flags were stored only know the read state
+        None if is_read => vec!["\\Seen".to_string()],
+        None => Vec::new(),
+    }
+}
+
+// Attachment list of the BODYSTRUCTURE as JSON, NULL for rows cached before it was stored
+fn attachments_to_column(attachments: &[Attachment]) -> Option<String> {
+    serde_json::to_string(attachments).ok()
+}
+
+fn attachments_from_column(column: Option<String>) -> Vec<Attachment> {
+    column
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+fn ids_from_column(column: Option<String>) -> Vec<String> {
+    column
+        .map(|ids| ids.split_whitespace().map(|id| id.to_string()).collect())
+        .unwrap_or_default()
+}
+
+fn has_flag(flags: &[String], flag: &str) -> bool {
+    flags.iter().any(|f| f.eq_ignore_ascii_case(flag))
+}
+
+// Columns of a header row in the order row_to_header reads them
+const HEADER_COLUMNS: &str = "uid, subject, from_addr, to_addr, date, is_read, has_attachments, flags,
+     part_count, size, attachment_list, message_id, in_reply_to, references_list";
+
+fn row_to_header(row: &Row<'_>) -> rusqlite::Result<EmailHeader> {
+    let is_read = row.get::<_, i32>(5)? != 0;
+    let flags = flags_from_column(row.get(7)?, is_read);
+    Ok(EmailHeader {
+        uid: row.get(0)?,
+        subject: row.get(1)?,
+        from: row.get(2)?,
+        to: row.get(3)?,
+        date: row.get(4)?,
+        is_read,
+        is_flagged: has_flag(&flags, "\\Flagged"),
+        is_answered: has_flag(&flags, "\\Answered"),
+        is_draft: has_flag(&flags, "\\Draft"),
+        flags,
+        has_attachments: row.get::<_, i32>(6)? != 0,
+        part_count: row.get::<_, Option<u32>>(8)?.unwrap_or(0),
+        size: row.get::<_, Option<u32>>(9)?.unwrap_or(0),
+        attachments: attachments_from_column(row.get(10)?),
+        message_id: row.get(11)?,
+        in_reply_to: row.get(12)?,
+        references: ids_from_column(row.get(13)?),
+    })
+}
+
 fn sanitize_account_id(account_id: &str) -> String {
     // Replace characters that are invalid in filenames
     account_id
@@ -81,6 +178,7 @@ impl EmailCache {
                 date_timestamp INTEGER,
                 is_read INTEGER DEFAULT 0,
                 has_attachments INTEGER DEFAULT 0,
+                flags TEXT,
                 body_text TEXT,
                 body_html TEXT,
                 cached_at INTEGER NOT NULL,
@@ -101,7 +199,10 @@ impl EmailCache {
             CREATE TABLE IF NOT EXISTS sync_state (
                 folder TEXT PRIMARY KEY,
                 last_sync INTEGER,
-                highest_uid INTEGER
+                highest_uid INTEGER,
+                uid_validity INTEGER,
+                uid_next INTEGER,
+                highest_modseq INTEGER
             );
 
             CREATE INDEX IF NOT EXISTS idx_emails_folder ON emails(folder);
@@ -109,6 +210,20 @@ impl EmailCache {
             "
         ).map_err(|e| format!("Failed to create tables: {}", e))?;
 
+        add_column_if_missing(&db, "emails", "flags", "TEXT")?;
+        add_column_if_missing(&db, "sync_state", "uid_validity", "INTEGER")?;
+        add_column_if_missing(&db, "sync_state", "uid_next", "INTEGER")?;
+        add_column_if_missing(&db, "sync_state", "highest_modseq", "INTEGER")?;
+        add_column_if_missing(&db, "sync_state", "synced_uid", "INTEGER")?;
+        add_column_if_missing(&db, "emails", "part_count", "INTEGER DEFAULT 0")?;
+        add_column_if_missing(&db, "emails", "size", "INTEGER DEFAULT 0")?;
+        add_column_if_missing(&db, "emails", "attachment_list", "TEXT")?;
+        add_column_if_missing(&db, "emails", "message_id", "TEXT")?;
+        add_column_if_missing(&db, "emails", "in_reply_to", "TEXT")?;
+        add_column_if_missing(&db, "emails", "references_list", "TEXT")?;
+        db.execute("CREATE INDEX IF NOT EXISTS idx_emails_message_id ON emails(message_id)", [])
+            .map_err(|e| format!("Failed to create index: {}", e))?;
+
         Ok(Self {
             db,
             account_id: account_id.to_string(),
@@ -116,29 +231,16 @@ impl EmailCache {
     }
 
     pub fn get_headers(&self, folder: &str, start: u32, limit: u32) -> Result<Vec<EmailHeader>, String> {
-        let mut stmt = self.db.prepare(
-            "SELECT uid, subject, from_addr, to_addr, date, is_read, has_attachments
+        let mut stmt = self.db.prepare(&format!(
+            "SELECT {HEADER_COLUMNS}
              FROM emails
              WHERE folder = ?1
              ORDER BY date_timestamp DESC
              LIMIT ?2 OFFSET ?3"
-        ).map_err(|e| format!("Failed to prepare query: {}", e))?;
+        )).map_err(|e| format!("Failed to prepare query: {}", e))?;
 
-        let rows = stmt.query_map(params![folder, limit, start], |row| {
-            Ok(EmailHeader {
-                uid: row.get(0)?,
-                subject: row.get(1)?,
-                from: row.get(2)?,
-                to: row.get(3)?,
-                date: row.get(4)?,
-                is_read: row.get::<_, i32>(5)? != 0,
-                is_flagged: false,
-                is_answered: false,
-                is_draft: false,
-                flags: Vec::new(),
-                has_attachments: row.get::<_, i32>(6)? != 0,
-            })
-        }).map_err(|e| format!("Failed to query headers: {}", e))?;
+        let rows = stmt.query_map(params![folder, limit, start], row_to_header)
+            .map_err(|e| format!("Failed to query headers: {}", e))?;
 
         let mut headers = Vec::new();
         for row in rows {
@@ -148,6 +250,79 @@ impl EmailCache {
         Ok(headers)
     }
 
+    // Headers of the given messages, in no particular order
+    pub fn get_headers_by_uid(&self, folder: &str, uids: &[u32]) -> Result<Vec<EmailHeader>, String> {
+        let uid_list = serde_json::to_string(uids)
+            .map_err(|e| format!("Failed to encode UIDs: {}", e))?;
+
+        let mut stmt = self.db.prepare(&format!(
+            "SELECT {HEADER_COLUMNS}
+             FROM emails
+             WHERE folder = ?1 AND uid IN (SELECT value FROM json_each(?2))"
+        )).map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let rows = stmt.query_map(params![folder, uid_list], row_to_header)
+            .map_err(|e| format!("Failed to query headers: {}", e))?;
+
+        let mut headers = Vec::new();
+        for row in rows {
+            headers.push(row.map_err(|e| format!("Failed to read row: {}", e))?);
+        }
+
+        Ok(headers)
+    }
+
+    // Sorted headers after the anchor UID (keyset pagination), for servers without SORT.
+    // The UID breaks ties so every message has a fixed place in the order.
+    pub fn get_sorted_headers(
+        &self,
+        folder: &str,
+        sort: &SortOptions,
+        after_uid: Option<u32>,
+        limit: u32,
+    ) -> Result<HeaderPage, String> {
+        // NULLs would drop out of the row value comparison, rows without a date or size
+        // sort as 0 instead
+        let column = match sort.key {
+            SortKey::Arrival => "uid",
+            SortKey::Date => "COALESCE(date_timestamp, 0)",
+            SortKey::From => "COALESCE(from_addr, '') COLLATE NOCASE",
+            SortKey::To => "COALESCE(to_addr, '') COLLATE NOCASE",
+            SortKey::Subject => "COALESCE(subject, '') COLLATE NOCASE",
+            SortKey::Size => "COALESCE(size, 0)",
+        };
+        let (compare, direction) = if sort.reverse { ("<", "DESC") } else { (">", "ASC") };
+
+        // A missing anchor compares as NULL and ends the listing
+        let sql = format!(
+            "SELECT {HEADER_COLUMNS}
+             FROM emails
+             WHERE folder = ?1
+               AND (?2 IS NULL OR ({column}, uid) {compare}
+                    (SELECT {column}, uid FROM emails WHERE folder = ?1 AND uid = ?2))
+             ORDER BY {column} {direction}, uid {direction}
+             LIMIT ?3"
+        );
+        let mut stmt = self.db.prepare(&sql)
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let rows = stmt.query_map(params![folder, after_uid, limit], row_to_header)
+            .map_err(|e| format!("Failed to query headers: {}", e))?;
+
+        let mut headers = Vec::new();
+        for row in rows {
+            headers.push(row.map_err(|e| format!("Failed to read row: {}", e))?);
+        }
+
+        let total: u32 = self.db.query_row(
+            "SELECT COUNT(*) FROM emails WHERE folder = ?1",
+            params![folder],
+            |row| row.get(0),
+        ).map_err(|e| format!("Failed to count emails: {}", e))?;
+
+        Ok(HeaderPage::new(headers, limit, total))
+    }
+
     pub fn get_email(&self, folder: &str, uid: u32) -> Result<Option<Email>, String> {
         let mut stmt = self.db.prepare(
             "SELECT uid, subject, from_addr, to_addr, cc, date, body_text, body_html
@@ -215,8 +390,9 @@ impl EmailCache {
 
         self.db.execute(
             "INSERT OR REPLACE INTO emails
-             (uid, folder, subject, from_addr, to_addr, date, date_timestamp, is_read, has_attachments, cached_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+             (uid, folder, subject, from_addr, to_addr, date, date_timestamp, is_read, has_attachments, flags,
+              part_count, size, attachment_list, message_id, in_reply_to, references_list, cached_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)",
             params![
                 header.uid,
                 folder,
@@ -227,6 +403,13 @@ impl EmailCache {
                 timestamp,
                 header.is_read as i32,
                 header.has_attachments as i32,
+                flags_to_column(&header.flags),
+                header.part_count,
+                header.size,
+                attachments_to_column(&header.attachments),
+                header.message_id,
+                header.in_reply_to,
+                header.references.join(" "),
                 now,
             ],
         ).map_err(|e| format!("Failed to store header: {}", e))?;
@@ -257,10 +440,18 @@ impl EmailCache {
             .unwrap()
             .as_secs() as i64;
 
+        // An update keeps what only the header sync stores: threading headers, part count, size
         self.db.execute(
-            "INSERT OR REPLACE INTO emails
-             (uid, folder, subject, from_addr, to_addr, cc, date, date_timestamp, is_read, has_attachments, body_text, body_html, cached_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            "INSERT INTO emails
+             (uid, folder, subject, from_addr, to_addr, cc, date, date_timestamp, is_read, has_attachments, flags,
+              attachment_list, body_text, body_html, cached_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)
+             ON CONFLICT(folder, uid) DO UPDATE SET
+              subject = excluded.subject, from_addr = excluded.from_addr, to_addr = excluded.to_addr,
+              cc = excluded.cc, date = excluded.date, date_timestamp = excluded.date_timestamp,
+              is_read = excluded.is_read, has_attachments = excluded.has_attachments, flags = excluded.flags,
+              attachment_list = excluded.attachment_list, body_text = excluded.body_text,
+              body_html = excluded.body_html, cached_at = excluded.cached_at",
             params![
                 email.uid,
                 folder,
@@ -270,8 +461,10 @@ impl EmailCache {
                 email.cc,
                 email.date,
                 timestamp,
-                0, // is_read will be updated separately
+                email.is_read as i32,
                 !email.attachments.is_empty() as i32,
+                flags_to_column(&email.flags),
+                attachments_to_column(&email.attachments),
                 email.body_text,
                 email.body_html,
                 now,
@@ -319,7 +512,8 @@ impl EmailCache {
 
     pub fn get_sync_state(&self, folder: &str) -> Result<Option<SyncState>, String> {
         let mut stmt = self.db.prepare(
-            "SELECT folder, last_sync, highest_uid FROM sync_state WHERE folder = ?1"
+            "SELECT folder, last_sync, highest_uid, uid_validity, uid_next, highest_modseq, synced_uid
+             FROM sync_state WHERE folder = ?1"
         ).map_err(|e| format!("Failed to prepare query: {}", e))?;
 
         let state = stmt.query_row(params![folder], |row| {
@@ -327,6 +521,10 @@ impl EmailCache {
                 folder: row.get(0)?,
                 last_sync: row.get(1)?,
                 highest_uid: row.get(2)?,
+                uid_validity: row.get(3)?,
+                uid_next: row.get(4)?,
+                highest_modseq: row.get(5)?,
+                synced_uid: row.get(6)?,
             })
         }).optional().map_err(|e| format!("Failed to query sync state: {}", e))?;
 
@@ -339,14 +537,144 @@ impl EmailCache {
             .unwrap()
             .as_secs() as i64;
 
+        // Keep UIDVALIDITY and HIGHESTMODSEQ recorded by the sync engine
         self.db.execute(
-            "INSERT OR REPLACE INTO sync_state (folder, last_sync, highest_uid) VALUES (?1, ?2, ?3)",
+            "INSERT INTO sync_state (folder, last_sync, highest_uid) VALUES (?1, ?2, ?3)
+             ON CONFLICT(folder) DO UPDATE SET last_sync = excluded.last_sync, highest_uid = excluded.highest_uid",
             params![folder, now, highest_uid],
         ).map_err(|e| format!("Failed to set sync state: {}", e))?;
 
         Ok(())
     }
 
+    // Record the UIDVALIDITY reported on SELECT. Returns true if it differs from the stored one,
+    // in which case the cached messages of the folder were dropped and the checkpoint reset.
+    pub fn check_uid_validity(&self, folder: &str, uid_validity: u32) -> Result<bool, String> {
+        let stored: Option<Option<u32>> = self.db.query_row(
+            "SELECT uid_validity FROM sync_state WHERE folder = ?1",
+            params![folder],
+            |row| row.get(0),
+        ).optional().map_err(|e| format!("Failed to query sync state: {}", e))?;
+
+        if stored.flatten() == Some(uid_validity) {
+            return Ok(false);
+        }
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        // Folders without a recorded UIDVALIDITY yet keep their rows, there is nothing to compare
+        let changed = matches!(stored, Some(Some(_)));
+
+        let tx = self.db.unchecked_transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        if changed {
+            self.delete_folder_rows(folder)?;
+            self.db.execute(
+                "INSERT OR REPLACE INTO sync_state (folder, last_sync, highest_uid, uid_validity) VALUES (?1, ?2, 0, ?3)",
+                params![folder, now, uid_validity],
+            ).map_err(|e| format!("Failed to reset sync state: {}", e))?;
+        } else {
+            self.db.execute(
+                "INSERT INTO sync_state (folder, last_sync, highest_uid, uid_validity) VALUES (?1, ?2, 0, ?3)
+                 ON CONFLICT(folder) DO UPDATE SET uid_validity = excluded.uid_validity",
+                params![folder, now, uid_validity],
+            ).map_err(|e| format!("Failed to set sync state: {}", e))?;
+        }
+
+        tx.commit().map_err(|e| format!("Failed to commit sync state: {}", e))?;
+
+        Ok(changed)
+    }
+
+    // Flags of every cached message in a folder, keyed by UID
+    pub fn get_folder_flags(&self, folder: &str) -> Result<HashMap<u32, Vec<String>>, String> {
+        let mut stmt = self.db.prepare(
+            "SELECT uid, flags, is_read FROM emails WHERE folder = ?1"
+        ).map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let rows = stmt.query_map(params![folder], |row| {
+            let uid: u32 = row.get(0)?;
+            let is_read = row.get::<_, i32>(2)? != 0;
+            Ok((uid, flags_from_column(row.get(1)?, is_read)))
+        }).map_err(|e| format!("Failed to query flags: {}", e))?;
+
+        let mut flags = HashMap::new();
+        for row in rows {
+            let (uid, uid_flags) = row.map_err(|e| format!("Failed to read row: {}", e))?;
+            flags.insert(uid, normalize_flags(uid_flags));
+        }
+
+        Ok(flags)
+    }
+
+    // Apply the result of ImapClient::sync_folder and store the new checkpoint
+    // Expunges and flag changes of a sync, the new headers follow via store_headers
+    pub fn apply_folder_changes(&self, folder: &str, changes: &FolderChanges) -> Result<(), String> {
+        let tx = self.db.unchecked_transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        if changes.full_resync {
+            self.delete_folder_rows(folder)?;
+        }
+
+        for uid in &changes.vanished {
+            self.db.execute(
+                "DELETE FROM attachments WHERE folder = ?1 AND email_uid = ?2",
+                params![folder, uid],
+            ).map_err(|e| format!("Failed to delete attachments: {}", e))?;
+            self.delete_email(folder, *uid)?;
+        }
+
+        for (uid, flags) in &changes.flag_updates {
+            let is_read = has_flag(flags, "\\Seen");
+            self.db.execute(
+                "UPDATE emails SET flags = ?1, is_read = ?2 WHERE folder = ?3 AND uid = ?4",
+                params![flags_to_column(flags), is_read as i32, folder, uid],
+            ).map_err(|e| format!("Failed to update flags: {}", e))?;
+        }
+
+        tx.commit().map_err(|e| format!("Failed to commit sync: {}", e))?;
+
+        Ok(())
+    }
+
+    // The checkpoint of a sync, once all of its headers are stored
+    pub fn record_sync(&self, folder: &str, changes: &FolderChanges) -> Result<(), String> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        self.db.execute(
+            "INSERT OR REPLACE INTO sync_state
+             (folder, last_sync, highest_uid, uid_validity, uid_next, highest_modseq, synced_uid)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?3)",
+            params![
+                folder,
+                now,
+                changes.highest_uid,
+                changes.uid_validity,
+                changes.uid_next,
+                changes.highest_modseq,
+            ],
+        ).map_err(|e| format!("Failed to set sync state: {}", e))?;
+
+        Ok(())
+    }
+
+    fn delete_folder_rows(&self, folder: &str) -> Result<(), String> {
+        self.db.execute("DELETE FROM attachments WHERE folder = ?1", params![folder])
+            .map_err(|e| format!("Failed to clear attachments: {}", e))?;
+        self.db.execute("DELETE FROM emails WHERE folder = ?1", params![folder])
+            .map_err(|e| format!("Failed to clear emails: {}", e))?;
+
+        Ok(())
+    }
+
     pub fn update_read_status(&self, folder: &str, uid: u32, is_read: bool) -> Result<(), String> {
         self.db.execute(
             "UPDATE emails SET is_read = ?1 WHERE folder = ?2 AND uid = ?3",
@@ -356,6 +684,15 @@ impl EmailCache {
         Ok(())
     }
 
+    pub fn update_flags(&self, folder: &str, uid: u32, flags: &[String]) -> Result<(), String> {
+        self.db.execute(
+            "UPDATE emails SET flags = ?1, is_read = ?2 WHERE folder = ?3 AND uid = ?4",
+            params![flags_to_column(flags), has_flag(flags, "\\Seen") as i32, folder, uid],
+        ).map_err(|e| format!("Failed to update flags: {}", e))?;
+
+        Ok(())
+    }
+
     pub fn delete_email(&self, folder: &str, uid: u32) -> Result<(), String> {
         // Attachments will be deleted by CASCADE
         self.db.execute(
@@ -387,29 +724,43 @@ impl EmailCache {
     pub fn search(&self, query: &str) -> Result<Vec<EmailHeader>, String> {
         let search_pattern = format!("%{}%", query);
 
-        let mut stmt = self.db.prepare(
-            "SELECT uid, folder, subject, from_addr, to_addr, date, is_read, has_attachments
+        let mut stmt = self.db.prepare(&format!(
+            "SELECT {HEADER_COLUMNS}
              FROM emails
              WHERE subject LIKE ?1 OR from_addr LIKE ?1 OR to_addr LIKE ?1 OR body_text LIKE ?1
              ORDER BY date_timestamp DESC
              LIMIT 100"
-        ).map_err(|e| format!("Failed to prepare search query: {}", e))?;
+        )).map_err(|e| format!("Failed to prepare search query: {}", e))?;
 
-        let rows = stmt.query_map(params![search_pattern], |row| {
-            Ok(EmailHeader {
-                uid: row.get(0)?,
-                subject: row.get(2)?,
-                from: row.get(3)?,
-                to: row.get(4)?,
-                date: row.get(5)?,
-                is_read: row.get::<_, i32>(6)? != 0,
-                is_flagged: false,
-                is_answered: false,
-                is_draft: false,
-                flags: Vec::new(),
-                has_attachments: row.get::<_, i32>(7)? != 0,
+        let rows = stmt.query_map(params![search_pattern], row_to_header)
+            .map_err(|e| format!("Failed to execute search: {}", e))?;
+
+        let mut headers = Vec::new();
+        for row in rows {
+            headers.push(row.map_err(|e| format!("Failed to read row: {}", e))?);
+        }
+
+        Ok(headers)
+    }
+
+    // Messages with a keyword in any folder, newest first. Keywords compare case-insensitively.
+    pub fn get_headers_with_flag(&self, flag: &str, limit: u32) -> Result<Vec<FolderHeader>, String> {
+        let pattern = format!(" {} ", flag.to_lowercase());
+
+        let mut stmt = self.db.prepare(&format!(
+            "SELECT {HEADER_COLUMNS}, folder
+             FROM emails
+             WHERE instr(' ' || lower(flags) || ' ', ?1) > 0
+             ORDER BY date_timestamp DESC
+             LIMIT ?2"
+        )).map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let rows = stmt.query_map(params![pattern, limit], |row| {
+            Ok(FolderHeader {
+                folder: row.get(14)?,
+                header: row_to_header(row)?,
             })
-        }).map_err(|e| format!("Failed to execute search: {}", e))?;
+        }).map_err(|e| format!("Failed to query headers: {}", e))?;
 
         let mut headers = Vec::new();
         for row in rows {
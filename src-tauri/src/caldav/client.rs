@@ -3,6 +3,8 @@ use serde::{Deserialize, Serialize};
 use regex::Regex;
 use chrono::Utc;
 
+use crate::trust;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Calendar {
@@ -63,18 +65,16 @@ pub struct CalDavClient {
 }
 
 impl CalDavClient {
-    pub fn new(base_url: &str, username: &str, password: &str) -> Self {
-        let client = Client::builder()
-            .danger_accept_invalid_certs(true)
-            .build()
-            .unwrap();
+    pub async fn new(base_url: &str, username: &str, password: &str) -> Result<Self, String> {
+        // Self-signed certificates are only accepted if they are the pinned one
+        let client = trust::http_client(username, base_url).await?;
 
-        Self {
+        Ok(Self {
             client,
             base_url: base_url.trim_end_matches('/').to_string(),
             username: username.to_string(),
             password: password.to_string(),
-        }
+        })
     }
 
     pub fn discover_url(host: &str, username: &str) -> String {
@@ -1,6 +1,8 @@
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 
+use crate::trust;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Contact {
@@ -36,21 +38,19 @@ pub struct CardDavClient {
 }
 
 impl CardDavClient {
-    pub fn new(base_url: &str, username: &str, password: &str) -> Self {
-        let client = Client::builder()
-            .danger_accept_invalid_certs(true) // For self-signed certs
-            .build()
-            .unwrap();
-
+    pub async fn new(base_url: &str, username: &str, password: &str) -> Result<Self, String> {
         // Normalize base URL
         let base_url = base_url.trim_end_matches('/').to_string();
 
-        Self {
+        // Self-signed certs are only accepted if they are the pinned one
+        let client = trust::http_client(username, &base_url).await?;
+
+        Ok(Self {
             client,
             base_url,
             username: username.to_string(),
             password: password.to_string(),
-        }
+        })
     }
 
     /// Auto-discover CardDAV URL from email domain
@@ -0,0 +1,289 @@
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+const SERVICE: &str = "MailClient";
+const PBKDF2_ROUNDS: u32 = 600_000;
+const LOCKED: &str = "Credential store is locked, enter the master passphrase first";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Backend {
+    Keyring,
+    EncryptedFile,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialStatus {
+    pub backend: Backend,
+    // Encrypted file only: a master passphrase was set up
+    pub initialized: bool,
+    pub locked: bool,
+}
+
+// Where account passwords are kept, keyed by account id
+pub trait CredentialBackend {
+    fn get(&self, key: &str) -> Result<Option<String>, String>;
+    fn set(&self, key: &str, secret: &str) -> Result<(), String>;
+    fn delete(&self, key: &str) -> Result<(), String>;
+}
+
+// Secret Service on Linux, Keychain on macOS, Credential Manager on Windows
+struct KeyringBackend;
+
+impl KeyringBackend {
+    fn entry(key: &str) -> Result<keyring::Entry, String> {
+        keyring::Entry::new(SERVICE, key).map_err(|e| format!("Failed to open keyring entry: {}", e))
+    }
+
+    // Without a running secret service (e.g. no desktop session) lookups fail with platform errors
+    fn available() -> bool {
+        match Self::entry("availability-check") {
+            Ok(entry) => matches!(entry.get_password(), Ok(_) | Err(keyring::Error::NoEntry)),
+            Err(_) => false,
+        }
+    }
+}
+
+impl CredentialBackend for KeyringBackend {
+    fn get(&self, key: &str) -> Result<Option<String>, String> {
+        match Self::entry(key)?.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(format!("Failed to read password from keyring: {}", e)),
+        }
+    }
+
+    fn set(&self, key: &str, secret: &str) -> Result<(), String> {
+        Self::entry(key)?
+            .set_password(secret)
+            .map_err(|e| format!("Failed to store password in keyring: {}", e))
+    }
+
+    fn delete(&self, key: &str) -> Result<(), String> {
+        match Self::entry(key)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(format!("Failed to delete password from keyring: {}", e)),
+        }
+    }
+}
+
+// AES-256-GCM encrypted JSON map, the key is derived from the master passphrase
+#[derive(Serialize, Deserialize)]
+struct EncryptedFile {
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+struct UnlockedKey {
+    key: [u8; 32],
+    salt: Vec<u8>,
+}
+
+// Derived key of the unlocked file, the passphrase itself is never kept
+fn unlocked_key() -> &'static Mutex<Option<UnlockedKey>> {
+    static KEY: OnceLock<Mutex<Option<UnlockedKey>>> = OnceLock::new();
+    KEY.get_or_init(|| Mutex::new(None))
+}
+
+struct EncryptedFileBackend;
+
+impl EncryptedFileBackend {
+    fn update(&self, change: impl FnOnce(&mut HashMap<String, String>)) -> Result<(), String> {
+        let unlocked = unlocked_key().lock().unwrap();
+        let unlocked = unlocked.as_ref().ok_or(LOCKED)?;
+
+        let mut secrets = match read_file()? {
+            Some(file) => decrypt(&file, &unlocked.key)?,
+            None => HashMap::new(),
+        };
+        change(&mut secrets);
+        write_file(&secrets, unlocked)
+    }
+}
+
+impl CredentialBackend for EncryptedFileBackend {
+    fn get(&self, key: &str) -> Result<Option<String>, String> {
+        let unlocked = unlocked_key().lock().unwrap();
+        let unlocked = unlocked.as_ref().ok_or(LOCKED)?;
+
+        match read_file()? {
+            Some(file) => Ok(decrypt(&file, &unlocked.key)?.remove(key)),
+            None => Ok(None),
+        }
+    }
+
+    fn set(&self, key: &str, secret: &str) -> Result<(), String> {
+        self.update(|secrets| {
+            secrets.insert(key.to_string(), secret.to_string());
+        })
+    }
+
+    fn delete(&self, key: &str) -> Result<(), String> {
+        self.update(|secrets| {
+            secrets.remove(key);
+        })
+    }
+}
+
+fn credentials_path() -> Result<PathBuf, String> {
+    let config_dir = dirs::config_dir()
+        .ok_or("Could not find config directory")?
+        .join("MailClient");
+
+    if !config_dir.exists() {
+        fs::create_dir_all(&config_dir)
+            .map_err(|e| format!("Failed to create config directory: {}", e))?;
+    }
+
+    Ok(config_dir.join("credentials.enc"))
+}
+
+fn read_file() -> Result<Option<EncryptedFile>, String> {
+    let path = credentials_path()?;
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read credentials file: {}", e))?;
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|e| format!("Failed to parse credentials file: {}", e))
+}
+
+fn write_file(secrets: &HashMap<String, String>, unlocked: &UnlockedKey) -> Result<(), String> {
+    let plaintext = serde_json::to_vec(secrets)
+        .map_err(|e| format!("Failed to serialize credentials: {}", e))?;
+
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&unlocked.key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext.as_ref())
+        .map_err(|e| format!("Failed to encrypt credentials: {}", e))?;
+
+    let engine = base64::engine::general_purpose::STANDARD;
+    let file = EncryptedFile {
+        salt: engine.encode(&unlocked.salt),
+        nonce: engine.encode(nonce),
+        ciphertext: engine.encode(ciphertext),
+    };
+    let content = serde_json::to_string_pretty(&file)
+        .map_err(|e| format!("Failed to serialize credentials file: {}", e))?;
+
+    fs::write(credentials_path()?, content)
+        .map_err(|e| format!("Failed to write credentials file: {}", e))
+}
+
+fn decrypt(file: &EncryptedFile, key: &[u8; 32]) -> Result<HashMap<String, String>, String> {
+    let engine = base64::engine::general_purpose::STANDARD;
+    let nonce = engine
+        .decode(&file.nonce)
+        .map_err(|e| format!("Failed to decode credentials file: {}", e))?;
+    let ciphertext = engine
+        .decode(&file.ciphertext)
+        .map_err(|e| format!("Failed to decode credentials file: {}", e))?;
+    if nonce.len() != 12 {
+        return Err("Credentials file is damaged".to_string());
+    }
+
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+        .map_err(|_| "Wrong master passphrase or damaged credentials file".to_string())?;
+
+    serde_json::from_slice(&plaintext).map_err(|e| format!("Failed to parse credentials: {}", e))
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, PBKDF2_ROUNDS, &mut key);
+    key
+}
+
+pub fn backend() -> Backend {
+    static BACKEND: OnceLock<Backend> = OnceLock::new();
+    *BACKEND.get_or_init(|| {
+        if KeyringBackend::available() {
+            println!("[Credentials] Using OS keyring");
+            Backend::Keyring
+        } else {
+            println!("[Credentials] No OS keyring available, using encrypted file");
+            Backend::EncryptedFile
+        }
+    })
+}
+
+fn store() -> Box<dyn CredentialBackend> {
+    match backend() {
+        Backend::Keyring => Box::new(KeyringBackend),
+        Backend::EncryptedFile => Box::new(EncryptedFileBackend),
+    }
+}
+
+pub fn get_password(account_id: &str) -> Result<Option<String>, String> {
+    store().get(account_id)
+}
+
+pub fn set_password(account_id: &str, password: &str) -> Result<(), String> {
+    store().set(account_id, password)
+}
+
+pub fn delete_password(account_id: &str) -> Result<(), String> {
+    store().delete(account_id)
+}
+
+pub fn status() -> Result<CredentialStatus, String> {
+    let backend = backend();
+    if backend == Backend::Keyring {
+        return Ok(CredentialStatus { backend, initialized: true, locked: false });
+    }
+
+    Ok(CredentialStatus {
+        backend,
+        initialized: credentials_path()?.exists(),
+        locked: unlocked_key().lock().unwrap().is_none(),
+    })
+}
+
+// Unlock the encrypted file. The first unlock sets the master passphrase and creates the file.
+pub fn unlock(passphrase: &str) -> Result<(), String> {
+    if passphrase.is_empty() {
+        return Err("Master passphrase must not be empty".to_string());
+    }
+
+    let unlocked = match read_file()? {
+        Some(file) => {
+            let salt = base64::engine::general_purpose::STANDARD
+                .decode(&file.salt)
+                .map_err(|e| format!("Failed to decode credentials file: {}", e))?;
+            let key = derive_key(passphrase, &salt);
+            decrypt(&file, &key)?;
+            UnlockedKey { key, salt }
+        }
+        None => {
+            let mut salt = vec![0u8; 16];
+            OsRng.fill_bytes(&mut salt);
+            let unlocked = UnlockedKey { key: derive_key(passphrase, &salt), salt };
+            write_file(&HashMap::new(), &unlocked)?;
+            println!("[Credentials] Created encrypted credentials file");
+            unlocked
+        }
+    };
+
+    *unlocked_key().lock().unwrap() = Some(unlocked);
+    Ok(())
+}
+
+pub fn lock() {
+    *unlocked_key().lock().unwrap() = None;
+}
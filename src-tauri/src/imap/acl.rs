@@ -0,0 +1,246 @@
+use imap_proto::types::AclRight;
+use serde::{Deserialize, Serialize};
+
+use super::client::decode_imap_utf7;
+
+// Namespaces of the account (RFC 2342), e.g. ("" "/") for the user's own folders and
+// ("shared/" "/") for mailboxes shared by others
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Namespace {
+    pub prefix: String,
+    pub delimiter: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Namespaces {
+    pub personal: Vec<Namespace>,
+    // Folders of other users
+    pub other: Vec<Namespace>,
+    pub shared: Vec<Namespace>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NamespaceKind {
+    #[default]
+    Personal,
+    Other,
+    Shared,
+}
+
+impl Namespaces {
+    // The namespace with the longest matching prefix. Folders outside every namespace,
+    // like INBOX with a personal prefix of "INBOX.", are the user's own.
+    pub fn kind_of(&self, folder: &str) -> NamespaceKind {
+        let kinds = [
+            (NamespaceKind::Personal, &self.personal),
+            (NamespaceKind::Other, &self.other),
+            (NamespaceKind::Shared, &self.shared),
+        ];
+
+        kinds
+            .iter()
+            .flat_map(|(kind, namespaces)| namespaces.iter().map(move |namespace| (*kind, namespace)))
+            .filter(|(_, namespace)| namespace.contains(folder))
+            .max_by_key(|(_, namespace)| namespace.prefix.len())
+            .map(|(kind, _)| kind)
+            .unwrap_or_default()
+    }
+}
+
+impl Namespace {
+    // The namespace root itself ("shared" for "shared/") belongs to it as well
+    fn contains(&self, folder: &str) -> bool {
+        if folder.starts_with(&self.prefix) {
+            return true;
+        }
+        match self.delimiter.as_deref() {
+            Some(delimiter) if !self.prefix.is_empty() => self.prefix.strip_suffix(delimiter) == Some(folder),
+            _ => false,
+        }
+    }
+}
+
+// What the user may do in a folder, from the MYRIGHTS response (RFC 4314 section 4).
+// Servers without ACL allow everything.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AllowedOperations {
+    // r: select the folder and read messages
+    pub read: bool,
+    // s: keep \Seen
+    pub mark_seen: bool,
+    // w: set other flags and keywords
+    pub write_flags: bool,
+    // i: append, copy or move messages into the folder
+    pub insert: bool,
+    // t: flag messages \Deleted
+    pub delete_messages: bool,
+    // e: expunge, needed together with t to delete or move messages out
+    pub expunge: bool,
+    // k: create subfolders
+    pub create_folders: bool,
+    // x: delete or rename the folder
+    pub delete_folder: bool,
+    // a: change the ACL
+    pub administer: bool,
+}
+
+impl Default for AllowedOperations {
+    fn default() -> Self {
+        Self::from_rights("lrswipkxtea")
+    }
+}
+
+impl AllowedOperations {
+    // Rights in RFC 4314 notation. The obsolete c and d of RFC 2086 stand for k and
+    // for t, e and x (section 2.1.1).
+    pub fn from_rights(rights: &str) -> Self {
+        let has = |right: char| rights.contains(right);
+        Self {
+            read: has('r'),
+            mark_seen: has('s'),
+            write_flags: has('w'),
+            insert: has('i'),
+            delete_messages: has('t') || has('d'),
+            expunge: has('e') || has('d'),
+            create_folders: has('k') || has('c'),
+            delete_folder: has('x') || has('d'),
+            administer: has('a'),
+        }
+    }
+}
+
+// One identifier of a folder's ACL, rights in RFC 4314 notation ("lrswi")
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AclEntry {
+    pub identifier: String,
+    pub rights: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MyRights {
+    pub rights: String,
+    pub allowed: AllowedOperations,
+}
+
+pub fn rights_string(rights: &[AclRight]) -> String {
+    rights.iter().map(|&right| char::from(right)).collect()
+}
+
+// SETACL takes rights to replace the current ones, or to add (+) or remove (-) some
+pub fn validate_rights(rights: &str) -> Result<(), String> {
+    let letters = rights.strip_prefix(['+', '-']).unwrap_or(rights);
+    if letters.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit()) {
+        Ok(())
+    } else {
+        Err(format!("Invalid rights: {}", rights))
+    }
+}
+
+// "NAMESPACE (("" "/")) NIL (("shared/" "/"))", tunneled past imap-proto
+pub fn parse_namespace_response(response: &str) -> Result<Namespaces, String> {
+    let rest = response
+        .get(..9)
+        .filter(|name| name.eq_ignore_ascii_case("NAMESPACE"))
+        .map(|_| &response[9..])
+        .ok_or("Not a NAMESPACE response")?;
+
+    let items = parse_items(rest)?;
+    if items.len() < 3 {
+        return Err("Incomplete NAMESPACE response".to_string());
+    }
+
+    Ok(Namespaces {
+        personal: namespaces(&items[0]),
+        other: namespaces(&items[1]),
+        shared: namespaces(&items[2]),
+    })
+}
+
+enum Item {
+    List(Vec<Item>),
+    Text(String),
+    Nil,
+}
+
+// Entries of one namespace group, NIL if there are none. Extension data is skipped.
+fn namespaces(group: &Item) -> Vec<Namespace> {
+    let Item::List(entries) = group else {
+        return Vec::new();
+    };
+
+    entries
+        .iter()
+        .filter_map(|entry| match entry {
+            Item::List(fields) => match fields.as_slice() {
+                [Item::Text(prefix), delimiter, ..] => Some(Namespace {
+                    prefix: decode_imap_utf7(prefix),
+                    delimiter: match delimiter {
+                        Item::Text(delimiter) => Some(delimiter.clone()),
+                        _ => None,
+                    },
+                }),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+// Parenthesized lists of quoted strings and atoms
+fn parse_items(input: &str) -> Result<Vec<Item>, String> {
+    let mut stack: Vec<Vec<Item>> = vec![Vec::new()];
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '(' => stack.push(Vec::new()),
+            ')' => {
+                let list = stack.pop().ok_or("Unbalanced parentheses")?;
+                stack
+                    .last_mut()
+                    .ok_or("Unbalanced parentheses")?
+                    .push(Item::List(list));
+            }
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\\') => text.extend(chars.next()),
+                        Some('"') => break,
+                        Some(c) => text.push(c),
+                        None => return Err("Unterminated string".to_string()),
+                    }
+                }
+                stack.last_mut().ok_or("Unbalanced parentheses")?.push(Item::Text(text));
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut atom = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || next == '(' || next == ')' {
+                        break;
+                    }
+                    atom.push(next);
+                    chars.next();
+                }
+                let item = if atom.eq_ignore_ascii_case("NIL") {
+                    Item::Nil
+                } else {
+                    Item::Text(atom)
+                };
+                stack.last_mut().ok_or("Unbalanced parentheses")?.push(item);
+            }
+        }
+    }
+
+    match (stack.pop(), stack.is_empty()) {
+        (Some(items), true) => Ok(items),
+        _ => Err("Unbalanced parentheses".to_string()),
+    }
+}
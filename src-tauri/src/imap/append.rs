@@ -0,0 +1,48 @@
+use async_imap::types::UnsolicitedResponse;
+use imap_proto::types::{Response, ResponseCode, UidSetMember};
+
+use super::client::ImapSession;
+use super::sync::quote;
+
+// APPEND a message with flags and optionally an internal date ("17-Oct-2026 09:30:00 +0200"),
+// returns the UID from APPENDUID (RFC 4315) if the server reports it. async-imap's append sends
+// the message as raw bytes but drops the tagged response, so the stream repeats its APPENDUID
+// as an unsolicited response, see stream::appenduid_copy.
+pub(crate) async fn append(
+    session: &mut ImapSession,
+    mailbox: &str,
+    flags: &[&str],
+    internal_date: Option<&str>,
+    content: &[u8],
+) -> Result<Option<u32>, String> {
+    let flag_list = format!("({})", flags.join(" "));
+    let date = internal_date.map(|date| format!("\"{}\"", date));
+
+    // async-imap adds the quotes itself, so it gets the escaped name without them
+    let quoted = quote(mailbox);
+    let escaped = &quoted[1..quoted.len() - 1];
+
+    // Nothing else reads unsolicited responses and the queue drops new ones when full
+    while session.unsolicited_responses.try_recv().is_ok() {}
+
+    session
+        .append(escaped, Some(&flag_list), date.as_deref(), content)
+        .await
+        .map_err(|e| format!("Failed to append message: {}", e))?;
+
+    let mut uid = None;
+    while let Ok(response) = session.unsolicited_responses.try_recv() {
+        let data = match response {
+            UnsolicitedResponse::Other(data) => data,
+            _ => continue,
+        };
+        if let Response::Data { code: Some(ResponseCode::AppendUid(_, uids)), .. } = data.parsed() {
+            uid = match uids.first() {
+                Some(UidSetMember::Uid(uid)) => Some(*uid),
+                Some(UidSetMember::UidRange(range)) => Some(*range.start()),
+                None => None,
+            };
+        }
+    }
+    Ok(uid)
+}
@@ -1,15 +1,55 @@
+use async_imap::types::{Capabilities, Fetch, Flag};
 use async_imap::Session;
-use async_native_tls::TlsStream;
 use futures::StreamExt;
+use imap_proto::types::{
+    BodyParams, BodyStructure, ContentEncoding, MailboxDatum, NameAttribute, QuotaResourceName, Response,
+    SectionPath,
+};
 use mailparse::{parse_mail, MailHeaderMap};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tauri::AppHandle;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::TcpStream;
-use tokio::sync::Mutex;
 use tokio_util::compat::TokioAsyncReadCompatExt;
 
-// Use a compat wrapper type for the session
-pub type ImapSession = Session<TlsStream<tokio_util::compat::Compat<TcpStream>>>;
+use super::acl::{self, AclEntry, AllowedOperations, MyRights, NamespaceKind, Namespaces};
+use super::append;
+use super::draft::{self, SavedDraft};
+use super::health::{self, ConnectionState};
+use super::pool::{PoolGuard, SessionPool};
+use super::read_marking::{self, OpenedMessage, ReadMarking};
+use super::search::{self, SearchQuery};
+use super::special_use::{self, FolderRole};
+use super::status;
+use super::sort::{self, HeaderPage, SortOptions};
+use super::stream::{self, CompressSwitch, ImapStream};
+use super::sync::{self, SyncMode, SyncResult};
+use super::thread::{self, Conversation, ThreadNode};
+use crate::cache::EmailCache;
+use crate::oauth::{self, OAuthConfig};
+use crate::quota::{Quota, QuotaUsage};
+use crate::smtp::client::OutgoingEmail;
+use crate::tags::PermanentFlags;
+use crate::transfer::{self, RawMessage};
+use crate::trust;
+
+pub type ImapSession = Session<ImapStream>;
+
+// Everything parse_header needs for a list entry. References is not part of the envelope.
+pub(crate) const HEADER_FETCH_ITEMS: &str =
+    "(UID FLAGS ENVELOPE BODYSTRUCTURE BODY.PEEK[HEADER.FIELDS (REFERENCES)])";
+
+// Transport security of a server connection
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SecurityMode {
+    None,
+    StartTls,
+    Tls,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -21,6 +61,45 @@ pub struct MailAccount {
     pub username: String,
     pub password: String,
     pub display_name: String,
+    // Sign in with an OAuth2 bearer token instead of the password
+    #[serde(default)]
+    pub oauth: Option<OAuthConfig>,
+    // Unset for accounts from older versions, see imap_security_mode/smtp_security_mode
+    #[serde(default)]
+    pub imap_security: Option<SecurityMode>,
+    #[serde(default)]
+    pub smtp_security: Option<SecurityMode>,
+    // Size of the session pool, see imap::pool::DEFAULT_MAX_CONNECTIONS
+    #[serde(default)]
+    pub max_connections: Option<usize>,
+    // Unset marks opened mail read immediately
+    #[serde(default)]
+    pub read_marking: Option<ReadMarking>,
+    // Folders the user picked for a role, instead of the one the server marks
+    #[serde(default)]
+    pub folder_roles: HashMap<FolderRole, String>,
+    // List only subscribed folders
+    #[serde(default)]
+    pub subscribed_only: bool,
+    // Negotiate COMPRESS=DEFLATE (RFC 4978) if the server offers it
+    #[serde(default)]
+    pub compress: bool,
+}
+
+impl MailAccount {
+    // IMAP always used implicit TLS before the mode was configurable
+    pub fn imap_security_mode(&self) -> SecurityMode {
+        self.imap_security.unwrap_or(SecurityMode::Tls)
+    }
+
+    // SMTP used to be guessed from the port: 465 implicit TLS, everything else STARTTLS
+    pub fn smtp_security_mode(&self) -> SecurityMode {
+        self.smtp_security.unwrap_or(if self.smtp_port == 465 {
+            SecurityMode::Tls
+        } else {
+            SecurityMode::StartTls
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -30,6 +109,12 @@ pub struct Folder {
     pub delimiter: String,
     pub unread_count: u32,
     pub total_count: u32,
+    // Special use of the folder, see imap::special_use
+    pub role: Option<FolderRole>,
+    pub subscribed: bool,
+    pub namespace: NamespaceKind,
+    // What the user may do here, see imap::acl
+    pub allowed: AllowedOperations,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -46,6 +131,20 @@ pub struct EmailHeader {
     pub is_draft: bool,
     pub flags: Vec<String>,
     pub has_attachments: bool,
+    // From BODYSTRUCTURE: number of leaf MIME parts and their total size in bytes
+    #[serde(default)]
+    pub part_count: u32,
+    #[serde(default)]
+    pub size: u32,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    // Threading headers, see imap::thread
+    #[serde(default)]
+    pub message_id: Option<String>,
+    #[serde(default)]
+    pub in_reply_to: Option<String>,
+    #[serde(default)]
+    pub references: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -77,109 +176,321 @@ pub struct Attachment {
     pub encoding: String,
 }
 
+#[derive(Clone)]
 pub struct ImapClient {
-    session: Option<Arc<Mutex<ImapSession>>>,
+    // Shared by clones, so commands of one account can run without holding the client map
+    pool: Option<Arc<SessionPool>>,
     account: Option<MailAccount>,
+    capabilities: Option<Arc<Capabilities>>,
+    // Receives connection state events, see imap::health
+    app: Option<AppHandle>,
+    opened: Arc<OpenedMessage>,
+    // Folder of each role as of the last listing
+    roles: Arc<Mutex<HashMap<FolderRole, String>>>,
+    // Fetched once per connection
+    namespaces: Arc<Mutex<Option<Namespaces>>>,
+    // MYRIGHTS of each folder by its name as sent, asked once per connection
+    rights: Arc<Mutex<HashMap<String, String>>>,
+    // Threads of folders listed from the cache, built for the first page and paged from here
+    cached_threads: Arc<Mutex<HashMap<String, Arc<Vec<ThreadNode>>>>>,
 }
 
 impl ImapClient {
     pub fn new() -> Self {
         Self {
-            session: None,
+            pool: None,
             account: None,
+            capabilities: None,
+            app: None,
+            opened: Arc::new(OpenedMessage::default()),
+            roles: Arc::new(Mutex::new(HashMap::new())),
+            namespaces: Arc::new(Mutex::new(None)),
+            rights: Arc::new(Mutex::new(HashMap::new())),
+            cached_threads: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
+    pub fn set_app_handle(&mut self, app: AppHandle) {
+        self.app = Some(app);
+    }
+
     pub async fn connect(&mut self, account: MailAccount) -> Result<(), String> {
-        let addr = format!("{}:{}", account.imap_host, account.imap_port);
+        let (session, capabilities) = establish_session(&account).await?;
+        health::emit_state(self.app.as_ref(), &account.username, ConnectionState::Connected, 0, None);
 
-        // Connect to IMAP server
-        let tcp_stream = TcpStream::connect(&addr)
-            .await
-            .map_err(|e| format!("TCP connection failed: {}", e))?;
+        self.pool = Some(Arc::new(SessionPool::new(account.clone(), self.app.clone(), session)));
+        self.account = Some(account);
+        self.capabilities = Some(Arc::new(capabilities));
 
-        // Wrap with compat layer for futures_io compatibility
-        let compat_stream = tcp_stream.compat();
+        Ok(())
+    }
 
-        // Upgrade to TLS (accept self-signed certificates)
-        let mut tls_builder = native_tls::TlsConnector::builder();
-        tls_builder.danger_accept_invalid_certs(true);
-        let tls: async_native_tls::TlsConnector = tls_builder.into();
-        let tls_stream = tls
-            .connect(&account.imap_host, compat_stream)
-            .await
-            .map_err(|e| format!("TLS connection failed: {}", e))?;
+    // A session from the pool, preferably one that has the folder selected already
+    async fn session(&self, folder: Option<&str>) -> Result<PoolGuard<'_>, String> {
+        let pool = self.pool.as_ref().ok_or("Not connected")?;
+        pool.acquire(folder).await
+    }
 
-        // Create IMAP client
-        let client = async_imap::Client::new(tls_stream);
+    // Probe the session after a failed command. Returns true if it was broken and re-established.
+    async fn recover(&self) -> Result<bool, String> {
+        let pool = self.pool.as_ref().ok_or("Not connected")?;
+        pool.recover().await
+    }
 
-        // Login
-        let session = client
-            .login(&account.username, &account.password)
-            .await
-            .map_err(|(e, _)| format!("Login failed: {}", e))?;
+    // Idempotent commands (fetches, flag changes) are run once more if they failed
+    // because the connection broke
+    async fn retry<T, F, Fut>(&self, op: F) -> Result<T, String>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, String>>,
+    {
+        match op().await {
+            Err(e) => {
+                if !self.recover().await? {
+                    return Err(e);
+                }
+                println!("[IMAP] Retrying after reconnect ({})", e);
+                op().await
+            }
+            result => result,
+        }
+    }
 
-        self.session = Some(Arc::new(Mutex::new(session)));
-        self.account = Some(account);
+    // Other commands are not repeated, the next command checks the sessions first
+    fn checked<T>(&self, result: Result<T, String>) -> Result<T, String> {
+        if let (Err(_), Some(pool)) = (&result, self.pool.as_ref()) {
+            pool.mark_suspect();
+        }
+        result
+    }
 
-        Ok(())
+    // Sessions that had a deleted or renamed folder selected must SELECT again,
+    // and the folder roles are looked up again
+    fn forget_selection(&self, folder: &str) {
+        if let Some(pool) = self.pool.as_ref() {
+            pool.forget_selection(folder);
+        }
+        self.roles.lock().unwrap().clear();
+    }
+
+    pub fn has_capability(&self, name: &str) -> bool {
+        self.capabilities
+            .as_ref()
+            .map(|caps| caps.has_str(name))
+            .unwrap_or(false)
     }
 
     pub async fn disconnect(&mut self) -> Result<(), String> {
-        if let Some(session) = self.session.take() {
-            let mut sess = session.lock().await;
-            let _ = sess.logout().await;
+        if let Some(pool) = self.pool.take() {
+            pool.close().await;
         }
         self.account = None;
+        self.capabilities = None;
+        *self.namespaces.lock().unwrap() = None;
+        self.rights.lock().unwrap().clear();
+        self.cached_threads.lock().unwrap().clear();
         Ok(())
     }
 
-    pub async fn list_folders(&self) -> Result<Vec<Folder>, String> {
-        let session = self
-            .session
-            .as_ref()
-            .ok_or("Not connected")?;
+    // Only subscribed folders if asked to, or if the account lists only those
+    pub async fn list_folders(&self, subscribed_only: Option<bool>) -> Result<Vec<Folder>, String> {
+        let subscribed_only = subscribed_only
+            .or_else(|| self.account.as_ref().map(|account| account.subscribed_only))
+            .unwrap_or(false);
+        self.retry(|| self.try_list_folders(subscribed_only, true)).await
+    }
 
-        let mut sess = session.lock().await;
+    // Without details only names, attributes and roles: no counts, no rights
+    async fn try_list_folders(&self, subscribed_only: bool, details: bool) -> Result<Vec<Folder>, String> {
+        let namespaces = self.get_namespaces().await?;
+        let mut sess = self.session(None).await?;
 
-        let mailboxes_stream = sess
-            .list(None, Some("*"))
-            .await
-            .map_err(|e| format!("Failed to list folders: {}", e))?;
+        let extended = self.has_capability("LIST-EXTENDED");
+        let list_status = details && extended && self.has_capability("LIST-STATUS");
 
-        // Collect the stream into a Vec
-        let mailboxes: Vec<_> = mailboxes_stream
-            .filter_map(|result| async { result.ok() })
-            .collect()
-            .await;
+        // One LIST with everything the server can return along with it (RFC 5258). Servers
+        // may leave the special-use attributes out of a plain LIST (RFC 6154 5.1).
+        let command = if extended {
+            let mut options = vec!["SUBSCRIBED"];
+            if self.has_capability("SPECIAL-USE") {
+                options.push("SPECIAL-USE");
+            }
+            if list_status {
+                options.push("STATUS (MESSAGES UNSEEN)");
+            }
+            let selection = if subscribed_only { "(SUBSCRIBED) " } else { "" };
+            format!("LIST {}\"\" \"*\" RETURN ({})", selection, options.join(" "))
+        } else if subscribed_only {
+            "LSUB \"\" \"*\"".to_string()
+        } else {
+            "LIST \"\" \"*\"".to_string()
+        };
 
-        let mut folders = Vec::new();
-        for mailbox in mailboxes {
-            folders.push(Folder {
-                name: decode_imap_utf7(mailbox.name()),
-                delimiter: mailbox.delimiter().unwrap_or("/").to_string(),
-                unread_count: 0,
-                total_count: 0,
-            });
+        // Folders under their name as sent, with whether they can be selected
+        let mut listed: Vec<(String, Folder, bool)> = Vec::new();
+        let mut counts = HashMap::new();
+        sync::run_raw(&mut sess, &command, |parsed| match parsed {
+            Response::MailboxData(MailboxDatum::List { name_attributes, delimiter, name }) => {
+                // Subscriptions can outlive their folder
+                if has_attribute(name_attributes, "\\NonExistent") {
+                    return;
+                }
+                let selectable = !name_attributes
+                    .iter()
+                    .any(|attribute| matches!(attribute, NameAttribute::NoSelect));
+                listed.push((
+                    name.to_string(),
+                    Folder {
+                        name: decode_imap_utf7(name),
+                        delimiter: delimiter.as_deref().unwrap_or("/").to_string(),
+                        unread_count: 0,
+                        total_count: 0,
+                        role: special_use::role_from_attributes(name_attributes),
+                        subscribed: subscribed_only || has_attribute(name_attributes, "\\Subscribed"),
+                        namespace: NamespaceKind::Personal,
+                        allowed: AllowedOperations::default(),
+                    },
+                    selectable,
+                ));
+            }
+            Response::MailboxData(MailboxDatum::Status { mailbox, status }) => {
+                counts.insert(mailbox.to_string(), status::counts(status));
+            }
+            _ => {}
+        })
+        .await?;
+
+        // Without LIST-EXTENDED the subscriptions are a listing of their own
+        if !extended && !subscribed_only {
+            let mut subscribed = HashSet::new();
+            sync::run_raw(&mut sess, "LSUB \"\" \"*\"", |parsed| {
+                if let Response::MailboxData(MailboxDatum::List { name, .. }) = parsed {
+                    subscribed.insert(name.to_string());
+                }
+            })
+            .await?;
+            for (name, folder, _) in listed.iter_mut() {
+                folder.subscribed = subscribed.contains(name);
+            }
+        }
+
+        let selectable: Vec<String> = listed
+            .iter()
+            .filter(|(_, _, selectable)| *selectable)
+            .map(|(name, _, _)| name.clone())
+            .collect();
+        if details && !list_status {
+            counts = status::status_batch(&mut sess, &selectable).await?;
         }
 
+        // Rights in every selectable folder, so actions the server would refuse can be blocked
+        // up front. Own folders can be restricted as well, e.g. a read-only archive. Only
+        // folders not seen on this connection yet are asked about.
+        if details && self.has_capability("ACL") {
+            let commands: Vec<String> = {
+                let known = self.rights.lock().unwrap();
+                selectable
+                    .iter()
+                    .filter(|name| !known.contains_key(*name))
+                    .map(|name| format!("MYRIGHTS {}", sync::quote(name)))
+                    .collect()
+            };
+            let mut fetched = HashMap::new();
+            sync::run_pipelined(&mut sess, &commands, |parsed| {
+                if let Response::MyRights(my_rights) = parsed {
+                    fetched.insert(my_rights.mailbox.to_string(), acl::rights_string(&my_rights.rights));
+                }
+            })
+            .await?;
+            self.rights.lock().unwrap().extend(fetched);
+        }
+        let rights = self.rights.lock().unwrap().clone();
+
+        let mut folders: Vec<Folder> = listed
+            .into_iter()
+            .map(|(name, mut folder, _)| {
+                if let Some(counts) = counts.get(&name) {
+                    folder.total_count = counts.total;
+                    folder.unread_count = counts.unread;
+                }
+                if let Some(rights) = rights.get(&name) {
+                    folder.allowed = AllowedOperations::from_rights(rights);
+                }
+                folder.namespace = namespaces.kind_of(&folder.name);
+                folder
+            })
+            .collect();
+
+        let overrides = self
+            .account
+            .as_ref()
+            .map(|account| account.folder_roles.clone())
+            .unwrap_or_default();
+        special_use::assign_roles(&mut folders, &overrides);
+
+        *self.roles.lock().unwrap() = folders
+            .iter()
+            .filter_map(|folder| folder.role.map(|role| (role, folder.name.clone())))
+            .collect();
+
         Ok(folders)
     }
 
-    pub async fn select_folder(&self, folder: &str) -> Result<(u32, u32), String> {
-        let session = self
-            .session
+    // The folder for a role (Sent, Trash, Drafts, ...), None if the account has none
+    pub async fn special_folder(&self, role: FolderRole) -> Result<Option<String>, String> {
+        if let Some(folder) = self.account.as_ref().and_then(|account| account.folder_roles.get(&role)) {
+            return Ok(Some(folder.clone()));
+        }
+
+        let known = self.roles.lock().unwrap().get(&role).cloned();
+        if known.is_some() {
+            return Ok(known);
+        }
+
+        // Roles only need a plain listing, no counts or rights
+        let folders = self.retry(|| self.try_list_folders(false, false)).await?;
+        Ok(folders.into_iter().find(|folder| folder.role == Some(role)).map(|folder| folder.name))
+    }
+
+    // Use a folder for a role instead of the detected one, None goes back to detection
+    pub fn set_folder_role(&mut self, role: FolderRole, folder: Option<String>) {
+        if let Some(account) = self.account.as_mut() {
+            match folder {
+                Some(folder) => {
+                    account.folder_roles.insert(role, folder);
+                }
+                None => {
+                    account.folder_roles.remove(&role);
+                }
+            }
+        }
+        self.roles.lock().unwrap().clear();
+    }
+
+    // Flags and keywords the folder keeps, known after its first SELECT
+    pub async fn get_permanent_flags(&self, folder: &str) -> Result<PermanentFlags, String> {
+        let known = self.pool.as_ref().and_then(|pool| pool.permanent_flags(folder));
+        if let Some(permanent) = known {
+            return Ok(permanent);
+        }
+
+        self.select_folder(folder).await?;
+        self.pool
             .as_ref()
-            .ok_or("Not connected")?;
+            .and_then(|pool| pool.permanent_flags(folder))
+            .ok_or_else(|| "Not connected".to_string())
+    }
 
-        let mut sess = session.lock().await;
+    pub async fn select_folder(&self, folder: &str) -> Result<(u32, u32), String> {
+        self.retry(|| self.try_select_folder(folder)).await
+    }
 
-        // Encode folder name for IMAP
-        let encoded_folder = encode_imap_utf7(folder);
-        let mailbox = sess
-            .select(&encoded_folder)
-            .await
-            .map_err(|e| format!("Failed to select folder: {}", e))?;
+    async fn try_select_folder(&self, folder: &str) -> Result<(u32, u32), String> {
+        let mut sess = self.session(Some(folder)).await?;
+
+        let mailbox = sess.select_folder(folder).await?;
+
+        self.verify_uid_validity(folder, mailbox.uid_validity);
 
         let total = mailbox.exists;
         let unseen = mailbox.unseen.unwrap_or(0);
@@ -188,19 +499,15 @@ impl ImapClient {
     }
 
     pub async fn fetch_headers(&self, folder: &str, start: u32, count: u32) -> Result<Vec<EmailHeader>, String> {
-        let session = self
-            .session
-            .as_ref()
-            .ok_or("Not connected")?;
+        self.retry(|| self.try_fetch_headers(folder, start, count)).await
+    }
 
-        let mut sess = session.lock().await;
+    async fn try_fetch_headers(&self, folder: &str, start: u32, count: u32) -> Result<Vec<EmailHeader>, String> {
+        let mut sess = self.session(Some(folder)).await?;
 
-        // Encode folder name for IMAP and select folder
-        let encoded_folder = encode_imap_utf7(folder);
-        let mailbox = sess
-            .select(&encoded_folder)
-            .await
-            .map_err(|e| format!("Failed to select folder: {}", e))?;
+        let mailbox = sess.select_folder(folder).await?;
+
+        self.verify_uid_validity(folder, mailbox.uid_validity);
 
         let total = mailbox.exists;
         if total == 0 {
@@ -218,7 +525,7 @@ impl ImapClient {
         let range = format!("{}:{}", begin, end);
 
         let messages_stream = sess
-            .fetch(&range, "(UID FLAGS ENVELOPE BODYSTRUCTURE)")
+            .fetch(&range, HEADER_FETCH_ITEMS)
             .await
             .map_err(|e| format!("Failed to fetch messages: {}", e))?;
 
@@ -228,121 +535,104 @@ impl ImapClient {
             .collect()
             .await;
 
-        let mut headers = Vec::new();
-        for msg in messages {
-            let uid = msg.uid.unwrap_or(0);
-
-            // Parse all flags
-            let flags_iter = msg.flags();
-            let mut is_read = false;
-            let mut is_flagged = false;
-            let mut is_answered = false;
-            let mut is_draft = false;
-            let mut flags_list: Vec<String> = Vec::new();
-
-            for flag in flags_iter {
-                match flag {
-                    async_imap::types::Flag::Seen => {
-                        is_read = true;
-                        flags_list.push("\\Seen".to_string());
-                    }
-                    async_imap::types::Flag::Flagged => {
-                        is_flagged = true;
-                        flags_list.push("\\Flagged".to_string());
-                    }
-                    async_imap::types::Flag::Answered => {
-                        is_answered = true;
-                        flags_list.push("\\Answered".to_string());
-                    }
-                    async_imap::types::Flag::Draft => {
-                        is_draft = true;
-                        flags_list.push("\\Draft".to_string());
-                    }
-                    async_imap::types::Flag::Deleted => {
-                        flags_list.push("\\Deleted".to_string());
-                    }
-                    async_imap::types::Flag::Recent => {
-                        flags_list.push("\\Recent".to_string());
-                    }
-                    async_imap::types::Flag::Custom(ref s) => {
-                        flags_list.push(s.to_string());
-                    }
-                    _ => {}
+        let mut headers: Vec<EmailHeader> = messages.iter().map(parse_header).collect();
+
+        // Reverse to show newest first
+        headers.reverse();
+
+        Ok(headers)
+    }
+
+    // Headers sorted on the server with SORT (RFC 5256), paged after the last UID of the
+    // previous page. Callers check for the SORT capability first.
+    pub async fn fetch_sorted_headers(
+        &self,
+        folder: &str,
+        sort: &SortOptions,
+        after_uid: Option<u32>,
+        count: u32,
+    ) -> Result<HeaderPage, String> {
+        self.retry(|| self.try_fetch_sorted_headers(folder, sort, after_uid, count)).await
+    }
+
+    async fn try_fetch_sorted_headers(
+        &self,
+        folder: &str,
+        sort: &SortOptions,
+        after_uid: Option<u32>,
+        count: u32,
+    ) -> Result<HeaderPage, String> {
+        let mut sess = self.session(Some(folder)).await?;
+
+        let mailbox = sess.select_folder(folder).await?;
+
+        self.verify_uid_validity(folder, mailbox.uid_validity);
+
+        let total = mailbox.exists;
+        if total == 0 || count == 0 {
+            return Ok(HeaderPage::new(Vec::new(), count, total));
+        }
+
+        // The first page only needs its own UIDs: ESORT (RFC 5267) returns just that range.
+        // Later pages need the whole order to find their anchor.
+        let partial = after_uid.is_none() && self.has_capability("ESORT") && self.has_capability("CONTEXT=SORT");
+        let return_options = if partial {
+            format!("RETURN (PARTIAL 1:{}) ", count)
+        } else {
+            String::new()
+        };
+        let command = format!("UID SORT {}({}) UTF-8 ALL", return_options, sort.criteria());
+
+        // ESORT results arrive as ESEARCH, tunneled past imap-proto (see imap::stream)
+        let mut sorted = Vec::new();
+        sync::run_raw(&mut sess, &command, |parsed| match parsed {
+            Response::MailboxData(MailboxDatum::Sort(uids)) => sorted.extend_from_slice(uids),
+            _ => {
+                if let Some(ranges) = stream::tunneled(parsed).and_then(search::parse_esearch) {
+                    sorted.extend(search::uids_in_order(&ranges));
                 }
             }
+        })
+        .await?;
 
-            let envelope = msg.envelope();
-            let (subject, from, to, date) = if let Some(env) = envelope {
-                let subject = env
-                    .subject
-                    .as_ref()
-                    .map(|s| decode_header_value(s))
-                    .unwrap_or_default();
-
-                let from = env
-                    .from
-                    .as_ref()
-                    .and_then(|addrs| addrs.first())
-                    .map(format_address)
-                    .unwrap_or_default();
-
-                let to = env
-                    .to
-                    .as_ref()
-                    .and_then(|addrs| addrs.first())
-                    .map(format_address)
-                    .unwrap_or_default();
-
-                let date = env
-                    .date
-                    .as_ref()
-                    .map(|d| String::from_utf8_lossy(d).to_string())
-                    .unwrap_or_default();
-
-                (subject, from, to, date)
-            } else {
-                (String::new(), String::new(), String::new(), String::new())
-            };
-
-            // Check for attachments (simplified)
-            let has_attachments = false; // TODO: Parse BODYSTRUCTURE
-
-            headers.push(EmailHeader {
-                uid,
-                subject,
-                from,
-                to,
-                date,
-                is_read,
-                is_flagged,
-                is_answered,
-                is_draft,
-                flags: flags_list,
-                has_attachments,
-            });
+        let uids = sort::page_after(&sorted, after_uid, count);
+        if uids.is_empty() {
+            return Ok(HeaderPage::new(Vec::new(), count, total));
         }
 
-        // Reverse to show newest first
-        headers.reverse();
+        let messages_stream = sess
+            .uid_fetch(uids_to_sequence(&uids), HEADER_FETCH_ITEMS)
+            .await
+            .map_err(|e| format!("Failed to fetch messages: {}", e))?;
 
-        Ok(headers)
+        let messages: Vec<_> = messages_stream
+            .filter_map(|result| async { result.ok() })
+            .collect()
+            .await;
+
+        // FETCH responses come in mailbox order, put them back into sort order
+        let mut by_uid: HashMap<u32, EmailHeader> = messages
+            .iter()
+            .map(parse_header)
+            .map(|header| (header.uid, header))
+            .collect();
+        let headers: Vec<EmailHeader> = uids.iter().filter_map(|uid| by_uid.remove(uid)).collect();
+
+        Ok(HeaderPage::new(headers, count, total))
     }
 
     pub async fn fetch_email(&self, folder: &str, uid: u32) -> Result<Email, String> {
-        let session = self
-            .session
-            .as_ref()
-            .ok_or("Not connected")?;
+        self.retry(|| self.try_fetch_email(folder, uid)).await
+    }
 
-        let mut sess = session.lock().await;
+    async fn try_fetch_email(&self, folder: &str, uid: u32) -> Result<Email, String> {
+        let mut sess = self.session(Some(folder)).await?;
 
-        let encoded_folder = encode_imap_utf7(folder);
-        sess.select(&encoded_folder)
-            .await
-            .map_err(|e| format!("Failed to select folder: {}", e))?;
+        sess.ensure_selected(folder).await?;
 
+        // Headers and structure first, the body parts follow once we know which ones to show
         let messages_stream = sess
-            .uid_fetch(uid.to_string(), "(UID FLAGS ENVELOPE BODY[])")
+            .uid_fetch(uid.to_string(), "(UID FLAGS BODYSTRUCTURE BODY.PEEK[HEADER])")
             .await
             .map_err(|e| format!("Failed to fetch message: {}", e))?;
 
@@ -357,81 +647,56 @@ impl ImapClient {
             .ok_or("Message not found")?;
 
         // Parse flags
-        let flags_iter = msg.flags();
-        let mut is_read = false;
-        let mut is_flagged = false;
-        let mut is_answered = false;
-        let mut is_draft = false;
-        let mut flags_list: Vec<String> = Vec::new();
-
-        for flag in flags_iter {
-            match flag {
-                async_imap::types::Flag::Seen => {
-                    is_read = true;
-                    flags_list.push("\\Seen".to_string());
-                }
-                async_imap::types::Flag::Flagged => {
-                    is_flagged = true;
-                    flags_list.push("\\Flagged".to_string());
-                }
-                async_imap::types::Flag::Answered => {
-                    is_answered = true;
-                    flags_list.push("\\Answered".to_string());
-                }
-                async_imap::types::Flag::Draft => {
-                    is_draft = true;
-                    flags_list.push("\\Draft".to_string());
-                }
-                async_imap::types::Flag::Deleted => {
-                    flags_list.push("\\Deleted".to_string());
-                }
-                async_imap::types::Flag::Recent => {
-                    flags_list.push("\\Recent".to_string());
+        let (is_read, is_flagged, is_answered, is_draft, flags_list) = parse_flags(msg);
+
+        let (headers, _) = mailparse::parse_headers(msg.header().unwrap_or(&[]))
+            .map_err(|e| format!("Failed to parse email: {}", e))?;
+
+        let subject = headers.get_first_value("Subject").unwrap_or_default();
+        let from = headers.get_first_value("From").unwrap_or_default();
+        let to = headers.get_first_value("To").unwrap_or_default();
+        let cc = headers.get_first_value("Cc").unwrap_or_default();
+        let date = headers.get_first_value("Date").unwrap_or_default();
+
+        let structure = msg.bodystructure().ok_or("Message has no body structure")?;
+
+        // Attachments are listed from the structure and downloaded with get_attachment
+        let mut summary = StructureSummary::default();
+        summarize_structure(structure, "", &mut summary);
+
+        let mut parts = BodyParts::default();
+        find_body_parts(structure, "", &mut parts);
+
+        // Only the text and HTML parts are downloaded, however large the attachments are
+        let sections: Vec<String> = [&parts.text, &parts.html]
+            .into_iter()
+            .flatten()
+            .map(|part| format!("BODY.PEEK[{}]", part.part_id))
+            .collect();
+
+        let mut body_text = String::new();
+        let mut body_html = String::new();
+        if !sections.is_empty() {
+            let messages_stream = sess
+                .uid_fetch(uid.to_string(), format!("(UID {})", sections.join(" ")))
+                .await
+                .map_err(|e| format!("Failed to fetch message body: {}", e))?;
+
+            let bodies: Vec<_> = messages_stream
+                .filter_map(|result| async { result.ok() })
+                .collect()
+                .await;
+
+            if let Some(body) = bodies.first() {
+                if let Some(ref part) = parts.text {
+                    body_text = part.decode(body);
                 }
-                async_imap::types::Flag::Custom(ref s) => {
-                    flags_list.push(s.to_string());
+                if let Some(ref part) = parts.html {
+                    body_html = part.decode(body);
                 }
-                _ => {}
             }
         }
 
-        let body = msg.body().unwrap_or(&[]);
-
-        // Parse email
-        let parsed = parse_mail(body)
-            .map_err(|e| format!("Failed to parse email: {}", e))?;
-
-        let subject = parsed
-            .headers
-            .get_first_value("Subject")
-            .unwrap_or_default();
-
-        let from = parsed
-            .headers
-            .get_first_value("From")
-            .unwrap_or_default();
-
-        let to = parsed
-            .headers
-            .get_first_value("To")
-            .unwrap_or_default();
-
-        let cc = parsed
-            .headers
-            .get_first_value("Cc")
-            .unwrap_or_default();
-
-        let date = parsed
-            .headers
-            .get_first_value("Date")
-            .unwrap_or_default();
-
-        // Extract body
-        let (body_text, body_html) = extract_body(&parsed);
-
-        // Extract attachments
-        let attachments = extract_attachments(&parsed);
-
         Ok(Email {
             uid,
             subject,
@@ -441,7 +706,7 @@ impl ImapClient {
             date,
             body_text,
             body_html: sanitize_html(&body_html),
-            attachments,
+            attachments: summary.attachments,
             is_read,
             is_flagged,
             is_answered,
@@ -450,21 +715,74 @@ impl ImapClient {
         })
     }
 
-    pub async fn get_attachment(&self, folder: &str, uid: u32, part_id: &str) -> Result<Vec<u8>, String> {
-        let session = self
-            .session
+    // Fetch a message the user opens and mark it read as the account's read marking says
+    pub async fn open_email(&self, folder: &str, uid: u32) -> Result<Email, String> {
+        let token = self.opened.opened();
+        let mut email = self.fetch_email(folder, uid).await?;
+        if email.is_read {
+            return Ok(email);
+        }
+
+        let policy = self
+            .account
             .as_ref()
-            .ok_or("Not connected")?;
+            .and_then(|account| account.read_marking)
+            .unwrap_or_default();
 
-        let mut sess = session.lock().await;
+        match policy {
+            ReadMarking::Immediately => {
+                self.mark_opened_read(folder, uid).await?;
+                email.is_read = true;
+                email.flags.push("\\Seen".to_string());
+            }
+            ReadMarking::Delayed { seconds } => {
+                let client = self.clone();
+                let folder = folder.to_string();
+                tokio::spawn(async move {
+                    tokio::time::sleep(std::time::Duration::from_secs(seconds as u64)).await;
+                    if !client.opened.is_current(token) {
+                        return;
+                    }
+                    if let Err(e) = client.mark_opened_read(&folder, uid).await {
+                        println!("[IMAP] Failed to mark message {} read: {}", uid, e);
+                    }
+                });
+            }
+            ReadMarking::Manual => {}
+        }
 
-        let encoded_folder = encode_imap_utf7(folder);
-        sess.select(&encoded_folder)
-            .await
-            .map_err(|e| format!("Failed to select folder: {}", e))?;
+        Ok(email)
+    }
+
+    // Set \Seen, update the cached copy and tell the frontend
+    async fn mark_opened_read(&self, folder: &str, uid: u32) -> Result<(), String> {
+        self.mark_read(folder, uid).await?;
+
+        let Some(account) = self.account.as_ref() else {
+            return Ok(());
+        };
+        let cached = EmailCache::new(&account.username)
+            .and_then(|cache| cache.update_read_status(folder, uid, true));
+        if let Err(e) = cached {
+            println!("[IMAP] Failed to update cached read status: {}", e);
+        }
+        read_marking::emit_read(self.app.as_ref(), &account.username, folder, uid);
+        Ok(())
+    }
+
+    pub async fn get_attachment(&self, folder: &str, uid: u32, part_id: &str) -> Result<Vec<u8>, String> {
+        self.retry(|| self.try_get_attachment(folder, uid, part_id)).await
+    }
+
+    async fn try_get_attachment(&self, folder: &str, uid: u32, part_id: &str) -> Result<Vec<u8>, String> {
+        let path = section_path(part_id).ok_or_else(|| format!("Invalid part id: {}", part_id))?;
+
+        let mut sess = self.session(Some(folder)).await?;
 
-        // Fetch the specific MIME part
-        let fetch_query = format!("BODY[{}]", part_id);
+        sess.ensure_selected(folder).await?;
+
+        // Fetch the specific MIME part, the structure tells how it is encoded
+        let fetch_query = format!("(UID BODYSTRUCTURE BODY.PEEK[{}])", part_id);
         let messages_stream = sess
             .uid_fetch(uid.to_string(), &fetch_query)
             .await
@@ -480,38 +798,32 @@ impl ImapClient {
             .first()
             .ok_or("Message not found")?;
 
-        let body = msg.body().unwrap_or(&[]);
-
-        // The body is the raw MIME part data, which might be base64 encoded
-        // We need to decode it based on the Content-Transfer-Encoding
-        // For now, try to decode as base64 if it looks like base64
-        let decoded = if body.iter().all(|&b| b.is_ascii_alphanumeric() || b == b'+' || b == b'/' || b == b'=' || b == b'\r' || b == b'\n') {
-            // Looks like base64
-            let cleaned: String = body.iter()
-                .filter(|&&b| b != b'\r' && b != b'\n')
-                .map(|&b| b as char)
-                .collect();
-            base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &cleaned)
-                .unwrap_or_else(|_| body.to_vec())
-        } else {
-            body.to_vec()
-        };
+        let body = msg
+            .section(&SectionPath::Part(path, None))
+            .ok_or("Attachment not found")?;
+
+        let encoding = msg
+            .bodystructure()
+            .and_then(|structure| find_part(structure, part_id))
+            .and_then(|part| match part {
+                BodyStructure::Basic { other, .. }
+                | BodyStructure::Text { other, .. }
+                | BodyStructure::Message { other, .. } => Some(transfer_encoding_name(&other.transfer_encoding)),
+                BodyStructure::Multipart { .. } => None,
+            })
+            .unwrap_or_else(|| "7bit".to_string());
 
-        Ok(decoded)
+        Ok(decode_transfer_encoding(body, &encoding))
     }
 
     pub async fn mark_read(&self, folder: &str, uid: u32) -> Result<(), String> {
-        let session = self
-            .session
-            .as_ref()
-            .ok_or("Not connected")?;
+        self.retry(|| self.try_mark_read(folder, uid)).await
+    }
 
-        let mut sess = session.lock().await;
+    async fn try_mark_read(&self, folder: &str, uid: u32) -> Result<(), String> {
+        let mut sess = self.session(Some(folder)).await?;
 
-        let encoded_folder = encode_imap_utf7(folder);
-        sess.select(&encoded_folder)
-            .await
-            .map_err(|e| format!("Failed to select folder: {}", e))?;
+        sess.ensure_selected(folder).await?;
 
         // Consume the stream
         let _: Vec<_> = sess.uid_store(uid.to_string(), "+FLAGS (\\Seen)")
@@ -523,80 +835,594 @@ impl ImapClient {
         Ok(())
     }
 
-    pub async fn delete_email(&self, folder: &str, uid: u32) -> Result<(), String> {
-        let session = self
-            .session
-            .as_ref()
-            .ok_or("Not connected")?;
+    // Move the message to Trash. It is only expunged for good when it already is in Trash
+    // or `permanent` is set.
+    pub async fn delete_email(&self, folder: &str, uid: u32, permanent: bool) -> Result<(), String> {
+        self.bulk_delete(folder, &[uid], permanent).await
+    }
+
+    pub async fn move_email(&self, folder: &str, uid: u32, target_folder: &str) -> Result<(), String> {
+        let result = self.try_move_email(folder, uid, target_folder).await;
+        self.checked(result)
+    }
+
+    async fn try_move_email(&self, folder: &str, uid: u32, target_folder: &str) -> Result<(), String> {
+        let mut sess = self.session(Some(folder)).await?;
 
-        let mut sess = session.lock().await;
+        let encoded_target = encode_imap_utf7(target_folder);
+        sess.ensure_selected(folder).await?;
 
-        let encoded_folder = encode_imap_utf7(folder);
-        sess.select(&encoded_folder)
+        sess.uid_mv(uid.to_string(), &encoded_target)
             .await
-            .map_err(|e| format!("Failed to select folder: {}", e))?;
+            .map_err(|e| format!("Failed to move: {}", e))?;
 
-        // Consume the stream
-        let _: Vec<_> = sess.uid_store(uid.to_string(), "+FLAGS (\\Deleted)")
+        Ok(())
+    }
+
+    // Bring the cached copy of a folder up to date with the server (see imap::sync)
+    pub async fn sync_folder(&self, folder: &str) -> Result<SyncResult, String> {
+        self.retry(|| self.try_sync_folder(folder)).await
+    }
+
+    async fn try_sync_folder(&self, folder: &str) -> Result<SyncResult, String> {
+        let account = self.account.as_ref().ok_or("Account not configured")?;
+        let mut sess = self.session(Some(folder)).await?;
+        sess.set_selected(None);
+        let result = sync::sync_to_cache(&mut sess, &account.username, folder, self.sync_mode()).await?;
+        sess.set_selected(Some(folder));
+        Ok(result)
+    }
+
+    // Server side search in a folder, newest matches first
+    pub async fn search(&self, folder: &str, query: &SearchQuery, limit: u32) -> Result<Vec<EmailHeader>, String> {
+        self.retry(|| self.try_search(folder, query, limit)).await
+    }
+
+    async fn try_search(&self, folder: &str, query: &SearchQuery, limit: u32) -> Result<Vec<EmailHeader>, String> {
+        let criteria = query.to_criteria()?;
+        let charset = if criteria.utf8 { "CHARSET UTF-8 " } else { "" };
+
+        let mut sess = self.session(Some(folder)).await?;
+        sess.ensure_selected(folder).await?;
+
+        // ESEARCH (RFC 4731) returns the matches as a compact UID set instead of one by one,
+        // it is only expanded as far as the result needs
+        let mut candidates: Box<dyn Iterator<Item = u32> + Send> = if self.has_capability("ESEARCH") {
+            let command = format!("UID SEARCH RETURN (ALL) {}{}", charset, criteria.criteria);
+            let mut ranges = Vec::new();
+            sync::run_raw(&mut sess, &command, |parsed| {
+                if let Some(found) = stream::tunneled(parsed).and_then(search::parse_esearch) {
+                    ranges.extend(found);
+                }
+            })
+            .await?;
+            Box::new(search::uids_descending(ranges))
+        } else {
+            let found = sess
+                .uid_search(format!("{}{}", charset, criteria.criteria))
+                .await
+                .map_err(|e| format!("Failed to search: {}", e))?;
+            let mut uids: Vec<u32> = found.into_iter().collect();
+            uids.sort_unstable_by(|a, b| b.cmp(a));
+            Box::new(uids.into_iter())
+        };
+
+        // Server matches for an attachment are only candidates, the BODYSTRUCTURE decides.
+        // Further batches are fetched until the page is full.
+        let limit = limit as usize;
+        let mut headers: Vec<EmailHeader> = Vec::new();
+        while headers.len() < limit {
+            let uids: Vec<u32> = candidates.by_ref().take(limit - headers.len()).collect();
+            if uids.is_empty() {
+                break;
+            }
+
+            let messages: Vec<_> = sess
+                .uid_fetch(uids_to_sequence(&uids), HEADER_FETCH_ITEMS)
+                .await
+                .map_err(|e| format!("Failed to fetch messages: {}", e))?
+                .filter_map(|result| async { result.ok() })
+                .collect()
+                .await;
+
+            headers.extend(
+                messages
+                    .iter()
+                    .map(parse_header)
+                    .filter(|header| !query.requires_attachment() || header.has_attachments),
+            );
+        }
+        headers.sort_by(|a, b| b.uid.cmp(&a.uid));
+
+        Ok(headers)
+    }
+
+    // Conversations of a folder, most recent activity first. The server threads them with
+    // THREAD=REFERENCES (RFC 5256), otherwise the cached headers are threaded here.
+    pub async fn list_threads(&self, folder: &str, start: u32, count: u32) -> Result<Vec<Conversation>, String> {
+        self.retry(|| self.try_list_threads(folder, start, count)).await
+    }
+
+    async fn try_list_threads(&self, folder: &str, start: u32, count: u32) -> Result<Vec<Conversation>, String> {
+        if !self.has_capability("THREAD=REFERENCES") {
+            return self.try_list_cached_threads(folder, start, count).await;
+        }
+
+        let mut sess = self.session(Some(folder)).await?;
+        sess.ensure_selected(folder).await?;
+
+        let mut response = None;
+        sync::run_raw(&mut sess, "UID THREAD REFERENCES UTF-8 ALL", |parsed| {
+            if let Some(text) = stream::tunneled(parsed) {
+                response = Some(text.to_string());
+            }
+        })
+        .await?;
+        let mut threads = thread::parse_thread_response(response.as_deref().unwrap_or("THREAD"))?;
+        thread::sort_newest_first(&mut threads);
+
+        let page: Vec<ThreadNode> = threads
+            .into_iter()
+            .skip(start as usize)
+            .take(count as usize)
+            .collect();
+
+        let mut uids = Vec::new();
+        for thread in &page {
+            let mut members = Vec::new();
+            thread.flatten(0, &mut members);
+            uids.extend(members.into_iter().map(|(uid, _)| uid));
+        }
+        if uids.is_empty() {
+            return Ok(Vec::new());
+        }
+        uids.sort_unstable();
+
+        let messages_stream = sess
+            .uid_fetch(uids_to_sequence(&uids), HEADER_FETCH_ITEMS)
             .await
-            .map_err(|e| format!("Failed to delete: {}", e))?
+            .map_err(|e| format!("Failed to fetch messages: {}", e))?;
+
+        let messages: Vec<_> = messages_stream
+            .filter_map(|result| async { result.ok() })
             .collect()
             .await;
 
-        let _: Vec<_> = sess.expunge()
+        let headers: HashMap<u32, EmailHeader> = messages
+            .iter()
+            .map(parse_header)
+            .map(|header| (header.uid, header))
+            .collect();
+
+        Ok(thread::conversations(&page, &headers))
+    }
+
+    // The cache keeps the threading headers. The first page syncs the folder and threads it,
+    // later pages only read the headers of their own messages.
+    async fn try_list_cached_threads(&self, folder: &str, start: u32, count: u32) -> Result<Vec<Conversation>, String> {
+        let account = self.account.as_ref().ok_or("Account not configured")?;
+        let known = if start == 0 {
+            None
+        } else {
+            self.cached_threads.lock().unwrap().get(folder).cloned()
+        };
+
+        let threads = match known {
+            Some(threads) => threads,
+            None => {
+                self.try_sync_folder(folder).await?;
+                let headers = EmailCache::new(&account.username)?.get_headers(folder, 0, u32::MAX)?;
+                let mut threads = thread::thread_headers(&headers);
+                thread::sort_newest_first(&mut threads);
+
+                let threads = Arc::new(threads);
+                self.cached_threads
+                    .lock()
+                    .unwrap()
+                    .insert(folder.to_string(), threads.clone());
+                threads
+            }
+        };
+
+        let page: Vec<ThreadNode> = threads
+            .iter()
+            .skip(start as usize)
+            .take(count as usize)
+            .cloned()
+            .collect();
+
+        let mut uids = Vec::new();
+        for thread in &page {
+            let mut members = Vec::new();
+            thread.flatten(0, &mut members);
+            uids.extend(members.into_iter().map(|(uid, _)| uid));
+        }
+        let by_uid: HashMap<u32, EmailHeader> = EmailCache::new(&account.username)?
+            .get_headers_by_uid(folder, &uids)?
+            .into_iter()
+            .map(|header| (header.uid, header))
+            .collect();
+
+        Ok(thread::conversations(&page, &by_uid))
+    }
+
+    fn sync_mode(&self) -> SyncMode {
+        if self.has_capability("QRESYNC") {
+            SyncMode::Qresync
+        } else if self.has_capability("CONDSTORE") {
+            SyncMode::Condstore
+        } else {
+            SyncMode::Full
+        }
+    }
+
+    // A changed UIDVALIDITY (RFC 3501 2.3.1.1) means every cached UID of the folder now points
+    // at the wrong message: drop the cached rows and rebuild them once the session is free again
+    fn verify_uid_validity(&self, folder: &str, uid_validity: Option<u32>) {
+        let (Some(account), Some(uid_validity)) = (self.account.as_ref(), uid_validity) else {
+            return;
+        };
+
+        let invalidated = EmailCache::new(&account.username)
+            .and_then(|cache| cache.check_uid_validity(folder, uid_validity));
+
+        match invalidated {
+            Ok(true) => {
+                println!("[SYNC] UIDVALIDITY of {} changed, cached messages dropped", folder);
+                self.spawn_sync(folder);
+            }
+            Ok(false) => {}
+            Err(e) => println!("[SYNC] Failed to check UIDVALIDITY of {}: {}", folder, e),
+        }
+    }
+
+    // Sync a folder into the cache without waiting for it
+    pub fn spawn_sync(&self, folder: &str) {
+        let (Some(pool), Some(account)) = (self.pool.clone(), self.account.as_ref()) else {
+            return;
+        };
+
+        let account_id = account.username.clone();
+        let folder = folder.to_string();
+        let mode = self.sync_mode();
+
+        tokio::spawn(async move {
+            if let Err(e) = sync_in_background(pool, &account_id, &folder, mode).await {
+                println!("[SYNC] Background sync of {} failed: {}", folder, e);
+            }
+        });
+    }
+
+    pub fn get_account(&self) -> Option<&MailAccount> {
+        self.account.as_ref()
+    }
+
+    // Returns the UID of the copy in Sent if the server reports APPENDUID
+    pub async fn append_to_sent(&self, message: &[u8]) -> Result<Option<u32>, String> {
+        let result = self.try_append_to_sent(message).await;
+        self.checked(result)
+    }
+
+    async fn try_append_to_sent(&self, message: &[u8]) -> Result<Option<u32>, String> {
+        let sent = self
+            .special_folder(FolderRole::Sent)
+            .await?
+            .ok_or("Could not find Sent folder")?;
+
+        // Stored as read and dated when it was sent, not when the server got it
+        let sent_at = mailparse::parse_headers(message)
+            .ok()
+            .and_then(|(headers, _)| headers.get_first_value("Date"))
+            .and_then(|date| chrono::DateTime::parse_from_rfc2822(date.trim()).ok())
+            .unwrap_or_else(|| chrono::Local::now().fixed_offset());
+        let date = transfer::imap_internal_date(&sent_at);
+
+        let mut sess = self.session(Some(&sent)).await?;
+
+        let uid = append::append(&mut sess, &encode_imap_utf7(&sent), &["\\Seen"], Some(&date), message)
             .await
-            .map_err(|e| format!("Failed to expunge: {}", e))?
+            .map_err(|e| format!("Failed to append to {}: {}", sent, e))?;
+
+        // Put the copy into the cache right away instead of waiting for the next sync
+        // of the folder. Folders that were never cached are left to their first sync.
+        let Some(uid) = uid else {
+            return Ok(None);
+        };
+        let Some(account) = self.account.as_ref() else {
+            return Ok(Some(uid));
+        };
+        let cached = EmailCache::new(&account.username).and_then(|cache| cache.get_sync_state(&sent));
+        if !matches!(cached, Ok(Some(_))) {
+            return Ok(Some(uid));
+        }
+
+        sess.ensure_selected(&sent).await?;
+        let messages: Vec<_> = sess
+            .uid_fetch(uid.to_string(), HEADER_FETCH_ITEMS)
+            .await
+            .map_err(|e| format!("Failed to fetch sent message: {}", e))?
+            .filter_map(|result| async { result.ok() })
             .collect()
             .await;
 
-        Ok(())
+        if let Some(header) = messages.iter().map(parse_header).find(|header| header.uid == uid) {
+            let stored = EmailCache::new(&account.username).and_then(|cache| cache.store_header(&sent, &header));
+            if let Err(e) = stored {
+                println!("[IMAP] Failed to cache sent message: {}", e);
+            }
+        }
+
+        Ok(Some(uid))
     }
 
-    pub async fn move_email(&self, folder: &str, uid: u32, target_folder: &str) -> Result<(), String> {
-        let session = self
-            .session
-            .as_ref()
-            .ok_or("Not connected")?;
+    pub async fn get_quota(&self) -> Result<Vec<Quota>, String> {
+        self.retry(|| self.try_get_quota()).await
+    }
 
-        let mut sess = session.lock().await;
+    async fn try_get_quota(&self) -> Result<Vec<Quota>, String> {
+        if !self.has_capability("QUOTA") {
+            return Ok(Vec::new());
+        }
 
-        let encoded_folder = encode_imap_utf7(folder);
-        let encoded_target = encode_imap_utf7(target_folder);
-        sess.select(&encoded_folder)
-            .await
-            .map_err(|e| format!("Failed to select folder: {}", e))?;
+        let mut sess = self.session(None).await?;
 
-        sess.uid_mv(uid.to_string(), &encoded_target)
-            .await
-            .map_err(|e| format!("Failed to move: {}", e))?;
+        // The quota roots of the INBOX, delivery fails once one of them is full
+        let mut quotas = Vec::new();
+        sync::run_raw(&mut sess, "GETQUOTAROOT \"INBOX\"", |parsed| {
+            if let Response::Quota(quota) = parsed {
+                quotas.push(imap_quota(quota));
+            }
+        })
+        .await?;
 
-        Ok(())
+        Ok(quotas)
     }
 
-    pub fn get_account(&self) -> Option<&MailAccount> {
-        self.account.as_ref()
+    // Personal, other users' and shared namespaces (RFC 2342). Servers without NAMESPACE
+    // have everything in the personal one.
+    pub async fn get_namespaces(&self) -> Result<Namespaces, String> {
+        if let Some(namespaces) = self.namespaces.lock().unwrap().clone() {
+            return Ok(namespaces);
+        }
+
+        let namespaces = self.retry(|| self.try_get_namespaces()).await?;
+        *self.namespaces.lock().unwrap() = Some(namespaces.clone());
+        Ok(namespaces)
     }
 
-    pub async fn append_to_sent(&self, message: &[u8]) -> Result<(), String> {
-        let session = self
-            .session
-            .as_ref()
-            .ok_or("Not connected")?;
+    async fn try_get_namespaces(&self) -> Result<Namespaces, String> {
+        if !self.has_capability("NAMESPACE") {
+            return Ok(Namespaces::default());
+        }
+
+        let mut sess = self.session(None).await?;
+
+        let mut response = None;
+        sync::run_raw(&mut sess, "NAMESPACE", |parsed| {
+            if let Some(text) = stream::tunneled(parsed) {
+                response = Some(text.to_string());
+            }
+        })
+        .await?;
+
+        acl::parse_namespace_response(&response.ok_or("Server sent no NAMESPACE response")?)
+    }
+
+    pub async fn get_acl(&self, folder: &str) -> Result<Vec<AclEntry>, String> {
+        self.retry(|| self.try_get_acl(folder)).await
+    }
+
+    async fn try_get_acl(&self, folder: &str) -> Result<Vec<AclEntry>, String> {
+        self.require_acl()?;
+        let mut sess = self.session(None).await?;
+
+        let mut entries = Vec::new();
+        let command = format!("GETACL {}", sync::quote(&encode_imap_utf7(folder)));
+        sync::run_raw(&mut sess, &command, |parsed| {
+            if let Response::Acl(response) = parsed {
+                entries.extend(response.acls.iter().map(|entry| AclEntry {
+                    identifier: entry.identifier.to_string(),
+                    rights: acl::rights_string(&entry.rights),
+                }));
+            }
+        })
+        .await
+        .map_err(|e| format!("Failed to get ACL of {}: {}", folder, e))?;
+
+        Ok(entries)
+    }
+
+    pub async fn my_rights(&self, folder: &str) -> Result<MyRights, String> {
+        self.retry(|| self.try_my_rights(folder)).await
+    }
+
+    async fn try_my_rights(&self, folder: &str) -> Result<MyRights, String> {
+        if !self.has_capability("ACL") {
+            return Ok(MyRights {
+                rights: String::new(),
+                allowed: AllowedOperations::default(),
+            });
+        }
+        let mut sess = self.session(None).await?;
+
+        let mut rights = None;
+        let command = format!("MYRIGHTS {}", sync::quote(&encode_imap_utf7(folder)));
+        sync::run_raw(&mut sess, &command, |parsed| {
+            if let Response::MyRights(response) = parsed {
+                rights = Some(acl::rights_string(&response.rights));
+            }
+        })
+        .await
+        .map_err(|e| format!("Failed to get rights in {}: {}", folder, e))?;
+
+        let rights = rights.ok_or("Server sent no MYRIGHTS response")?;
+        Ok(MyRights {
+            allowed: AllowedOperations::from_rights(&rights),
+            rights,
+        })
+    }
+
+    // Rights are replaced, or added and removed with a leading + or -
+    pub async fn set_acl(&self, folder: &str, identifier: &str, rights: &str) -> Result<(), String> {
+        acl::validate_rights(rights)?;
+        self.retry(|| self.try_set_acl(folder, identifier, Some(rights))).await
+    }
+
+    pub async fn delete_acl(&self, folder: &str, identifier: &str) -> Result<(), String> {
+        self.retry(|| self.try_set_acl(folder, identifier, None)).await
+    }
+
+    // No rights removes the identifier from the ACL
+    async fn try_set_acl(&self, folder: &str, identifier: &str, rights: Option<&str>) -> Result<(), String> {
+        self.require_acl()?;
+        let mut sess = self.session(None).await?;
 
-        let mut sess = session.lock().await;
+        // Changing an entry can change our own rights as well
+        let encoded = encode_imap_utf7(folder);
+        self.rights.lock().unwrap().remove(&encoded);
 
-        // Try common sent folder names
-        let sent_folders = ["Sent", "Gesendet", "INBOX.Sent", "INBOX.Gesendet", "Sent Items", "Sent Messages"];
+        let mailbox = sync::quote(&encoded);
+        let command = match rights {
+            Some(rights) => format!("SETACL {} {} {}", mailbox, sync::quote(identifier), sync::quote(rights)),
+            None => format!("DELETEACL {} {}", mailbox, sync::quote(identifier)),
+        };
+        sync::run_raw(&mut sess, &command, |_| {})
+            .await
+            .map_err(|e| format!("Failed to change ACL of {}: {}", folder, e))
+    }
+
+    fn require_acl(&self) -> Result<(), String> {
+        if self.has_capability("ACL") {
+            Ok(())
+        } else {
+            Err("The server does not support access control lists".to_string())
+        }
+    }
 
-        for folder_name in &sent_folders {
-            // append(mailbox, flags, date, content)
-            match sess.append(folder_name, None, None, message).await {
-                Ok(_) => return Ok(()),
-                Err(_) => continue,
+    // Store a draft in the Drafts folder. The version it replaces is removed once the new
+    // one is saved, so there is never a moment without a copy.
+    pub async fn save_draft(&self, message: &[u8], replace_uid: Option<u32>) -> Result<SavedDraft, String> {
+        let result = self.try_save_draft(message, replace_uid).await;
+        self.checked(result)
+    }
+
+    async fn try_save_draft(&self, message: &[u8], replace_uid: Option<u32>) -> Result<SavedDraft, String> {
+        let drafts = self
+            .special_folder(FolderRole::Drafts)
+            .await?
+            .ok_or("This account has no Drafts folder")?;
+
+        let mut sess = self.session(Some(&drafts)).await?;
+
+        let flags = ["\\Draft", "\\Seen"];
+        let mut uid = append::append(&mut sess, &encode_imap_utf7(&drafts), &flags, None, message).await?;
+
+        // Without APPENDUID the new copy is looked up by its Message-ID
+        if uid.is_none() {
+            let message_id = mailparse::parse_headers(message)
+                .ok()
+                .and_then(|(headers, _)| headers.get_first_value("Message-ID"))
+                .map(|id| id.trim().replace(['"', '\\'], ""))
+                .filter(|id| !id.is_empty());
+            if let Some(message_id) = message_id {
+                sess.ensure_selected(&drafts).await?;
+                uid = sess
+                    .uid_search(format!("HEADER Message-ID \"{}\"", message_id))
+                    .await
+                    .map_err(|e| format!("Failed to find saved draft: {}", e))?
+                    .into_iter()
+                    .max();
             }
         }
 
-        Err("Could not find Sent folder".to_string())
+        if let Some(old_uid) = replace_uid.filter(|&old_uid| Some(old_uid) != uid) {
+            sess.ensure_selected(&drafts).await?;
+            expunge_uids(&mut sess, &[old_uid], self.has_capability("UIDPLUS")).await?;
+        }
+
+        Ok(SavedDraft { folder: drafts, uid })
+    }
+
+    pub async fn load_draft(&self, folder: &str, uid: u32) -> Result<OutgoingEmail, String> {
+        self.retry(|| self.try_load_draft(folder, uid)).await
+    }
+
+    async fn try_load_draft(&self, folder: &str, uid: u32) -> Result<OutgoingEmail, String> {
+        let mut sess = self.session(Some(folder)).await?;
+
+        sess.ensure_selected(folder).await?;
+
+        // The whole message, attachments included
+        let messages_stream = sess
+            .uid_fetch(uid.to_string(), "(UID BODY.PEEK[])")
+            .await
+            .map_err(|e| format!("Failed to fetch draft: {}", e))?;
+
+        let messages: Vec<_> = messages_stream
+            .filter_map(|result| async { result.ok() })
+            .collect()
+            .await;
+
+        let body = messages
+            .first()
+            .and_then(|msg| msg.body())
+            .ok_or("Draft not found")?;
+
+        draft::parse_draft(body)
+    }
+
+    // The message as another account needs it for a copy
+    pub async fn fetch_raw_message(&self, folder: &str, uid: u32) -> Result<RawMessage, String> {
+        self.retry(|| self.try_fetch_raw_message(folder, uid)).await
+    }
+
+    async fn try_fetch_raw_message(&self, folder: &str, uid: u32) -> Result<RawMessage, String> {
+        let mut sess = self.session(Some(folder)).await?;
+
+        sess.ensure_selected(folder).await?;
+
+        let messages_stream = sess
+            .uid_fetch(uid.to_string(), "(UID FLAGS INTERNALDATE BODY.PEEK[])")
+            .await
+            .map_err(|e| format!("Failed to fetch message: {}", e))?;
+
+        let messages: Vec<_> = messages_stream
+            .filter_map(|result| async { result.ok() })
+            .collect()
+            .await;
+
+        let msg = messages
+            .iter()
+            .find(|msg| msg.uid == Some(uid))
+            .ok_or("Message not found")?;
+
+        Ok(RawMessage {
+            content: msg.body().ok_or("Message has no content")?.to_vec(),
+            flags: msg.flags().filter_map(|flag| flag_name(&flag)).collect(),
+            internal_date: msg.internal_date(),
+        })
+    }
+
+    // APPEND a message from another account with its flags and date. Returns the new UID
+    // if the server reports it.
+    pub async fn import_message(&self, folder: &str, message: &RawMessage) -> Result<Option<u32>, String> {
+        let result = self.try_import_message(folder, message).await;
+        self.checked(result)
+    }
+
+    async fn try_import_message(&self, folder: &str, message: &RawMessage) -> Result<Option<u32>, String> {
+        // Keywords the folder can't keep would fail the whole APPEND on some servers
+        let permanent = self.pool.as_ref().and_then(|pool| pool.permanent_flags(folder));
+        let flags: Vec<&str> = message
+            .flags_to_copy()
+            .into_iter()
+            .filter(|flag| permanent.as_ref().map_or(true, |permanent| permanent.allows(flag)))
+            .collect();
+        let date = message.internal_date.as_ref().map(transfer::imap_internal_date);
+
+        let mut sess = self.session(None).await?;
+
+        append::append(&mut sess, &encode_imap_utf7(folder), &flags, date.as_deref(), &message.content).await
     }
 
     // Flag operations
@@ -614,17 +1440,19 @@ impl ImapClient {
     }
 
     pub async fn add_flags(&self, folder: &str, uid: u32, flags: &[&str]) -> Result<(), String> {
-        let session = self
-            .session
-            .as_ref()
-            .ok_or("Not connected")?;
+        // Servers accept keywords they can't keep and drop them with the session
+        if let Some(permanent) = self.pool.as_ref().and_then(|pool| pool.permanent_flags(folder)) {
+            if let Some(flag) = flags.iter().find(|flag| !permanent.allows(flag)) {
+                return Err(format!("The server does not store the flag {} in {}", flag, folder));
+            }
+        }
+        self.retry(|| self.try_add_flags(folder, uid, flags)).await
+    }
 
-        let mut sess = session.lock().await;
+    async fn try_add_flags(&self, folder: &str, uid: u32, flags: &[&str]) -> Result<(), String> {
+        let mut sess = self.session(Some(folder)).await?;
 
-        let encoded_folder = encode_imap_utf7(folder);
-        sess.select(&encoded_folder)
-            .await
-            .map_err(|e| format!("Failed to select folder: {}", e))?;
+        sess.ensure_selected(folder).await?;
 
         let flags_str = format!("+FLAGS ({})", flags.join(" "));
         let _: Vec<_> = sess.uid_store(uid.to_string(), &flags_str)
@@ -637,17 +1465,13 @@ impl ImapClient {
     }
 
     pub async fn remove_flags(&self, folder: &str, uid: u32, flags: &[&str]) -> Result<(), String> {
-        let session = self
-            .session
-            .as_ref()
-            .ok_or("Not connected")?;
+        self.retry(|| self.try_remove_flags(folder, uid, flags)).await
+    }
 
-        let mut sess = session.lock().await;
+    async fn try_remove_flags(&self, folder: &str, uid: u32, flags: &[&str]) -> Result<(), String> {
+        let mut sess = self.session(Some(folder)).await?;
 
-        let encoded_folder = encode_imap_utf7(folder);
-        sess.select(&encoded_folder)
-            .await
-            .map_err(|e| format!("Failed to select folder: {}", e))?;
+        sess.ensure_selected(folder).await?;
 
         let flags_str = format!("-FLAGS ({})", flags.join(" "));
         let _: Vec<_> = sess.uid_store(uid.to_string(), &flags_str)
@@ -660,17 +1484,13 @@ impl ImapClient {
     }
 
     pub async fn set_flags(&self, folder: &str, uid: u32, flags: &[&str]) -> Result<(), String> {
-        let session = self
-            .session
-            .as_ref()
-            .ok_or("Not connected")?;
+        self.retry(|| self.try_set_flags(folder, uid, flags)).await
+    }
 
-        let mut sess = session.lock().await;
+    async fn try_set_flags(&self, folder: &str, uid: u32, flags: &[&str]) -> Result<(), String> {
+        let mut sess = self.session(Some(folder)).await?;
 
-        let encoded_folder = encode_imap_utf7(folder);
-        sess.select(&encoded_folder)
-            .await
-            .map_err(|e| format!("Failed to select folder: {}", e))?;
+        sess.ensure_selected(folder).await?;
 
         let flags_str = format!("FLAGS ({})", flags.join(" "));
         let _: Vec<_> = sess.uid_store(uid.to_string(), &flags_str)
@@ -685,44 +1505,76 @@ impl ImapClient {
     // Folder operations
 
     pub async fn create_folder(&self, folder_name: &str) -> Result<(), String> {
-        let session = self
-            .session
-            .as_ref()
-            .ok_or("Not connected")?;
+        let result = self.try_create_folder(folder_name).await;
+        self.checked(result)
+    }
 
-        let mut sess = session.lock().await;
+    async fn try_create_folder(&self, folder_name: &str) -> Result<(), String> {
+        let mut sess = self.session(None).await?;
 
         let encoded_folder = encode_imap_utf7(folder_name);
         sess.create(&encoded_folder)
             .await
             .map_err(|e| format!("Failed to create folder: {}", e))?;
 
+        // New folders are subscribed, or they would not show in a subscribed-only listing
+        if let Err(e) = sess.subscribe(&encoded_folder).await {
+            println!("[IMAP] Failed to subscribe to {}: {}", folder_name, e);
+        }
+
+        Ok(())
+    }
+
+    pub async fn subscribe_folder(&self, folder_name: &str) -> Result<(), String> {
+        self.retry(|| self.try_subscribe_folder(folder_name, true)).await
+    }
+
+    pub async fn unsubscribe_folder(&self, folder_name: &str) -> Result<(), String> {
+        self.retry(|| self.try_subscribe_folder(folder_name, false)).await
+    }
+
+    async fn try_subscribe_folder(&self, folder_name: &str, subscribe: bool) -> Result<(), String> {
+        let mut sess = self.session(None).await?;
+
+        let encoded_folder = encode_imap_utf7(folder_name);
+        if subscribe {
+            sess.subscribe(&encoded_folder)
+                .await
+                .map_err(|e| format!("Failed to subscribe to folder: {}", e))?;
+        } else {
+            sess.unsubscribe(&encoded_folder)
+                .await
+                .map_err(|e| format!("Failed to unsubscribe from folder: {}", e))?;
+        }
+
         Ok(())
     }
 
     pub async fn delete_folder(&self, folder_name: &str) -> Result<(), String> {
-        let session = self
-            .session
-            .as_ref()
-            .ok_or("Not connected")?;
+        let result = self.try_delete_folder(folder_name).await;
+        self.checked(result)
+    }
 
-        let mut sess = session.lock().await;
+    async fn try_delete_folder(&self, folder_name: &str) -> Result<(), String> {
+        let mut sess = self.session(None).await?;
 
         let encoded_folder = encode_imap_utf7(folder_name);
         sess.delete(&encoded_folder)
             .await
             .map_err(|e| format!("Failed to delete folder: {}", e))?;
 
+        sess.set_selected(None);
+        self.forget_selection(folder_name);
         Ok(())
     }
 
     pub async fn rename_folder(&self, old_name: &str, new_name: &str) -> Result<(), String> {
-        let session = self
-            .session
-            .as_ref()
-            .ok_or("Not connected")?;
+        let result = self.try_rename_folder(old_name, new_name).await;
+        self.checked(result)
+    }
 
-        let mut sess = session.lock().await;
+    async fn try_rename_folder(&self, old_name: &str, new_name: &str) -> Result<(), String> {
+        let mut sess = self.session(None).await?;
 
         let encoded_old = encode_imap_utf7(old_name);
         let encoded_new = encode_imap_utf7(new_name);
@@ -730,6 +1582,8 @@ impl ImapClient {
             .await
             .map_err(|e| format!("Failed to rename folder: {}", e))?;
 
+        sess.set_selected(None);
+        self.forget_selection(old_name);
         Ok(())
     }
 
@@ -747,81 +1601,75 @@ impl ImapClient {
         self.bulk_add_flags(folder, uids, &["\\Flagged"]).await
     }
 
-    pub async fn bulk_delete(&self, folder: &str, uids: &[u32]) -> Result<(), String> {
+    pub async fn bulk_delete(&self, folder: &str, uids: &[u32], permanent: bool) -> Result<(), String> {
+        let result = self.try_bulk_delete(folder, uids, permanent).await;
+        self.checked(result)
+    }
+
+    async fn try_bulk_delete(&self, folder: &str, uids: &[u32], permanent: bool) -> Result<(), String> {
         if uids.is_empty() {
             return Ok(());
         }
 
-        let session = self
-            .session
-            .as_ref()
-            .ok_or("Not connected")?;
-
-        let mut sess = session.lock().await;
-
-        let encoded_folder = encode_imap_utf7(folder);
-        sess.select(&encoded_folder)
-            .await
-            .map_err(|e| format!("Failed to select folder: {}", e))?;
+        if !permanent {
+            let trash = self
+                .special_folder(FolderRole::Trash)
+                .await?
+                .ok_or("This account has no Trash folder")?;
+            if trash != folder {
+                return self.try_bulk_move(folder, uids, &trash).await;
+            }
+        }
 
-        let uid_str = uids_to_sequence(uids);
-        let _: Vec<_> = sess.uid_store(&uid_str, "+FLAGS (\\Deleted)")
-            .await
-            .map_err(|e| format!("Failed to mark deleted: {}", e))?
-            .collect()
-            .await;
+        let mut sess = self.session(Some(folder)).await?;
 
-        let _: Vec<_> = sess.expunge()
-            .await
-            .map_err(|e| format!("Failed to expunge: {}", e))?
-            .collect()
-            .await;
+        sess.ensure_selected(folder).await?;
 
-        Ok(())
+        expunge_uids(&mut sess, uids, self.has_capability("UIDPLUS")).await
     }
 
     pub async fn bulk_move(&self, folder: &str, uids: &[u32], target_folder: &str) -> Result<(), String> {
+        let result = self.try_bulk_move(folder, uids, target_folder).await;
+        self.checked(result)
+    }
+
+    async fn try_bulk_move(&self, folder: &str, uids: &[u32], target_folder: &str) -> Result<(), String> {
         if uids.is_empty() {
             return Ok(());
         }
 
-        let session = self
-            .session
-            .as_ref()
-            .ok_or("Not connected")?;
-
-        let mut sess = session.lock().await;
+        let mut sess = self.session(Some(folder)).await?;
 
-        let encoded_folder = encode_imap_utf7(folder);
         let encoded_target = encode_imap_utf7(target_folder);
-        sess.select(&encoded_folder)
-            .await
-            .map_err(|e| format!("Failed to select folder: {}", e))?;
+        sess.ensure_selected(folder).await?;
 
         let uid_str = uids_to_sequence(uids);
-        sess.uid_mv(&uid_str, &encoded_target)
-            .await
-            .map_err(|e| format!("Failed to move emails: {}", e))?;
+        if self.has_capability("MOVE") {
+            sess.uid_mv(&uid_str, &encoded_target)
+                .await
+                .map_err(|e| format!("Failed to move emails: {}", e))?;
+            return Ok(());
+        }
 
-        Ok(())
+        // Without MOVE (RFC 6851): copy, then remove just these messages from the source
+        sess.uid_copy(&uid_str, &encoded_target)
+            .await
+            .map_err(|e| format!("Failed to copy emails: {}", e))?;
+        expunge_uids(&mut sess, uids, self.has_capability("UIDPLUS")).await
     }
 
     pub async fn bulk_add_flags(&self, folder: &str, uids: &[u32], flags: &[&str]) -> Result<(), String> {
+        self.retry(|| self.try_bulk_add_flags(folder, uids, flags)).await
+    }
+
+    async fn try_bulk_add_flags(&self, folder: &str, uids: &[u32], flags: &[&str]) -> Result<(), String> {
         if uids.is_empty() {
             return Ok(());
         }
 
-        let session = self
-            .session
-            .as_ref()
-            .ok_or("Not connected")?;
-
-        let mut sess = session.lock().await;
+        let mut sess = self.session(Some(folder)).await?;
 
-        let encoded_folder = encode_imap_utf7(folder);
-        sess.select(&encoded_folder)
-            .await
-            .map_err(|e| format!("Failed to select folder: {}", e))?;
+        sess.ensure_selected(folder).await?;
 
         let uid_str = uids_to_sequence(uids);
         let flags_str = format!("+FLAGS ({})", flags.join(" "));
@@ -835,21 +1683,17 @@ impl ImapClient {
     }
 
     pub async fn bulk_remove_flags(&self, folder: &str, uids: &[u32], flags: &[&str]) -> Result<(), String> {
+        self.retry(|| self.try_bulk_remove_flags(folder, uids, flags)).await
+    }
+
+    async fn try_bulk_remove_flags(&self, folder: &str, uids: &[u32], flags: &[&str]) -> Result<(), String> {
         if uids.is_empty() {
             return Ok(());
         }
 
-        let session = self
-            .session
-            .as_ref()
-            .ok_or("Not connected")?;
-
-        let mut sess = session.lock().await;
+        let mut sess = self.session(Some(folder)).await?;
 
-        let encoded_folder = encode_imap_utf7(folder);
-        sess.select(&encoded_folder)
-            .await
-            .map_err(|e| format!("Failed to select folder: {}", e))?;
+        sess.ensure_selected(folder).await?;
 
         let uid_str = uids_to_sequence(uids);
         let flags_str = format!("-FLAGS ({})", flags.join(" "));
@@ -863,8 +1707,650 @@ impl ImapClient {
     }
 }
 
+// Attributes imap-proto has no variant for, e.g. \Subscribed (RFC 5258)
+fn has_attribute(attributes: &[NameAttribute<'_>], name: &str) -> bool {
+    attributes.iter().any(|attribute| match attribute {
+        NameAttribute::Extension(extension) => extension.eq_ignore_ascii_case(name),
+        _ => false,
+    })
+}
+
+// STORAGE is counted in units of 1024 octets (RFC 9208 section 5.1)
+fn imap_quota(quota: &imap_proto::types::Quota) -> Quota {
+    let mut result = Quota {
+        name: quota.root_name.to_string(),
+        storage: None,
+        messages: None,
+    };
+    for resource in &quota.resources {
+        match resource.name {
+            QuotaResourceName::Storage => {
+                result.storage = Some(QuotaUsage {
+                    used: resource.usage * 1024,
+                    limit: resource.limit * 1024,
+                });
+            }
+            QuotaResourceName::Message => {
+                result.messages = Some(QuotaUsage {
+                    used: resource.usage,
+                    limit: resource.limit,
+                });
+            }
+            QuotaResourceName::Atom(_) => {}
+        }
+    }
+    result
+}
+
+// Remove exactly the given messages from the selected folder. Without UID EXPUNGE (UIDPLUS)
+// a plain EXPUNGE would also remove messages other clients flagged \Deleted, so those lose
+// the flag for the moment of the EXPUNGE (RFC 4315 section 4).
+async fn expunge_uids(sess: &mut ImapSession, uids: &[u32], uidplus: bool) -> Result<(), String> {
+    let uid_str = uids_to_sequence(uids);
+    let _: Vec<_> = sess.uid_store(&uid_str, "+FLAGS.SILENT (\\Deleted)")
+        .await
+        .map_err(|e| format!("Failed to mark deleted: {}", e))?
+        .collect()
+        .await;
+
+    if uidplus {
+        let _: Vec<_> = sess.uid_expunge(&uid_str)
+            .await
+            .map_err(|e| format!("Failed to expunge: {}", e))?
+            .collect()
+            .await;
+        return Ok(());
+    }
+
+    let flagged = sess
+        .uid_search("DELETED")
+        .await
+        .map_err(|e| format!("Failed to search deleted messages: {}", e))?;
+    let others: Vec<u32> = flagged.into_iter().filter(|uid| !uids.contains(uid)).collect();
+    let others = uids_to_sequence(&others);
+
+    if !others.is_empty() {
+        let _: Vec<_> = sess.uid_store(&others, "-FLAGS.SILENT (\\Deleted)")
+            .await
+            .map_err(|e| format!("Failed to keep other messages: {}", e))?
+            .collect()
+            .await;
+    }
+
+    let expunged: Result<Vec<_>, String> = match sess.expunge().await {
+        Ok(stream) => Ok(stream.collect().await),
+        Err(e) => Err(format!("Failed to expunge: {}", e)),
+    };
+
+    if !others.is_empty() {
+        let _: Vec<_> = sess.uid_store(&others, "+FLAGS.SILENT (\\Deleted)")
+            .await
+            .map_err(|e| format!("Failed to restore deleted flags: {}", e))?
+            .collect()
+            .await;
+    }
+
+    expunged.map(|_| ())
+}
+
+// Open a session for an ImapClient: authenticated, capabilities read and QRESYNC enabled
+pub async fn establish_session(account: &MailAccount) -> Result<(ImapSession, Capabilities), String> {
+    let (mut session, compress) = connect(account).await?;
+
+    let capabilities = session
+        .capabilities()
+        .await
+        .map_err(|e| format!("Failed to read capabilities: {}", e))?;
+
+    // Everything after the server's OK is compressed, including the ENABLE below (RFC 4978)
+    if account.compress && capabilities.has_str("COMPRESS=DEFLATE") {
+        compress.arm();
+        session
+            .run_command_and_check_ok("COMPRESS DEFLATE")
+            .await
+            .map_err(|e| format!("Failed to enable compression: {}", e))?;
+    }
+
+    // QRESYNC has to be enabled once per session, before the first SELECT (RFC 7162)
+    if capabilities.has_str("QRESYNC") {
+        session
+            .run_command_and_check_ok("ENABLE QRESYNC")
+            .await
+            .map_err(|e| format!("Failed to enable QRESYNC: {}", e))?;
+    }
+
+    Ok((session, capabilities))
+}
+
+// Open a new authenticated IMAP session for the account
+pub async fn open_session(account: &MailAccount) -> Result<ImapSession, String> {
+    connect(account).await.map(|(session, _)| session)
+}
+
+// The session, with the switch that turns on compression for its stream
+async fn connect(account: &MailAccount) -> Result<(ImapSession, CompressSwitch), String> {
+    let addr = format!("{}:{}", account.imap_host, account.imap_port);
+    let addr = &addr;
+    let security = account.imap_security_mode();
+
+    // The compat layer adapts tokio streams to futures_io for async-imap
+    let stream = match security {
+        SecurityMode::None => {
+            println!("[IMAP] Connecting to {} without encryption", addr);
+            ImapStream::plain(connect_tcp(addr).await?.compat())
+        }
+        SecurityMode::Tls | SecurityMode::StartTls => {
+            // Self-signed certificates are only accepted if pinned
+            let tls_stream = trust::connect_tls(&account.username, &account.imap_host, account.imap_port, move || async move {
+                let tcp_stream = connect_tcp(addr).await?;
+                let tcp_stream = if security == SecurityMode::StartTls {
+                    starttls(tcp_stream).await?
+                } else {
+                    tcp_stream
+                };
+                Ok(tcp_stream.compat())
+            })
+            .await?;
+            ImapStream::tls(tls_stream)
+        }
+    };
+
+    // Create IMAP client
+    let compress = stream.compress_switch();
+    let client = async_imap::Client::new(stream);
+
+    let session = match account.oauth {
+        Some(ref config) => {
+            let token = oauth::access_token(&account.username, config).await?;
+            let authenticator = OAuthAuthenticator {
+                response: Some(oauth::sasl_response(
+                    config.mechanism,
+                    &account.username,
+                    &account.imap_host,
+                    account.imap_port,
+                    &token,
+                )),
+            };
+
+            client
+                .authenticate(config.mechanism.as_str(), authenticator)
+                .await
+                .map_err(|(e, _)| format!("OAuth authentication failed: {}", e))
+        }
+        None => client
+            .login(&account.username, &account.password)
+            .await
+            .map_err(|(e, _)| format!("Login failed: {}", e)),
+    }?;
+    Ok((session, compress))
+}
+
+// Offline, a connect can hang for the OS timeout (often over a minute) instead of failing
+const CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(15);
+
+async fn connect_tcp(addr: &str) -> Result<TcpStream, String> {
+    tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(addr))
+        .await
+        .map_err(|_| format!("TCP connection to {} timed out", addr))?
+        .map_err(|e| format!("TCP connection failed: {}", e))
+}
+
+// Read the greeting and run STARTTLS on the plain connection (RFC 3501 6.2.1).
+// The server sends no new greeting after the TLS handshake.
+async fn starttls(stream: TcpStream) -> Result<TcpStream, String> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+
+    reader
+        .read_line(&mut line)
+        .await
+        .map_err(|e| format!("Failed to read IMAP greeting: {}", e))?;
+    if !line.starts_with("* OK") {
+        return Err(format!("Unexpected IMAP greeting: {}", line.trim()));
+    }
+
+    reader
+        .get_mut()
+        .write_all(b"S1 STARTTLS\r\n")
+        .await
+        .map_err(|e| format!("Failed to send STARTTLS: {}", e))?;
+
+    loop {
+        line.clear();
+        let read = reader
+            .read_line(&mut line)
+            .await
+            .map_err(|e| format!("Failed to read STARTTLS response: {}", e))?;
+        if read == 0 {
+            return Err("IMAP server closed the connection".to_string());
+        }
+        if line.starts_with("S1 OK") {
+            return Ok(reader.into_inner());
+        }
+        if line.starts_with("S1 ") {
+            return Err(format!("STARTTLS failed: {}", line.trim()));
+        }
+    }
+}
+
+// Sends the bearer token once. Error challenges are answered with an empty response
+// so the server can finish with NO.
+struct OAuthAuthenticator {
+    response: Option<String>,
+}
+
+impl async_imap::Authenticator for OAuthAuthenticator {
+    type Response = String;
+
+    fn process(&mut self, _challenge: &[u8]) -> Self::Response {
+        self.response.take().unwrap_or_default()
+    }
+}
+
+// Sync on a pooled session, for callers that don't wait for it
+async fn sync_in_background(
+    pool: Arc<SessionPool>,
+    account_id: &str,
+    folder: &str,
+    mode: SyncMode,
+) -> Result<(), String> {
+    let mut sess = pool.acquire(Some(folder)).await?;
+    sess.set_selected(None);
+    sync::sync_to_cache(&mut sess, account_id, folder, mode).await?;
+    sess.set_selected(Some(folder));
+    Ok(())
+}
+
+// Parse the FLAGS of a fetched message into (is_read, is_flagged, is_answered, is_draft, flags)
+fn parse_flags(msg: &Fetch) -> (bool, bool, bool, bool, Vec<String>) {
+    let mut is_read = false;
+    let mut is_flagged = false;
+    let mut is_answered = false;
+    let mut is_draft = false;
+    let mut flags_list: Vec<String> = Vec::new();
+
+    for flag in msg.flags() {
+        match flag {
+            async_imap::types::Flag::Seen => {
+                is_read = true;
+                flags_list.push("\\Seen".to_string());
+            }
+            async_imap::types::Flag::Flagged => {
+                is_flagged = true;
+                flags_list.push("\\Flagged".to_string());
+            }
+            async_imap::types::Flag::Answered => {
+                is_answered = true;
+                flags_list.push("\\Answered".to_string());
+            }
+            async_imap::types::Flag::Draft => {
+                is_draft = true;
+                flags_list.push("\\Draft".to_string());
+            }
+            async_imap::types::Flag::Deleted => {
+                flags_list.push("\\Deleted".to_string());
+            }
+            async_imap::types::Flag::Recent => {
+                flags_list.push("\\Recent".to_string());
+            }
+            async_imap::types::Flag::Custom(ref s) => {
+                flags_list.push(s.to_string());
+            }
+            _ => {}
+        }
+    }
+
+    (is_read, is_flagged, is_answered, is_draft, flags_list)
+}
+
+// A flag as sent on the wire, \* for PERMANENTFLAGS allowing new keywords
+pub(crate) fn flag_name(flag: &Flag<'_>) -> Option<String> {
+    let name = match flag {
+        Flag::Seen => "\\Seen",
+        Flag::Answered => "\\Answered",
+        Flag::Flagged => "\\Flagged",
+        Flag::Deleted => "\\Deleted",
+        Flag::Draft => "\\Draft",
+        Flag::Recent => "\\Recent",
+        Flag::MayCreate => "\\*",
+        Flag::Custom(name) => return Some(name.to_string()),
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+// Build a list entry from a FETCH HEADER_FETCH_ITEMS response
+pub(crate) fn parse_header(msg: &Fetch) -> EmailHeader {
+    let uid = msg.uid.unwrap_or(0);
+    let (is_read, is_flagged, is_answered, is_draft, flags) = parse_flags(msg);
+
+    let envelope = msg.envelope();
+    let (subject, from, to, date) = if let Some(env) = envelope {
+        let subject = env
+            .subject
+            .as_ref()
+            .map(|s| decode_header_value(s))
+            .unwrap_or_default();
+
+        let from = env
+            .from
+            .as_ref()
+            .and_then(|addrs| addrs.first())
+            .map(format_address)
+            .unwrap_or_default();
+
+        let to = env
+            .to
+            .as_ref()
+            .and_then(|addrs| addrs.first())
+            .map(format_address)
+            .unwrap_or_default();
+
+        let date = env
+            .date
+            .as_ref()
+            .map(|d| String::from_utf8_lossy(d).to_string())
+            .unwrap_or_default();
+
+        (subject, from, to, date)
+    } else {
+        (String::new(), String::new(), String::new(), String::new())
+    };
+
+    let message_id = envelope
+        .and_then(|env| env.message_id.as_ref())
+        .and_then(|id| message_ids(&String::from_utf8_lossy(id)).into_iter().next());
+    let in_reply_to = envelope
+        .and_then(|env| env.in_reply_to.as_ref())
+        .and_then(|id| message_ids(&String::from_utf8_lossy(id)).into_iter().next());
+    let references = msg
+        .header()
+        .and_then(|raw| mailparse::parse_headers(raw).ok())
+        .and_then(|(headers, _)| headers.get_first_value("References"))
+        .map(|value| message_ids(&value))
+        .unwrap_or_default();
+
+    let mut summary = StructureSummary::default();
+    if let Some(structure) = msg.bodystructure() {
+        summarize_structure(structure, "", &mut summary);
+    }
+
+    EmailHeader {
+        uid,
+        subject,
+        from,
+        to,
+        date,
+        is_read,
+        is_flagged,
+        is_answered,
+        is_draft,
+        flags,
+        has_attachments: !summary.attachments.is_empty(),
+        part_count: summary.part_count,
+        size: summary.size,
+        attachments: summary.attachments,
+        message_id,
+        in_reply_to,
+        references,
+    }
+}
+
+// The <...> ids of a Message-ID, In-Reply-To or References header
+pub(crate) fn message_ids(value: &str) -> Vec<String> {
+    let mut ids = Vec::new();
+    let mut rest = value;
+    while let Some(start) = rest.find('<') {
+        let Some(len) = rest[start..].find('>') else {
+            break;
+        };
+        let id = rest[start..=start + len].trim();
+        if id.len() > 2 {
+            ids.push(id.to_string());
+        }
+        rest = &rest[start + len + 1..];
+    }
+    ids
+}
+
+#[derive(Default)]
+struct StructureSummary {
+    part_count: u32,
+    size: u32,
+    attachments: Vec<Attachment>,
+}
+
+// Walk a BODYSTRUCTURE tree. Part ids follow the IMAP section numbering (RFC 3501 6.4.5),
+// so they can be passed to get_attachment as they are.
+fn summarize_structure(structure: &BodyStructure, parent_id: &str, summary: &mut StructureSummary) {
+    let (common, other) = match structure {
+        BodyStructure::Multipart { bodies, .. } => {
+            for (idx, body) in bodies.iter().enumerate() {
+                summarize_structure(body, &child_part_id(parent_id, idx), summary);
+            }
+            return;
+        }
+        BodyStructure::Basic { common, other, .. }
+        | BodyStructure::Text { common, other, .. }
+        | BodyStructure::Message { common, other, .. } => (common, other),
+    };
+
+    // A single part message is section 1
+    let part_id = if parent_id.is_empty() { "1" } else { parent_id };
+
+    summary.part_count += 1;
+    summary.size = summary.size.saturating_add(other.octets);
+
+    let mime_type = format!("{}/{}", common.ty.ty, common.ty.subtype).to_lowercase();
+    let disposition = common
+        .disposition
+        .as_ref()
+        .map(|d| d.ty.to_lowercase())
+        .unwrap_or_default();
+
+    // Explicit attachments, and everything that is neither text nor shown inline.
+    // Parts with a Content-ID are images embedded in the HTML body.
+    let is_attachment = match disposition.as_str() {
+        "attachment" => true,
+        "inline" => false,
+        _ => !common.ty.ty.eq_ignore_ascii_case("text") && other.id.is_none(),
+    };
+    if !is_attachment {
+        return;
+    }
+
+    let filename = common
+        .disposition
+        .as_ref()
+        .and_then(|d| body_param(&d.params, "filename"))
+        .or_else(|| body_param(&common.ty.params, "name"))
+        .map(|name| decode_rfc2047(&name))
+        .unwrap_or_else(|| "unnamed".to_string());
+
+    summary.attachments.push(Attachment {
+        filename,
+        mime_type,
+        size: other.octets as usize,
+        part_id: part_id.to_string(),
+        encoding: transfer_encoding_name(&other.transfer_encoding),
+    });
+}
+
+// Parameter of a Content-Type or Content-Disposition, RFC 2231 encoded values (name*=) included
+fn body_param(params: &BodyParams, name: &str) -> Option<String> {
+    let params = params.as_ref()?;
+
+    if let Some((_, value)) = params.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)) {
+        return Some(value.to_string());
+    }
+
+    let extended = format!("{}*", name);
+    params
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(&extended))
+        .map(|(_, value)| decode_rfc2231(value))
+}
+
+// charset'language'percent-encoded-value, only UTF-8 and ASCII are expected in practice
+fn decode_rfc2231(value: &str) -> String {
+    let encoded = value.splitn(3, '\'').nth(2).unwrap_or(value);
+
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut chars = encoded.bytes();
+    while let Some(b) = chars.next() {
+        if b == b'%' {
+            let hex: Vec<u8> = chars.by_ref().take(2).collect();
+            match std::str::from_utf8(&hex).ok().and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                Some(decoded) => bytes.push(decoded),
+                None => {
+                    bytes.push(b'%');
+                    bytes.extend(hex);
+                }
+            }
+        } else {
+            bytes.push(b);
+        }
+    }
+
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+fn transfer_encoding_name(encoding: &ContentEncoding) -> String {
+    match encoding {
+        ContentEncoding::SevenBit => "7bit".to_string(),
+        ContentEncoding::EightBit => "8bit".to_string(),
+        ContentEncoding::Binary => "binary".to_string(),
+        ContentEncoding::Base64 => "base64".to_string(),
+        ContentEncoding::QuotedPrintable => "quoted-printable".to_string(),
+        ContentEncoding::Other(other) => other.to_lowercase(),
+    }
+}
+
+fn child_part_id(parent_id: &str, index: usize) -> String {
+    if parent_id.is_empty() {
+        format!("{}", index + 1)
+    } else {
+        format!("{}.{}", parent_id, index + 1)
+    }
+}
+
+// A text part to download for display, with what is needed to decode it
+struct TextPart {
+    part_id: String,
+    charset: String,
+    encoding: String,
+}
+
+impl TextPart {
+    fn decode(&self, msg: &Fetch) -> String {
+        let data = section_path(&self.part_id)
+            .and_then(|path| msg.section(&SectionPath::Part(path, None)))
+            .unwrap_or(&[]);
+
+        // mailparse undoes transfer encoding and charset once the part has its headers back
+        let mut raw = format!(
+            "Content-Type: text/plain; charset=\"{}\"\r\nContent-Transfer-Encoding: {}\r\n\r\n",
+            self.charset, self.encoding
+        )
+        .into_bytes();
+        raw.extend_from_slice(data);
+
+        parse_mail(&raw)
+            .and_then(|part| part.get_body())
+            .unwrap_or_else(|_| String::from_utf8_lossy(data).to_string())
+    }
+}
+
+#[derive(Default)]
+struct BodyParts {
+    text: Option<TextPart>,
+    html: Option<TextPart>,
+}
+
+// The first text/plain and text/html part that is not an attachment. Attached messages
+// are not looked into, they are attachments of their own.
+fn find_body_parts(structure: &BodyStructure, parent_id: &str, parts: &mut BodyParts) {
+    let (common, other) = match structure {
+        BodyStructure::Multipart { bodies, .. } => {
+            for (idx, body) in bodies.iter().enumerate() {
+                find_body_parts(body, &child_part_id(parent_id, idx), parts);
+            }
+            return;
+        }
+        BodyStructure::Text { common, other, .. } => (common, other),
+        BodyStructure::Basic { .. } | BodyStructure::Message { .. } => return,
+    };
+
+    let is_attachment = common
+        .disposition
+        .as_ref()
+        .is_some_and(|d| d.ty.eq_ignore_ascii_case("attachment"));
+    if is_attachment {
+        return;
+    }
+
+    let slot = if common.ty.subtype.eq_ignore_ascii_case("plain") {
+        &mut parts.text
+    } else if common.ty.subtype.eq_ignore_ascii_case("html") {
+        &mut parts.html
+    } else {
+        return;
+    };
+    if slot.is_some() {
+        return;
+    }
+
+    let charset = body_param(&common.ty.params, "charset")
+        .filter(|charset| charset.chars().all(|c| c.is_ascii_alphanumeric() || "-_.:".contains(c)))
+        .unwrap_or_else(|| "us-ascii".to_string());
+
+    *slot = Some(TextPart {
+        part_id: if parent_id.is_empty() { "1".to_string() } else { parent_id.to_string() },
+        charset,
+        encoding: transfer_encoding_name(&other.transfer_encoding),
+    });
+}
+
+// The part with the given section number, e.g. "2.1"
+fn find_part<'a>(structure: &'a BodyStructure<'a>, part_id: &str) -> Option<&'a BodyStructure<'a>> {
+    let mut current = structure;
+    for (depth, index) in part_id.split('.').enumerate() {
+        let index: usize = index.parse().ok()?;
+
+        // Parts of an attached message are numbered within that message
+        let container = match current {
+            BodyStructure::Message { body, .. } if depth > 0 => body.as_ref(),
+            other => other,
+        };
+        current = match container {
+            BodyStructure::Multipart { bodies, .. } => bodies.get(index.checked_sub(1)?)?,
+            // A part that is not multipart is its own part 1
+            single if index == 1 => single,
+            _ => return None,
+        };
+    }
+    Some(current)
+}
+
+// "2.1" -> [2, 1]
+fn section_path(part_id: &str) -> Option<Vec<u32>> {
+    part_id
+        .split('.')
+        .map(|index| index.parse().ok().filter(|&index| index > 0))
+        .collect()
+}
+
+fn decode_transfer_encoding(data: &[u8], encoding: &str) -> Vec<u8> {
+    let mut raw = format!("Content-Transfer-Encoding: {}\r\n\r\n", encoding).into_bytes();
+    raw.extend_from_slice(data);
+
+    parse_mail(&raw)
+        .and_then(|part| part.get_body_raw())
+        .unwrap_or_else(|_| data.to_vec())
+}
+
 // Helper function to convert UID array to IMAP sequence string (e.g., "1,2,3,5:10")
-fn uids_to_sequence(uids: &[u32]) -> String {
+pub(crate) fn uids_to_sequence(uids: &[u32]) -> String {
     if uids.is_empty() {
         return String::new();
     }
@@ -1056,111 +2542,12 @@ fn format_address(addr: &imap_proto::types::Address) -> String {
     }
 }
 
-fn extract_body(mail: &mailparse::ParsedMail) -> (String, String) {
-    let mut text_body = String::new();
-    let mut html_body = String::new();
-
-    if mail.subparts.is_empty() {
-        let content_type = mail.ctype.mimetype.to_lowercase();
-        let body = mail.get_body().unwrap_or_default();
-
-        if content_type.contains("text/html") {
-            html_body = body;
-        } else {
-            text_body = body;
-        }
-    } else {
-        for part in &mail.subparts {
-            let (t, h) = extract_body(part);
-            if !t.is_empty() && text_body.is_empty() {
-                text_body = t;
-            }
-            if !h.is_empty() && html_body.is_empty() {
-                html_body = h;
-            }
-        }
-    }
-
-    (text_body, html_body)
-}
-
-fn extract_attachments(mail: &mailparse::ParsedMail) -> Vec<Attachment> {
-    extract_attachments_with_path(mail, "")
-}
-
-fn extract_attachments_with_path(mail: &mailparse::ParsedMail, parent_path: &str) -> Vec<Attachment> {
-    let mut attachments = Vec::new();
-
-    for (idx, part) in mail.subparts.iter().enumerate() {
-        // Build IMAP part path (1-indexed)
-        let part_id = if parent_path.is_empty() {
-            format!("{}", idx + 1)
-        } else {
-            format!("{}.{}", parent_path, idx + 1)
-        };
-
-        let disposition = part
-            .headers
-            .iter()
-            .find(|h| h.get_key().to_lowercase() == "content-disposition");
-
-        // Get content-transfer-encoding
-        let encoding = part
-            .headers
-            .iter()
-            .find(|h| h.get_key().to_lowercase() == "content-transfer-encoding")
-            .map(|h| h.get_value().to_lowercase())
-            .unwrap_or_else(|| "7bit".to_string());
-
-        if let Some(disp) = disposition {
-            let value = disp.get_value();
-            if value.to_lowercase().starts_with("attachment") {
-                // Try to get filename from Content-Disposition first
-                let mut filename = disp.get_value()
-                    .split(';')
-                    .find(|p| p.trim().to_lowercase().starts_with("filename="))
-                    .map(|p| {
-                        let val = p.trim()[9..].trim();
-                        val.trim_matches('"').to_string()
-                    });
-
-                // Fallback to Content-Type name parameter
-                if filename.is_none() {
-                    filename = part
-                        .headers
-                        .iter()
-                        .find(|h| h.get_key().to_lowercase() == "content-type")
-                        .and_then(|h| {
-                            h.get_value()
-                                .split(';')
-                                .find(|p| p.trim().to_lowercase().starts_with("name="))
-                                .map(|p| p.trim()[5..].trim_matches('"').to_string())
-                        });
-                }
-
-                attachments.push(Attachment {
-                    filename: filename.unwrap_or_else(|| "attachment".to_string()),
-                    mime_type: part.ctype.mimetype.clone(),
-                    size: part.get_body_raw().map(|b| b.len()).unwrap_or(0),
-                    part_id: part_id.clone(),
-                    encoding: encoding.clone(),
-                });
-            }
-        }
-
-        // Recurse into subparts
-        attachments.extend(extract_attachments_with_path(part, &part_id));
-    }
-
-    attachments
-}
-
 fn sanitize_html(html: &str) -> String {
     ammonia::clean(html)
 }
 
 // Decode IMAP Modified UTF-7 folder names (RFC 3501)
-fn decode_imap_utf7(input: &str) -> String {
+pub(crate) fn decode_imap_utf7(input: &str) -> String {
     let mut result = String::new();
     let mut chars = input.chars().peekable();
 
@@ -1231,7 +2618,7 @@ fn decode_imap_utf7(input: &str) -> String {
 }
 
 // Encode folder name to IMAP Modified UTF-7 (RFC 3501)
-fn encode_imap_utf7(input: &str) -> String {
+pub(crate) fn encode_imap_utf7(input: &str) -> String {
     let mut result = String::new();
     let mut non_ascii = String::new();
 
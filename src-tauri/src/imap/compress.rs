@@ -0,0 +1,247 @@
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+use std::io;
+
+// Raw DEFLATE in both directions of a session after COMPRESS DEFLATE (RFC 4978).
+// Every write is sync-flushed so the server can act on a command right away.
+#[derive(Debug)]
+pub struct Deflate {
+    compress: Compress,
+    decompress: Decompress,
+}
+
+impl Default for Deflate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deflate {
+    pub fn new() -> Self {
+        Self {
+            compress: Compress::new(Compression::default(), false),
+            decompress: Decompress::new(false),
+        }
+    }
+
+    pub fn deflate(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+        let mut consumed = 0;
+        loop {
+            output.reserve(input.len() - consumed + 64);
+            let before = self.compress.total_in();
+            self.compress
+                .compress_vec(&input[consumed..], output, FlushCompress::Sync)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            consumed += (self.compress.total_in() - before) as usize;
+
+            // The flush is complete once zlib leaves room in the output
+            if consumed == input.len() && output.len() < output.capacity() {
+                return Ok(());
+            }
+        }
+    }
+
+    // zlib can take input without producing output (block headers, the empty block of a
+    // sync flush), so only a call that makes no progress at all is an error
+    pub fn inflate(&mut self, input: &[u8]) -> io::Result<Vec<u8>> {
+        let mut output = Vec::with_capacity(input.len() * 4 + 64);
+        let mut consumed = 0;
+        loop {
+            let before = self.decompress.total_in();
+            let produced = output.len();
+            let status = self
+                .decompress
+                .decompress_vec(&input[consumed..], &mut output, FlushDecompress::None)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            let read = (self.decompress.total_in() - before) as usize;
+            consumed += read;
+
+            if status == Status::StreamEnd {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "Compressed stream ended"));
+            }
+            let room_left = output.len() < output.capacity();
+            if room_left && consumed == input.len() {
+                return Ok(output);
+            }
+            if room_left && read == 0 && output.len() == produced {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "Compressed data could not be inflated"));
+            }
+            if !room_left {
+                output.reserve(output.capacity());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imap::append::append;
+    use crate::imap::client::{establish_session, MailAccount};
+    use futures::StreamExt;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    // Lines that look like responses, which must pass through a literal unchanged
+    const BODY: &[u8] = b"Subject: test\r\n\r\nA9 OK inside the literal\r\n* THREAD (1)\r\n";
+    // Not valid UTF-8
+    const DRAFT: &[u8] = b"Subject: caf\xe9\r\n\r\nna\xefve\r\n";
+
+    // Server end of the loopback connection, compressing once COMPRESS is accepted
+    struct Server {
+        stream: TcpStream,
+        deflate: Option<Deflate>,
+        received: Vec<u8>,
+    }
+
+    impl Server {
+        async fn receive(&mut self) {
+            let mut chunk = [0u8; 4096];
+            let read = self.stream.read(&mut chunk).await.unwrap();
+            assert!(read > 0, "client closed the connection");
+            match self.deflate.as_mut() {
+                Some(deflate) => self.received.extend(deflate.inflate(&chunk[..read]).unwrap()),
+                None => self.received.extend_from_slice(&chunk[..read]),
+            }
+        }
+
+        async fn read_line(&mut self) -> String {
+            loop {
+                if let Some(end) = self.received.windows(2).position(|pair| pair == b"\r\n") {
+                    let line: Vec<u8> = self.received.drain(..end + 2).collect();
+                    return String::from_utf8(line).unwrap();
+                }
+                self.receive().await;
+            }
+        }
+
+        async fn read_bytes(&mut self, len: usize) -> Vec<u8> {
+            while self.received.len() < len {
+                self.receive().await;
+            }
+            self.received.drain(..len).collect()
+        }
+
+        // Returns the tag of the command
+        async fn expect(&mut self, command: &str) -> String {
+            let line = self.read_line().await;
+            let (tag, rest) = line.trim_end().split_once(' ').unwrap();
+            assert!(rest.starts_with(command), "expected {}, got {}", command, line);
+            tag.to_string()
+        }
+
+        async fn send(&mut self, data: &[u8]) {
+            match self.deflate.as_mut() {
+                Some(deflate) => {
+                    let mut compressed = Vec::new();
+                    deflate.deflate(data, &mut compressed).unwrap();
+                    self.stream.write_all(&compressed).await.unwrap();
+                }
+                None => self.stream.write_all(data).await.unwrap(),
+            }
+        }
+    }
+
+    async fn serve(listener: TcpListener) {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut server = Server {
+            stream,
+            deflate: None,
+            received: Vec::new(),
+        };
+        server.send(b"* OK test server ready\r\n").await;
+
+        let tag = server.expect("LOGIN").await;
+        server.send(format!("{} OK logged in\r\n", tag).as_bytes()).await;
+
+        let tag = server.expect("CAPABILITY").await;
+        server
+            .send(format!("* CAPABILITY IMAP4rev1 COMPRESS=DEFLATE\r\n{} OK done\r\n", tag).as_bytes())
+            .await;
+
+        let tag = server.expect("COMPRESS DEFLATE").await;
+        server.send(format!("{} OK DEFLATE active\r\n", tag).as_bytes()).await;
+        server.deflate = Some(Deflate::new());
+
+        let tag = server.expect("SELECT").await;
+        server
+            .send(
+                format!(
+                    "* 1 EXISTS\r\n* FLAGS (\\Seen)\r\n* OK [UIDVALIDITY 7] ok\r\n{} OK [READ-WRITE] done\r\n",
+                    tag
+                )
+                .as_bytes(),
+            )
+            .await;
+
+        // The literal is split over two flushes
+        let tag = server.expect("UID FETCH").await;
+        let (first, second) = BODY.split_at(20);
+        let mut head = format!("* 1 FETCH (UID 1 BODY[] {{{}}}\r\n", BODY.len()).into_bytes();
+        head.extend_from_slice(first);
+        server.send(&head).await;
+        let mut tail = second.to_vec();
+        tail.extend_from_slice(format!(")\r\n{} OK done\r\n", tag).as_bytes());
+        server.send(&tail).await;
+
+        let line = server.read_line().await;
+        let (tag, command) = line.trim_end().split_once(' ').unwrap();
+        assert_eq!(command, format!("APPEND \"Drafts\" (\\Draft) {{{}}}", DRAFT.len()));
+        let tag = tag.to_string();
+        server.send(b"+ go ahead\r\n").await;
+        assert_eq!(server.read_bytes(DRAFT.len() + 2).await, [DRAFT, b"\r\n"].concat());
+        server.send(format!("{} OK [APPENDUID 7 42] done\r\n", tag).as_bytes()).await;
+    }
+
+    #[test]
+    fn inflate_takes_all_input() {
+        let mut deflate = Deflate::new();
+        let mut compressed = Vec::new();
+        deflate.deflate(BODY, &mut compressed).unwrap();
+        deflate.deflate(b"", &mut compressed).unwrap();
+
+        // Byte by byte most calls produce nothing, none may lose input
+        let mut inflated = Vec::new();
+        for byte in &compressed {
+            inflated.extend(deflate.inflate(std::slice::from_ref(byte)).unwrap());
+        }
+        assert_eq!(inflated, BODY);
+    }
+
+    #[tokio::test]
+    async fn session_after_compress() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(serve(listener));
+
+        let account: MailAccount = serde_json::from_value(serde_json::json!({
+            "imapHost": "127.0.0.1",
+            "imapPort": port,
+            "smtpHost": "127.0.0.1",
+            "smtpPort": 25,
+            "username": "user",
+            "password": "secret",
+            "displayName": "User",
+            "imapSecurity": "none",
+            "compress": true,
+        }))
+        .unwrap();
+
+        let (mut session, capabilities) = establish_session(&account).await.unwrap();
+        assert!(capabilities.has_str("COMPRESS=DEFLATE"));
+
+        let mailbox = session.select("INBOX").await.unwrap();
+        assert_eq!(mailbox.exists, 1);
+        assert_eq!(mailbox.uid_validity, Some(7));
+
+        let fetches: Vec<_> = session.uid_fetch("1", "BODY[]").await.unwrap().collect().await;
+        assert_eq!(fetches.len(), 1);
+        let fetch = fetches.into_iter().next().unwrap().unwrap();
+        assert_eq!(fetch.uid, Some(1));
+        assert_eq!(fetch.body(), Some(BODY));
+
+        let uid = append(&mut session, "Drafts", &["\\Draft"], None, DRAFT).await.unwrap();
+        assert_eq!(uid, Some(42));
+
+        server.await.unwrap();
+    }
+}
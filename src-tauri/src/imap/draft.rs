@@ -0,0 +1,123 @@
+use base64::Engine;
+use mailparse::{addrparse_header, parse_mail, DispositionType, MailAddr, MailHeaderMap, ParsedMail};
+use serde::{Deserialize, Serialize};
+
+use crate::smtp::client::{OutgoingAttachment, OutgoingEmail};
+
+// Where a draft was saved. The UID is unknown if the server has no UIDPLUS and the
+// message could not be found again by its Message-ID.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedDraft {
+    pub folder: String,
+    pub uid: Option<u32>,
+}
+
+// Turn a stored draft back into an email to compose, whichever client saved it
+pub fn parse_draft(raw: &[u8]) -> Result<OutgoingEmail, String> {
+    let parsed = parse_mail(raw).map_err(|e| format!("Failed to parse draft: {}", e))?;
+
+    let addresses = |name: &str| -> Vec<String> {
+        parsed
+            .headers
+            .get_all_headers(name)
+            .into_iter()
+            .filter_map(|header| addrparse_header(header).ok())
+            .flat_map(|list| list.iter().flat_map(format_addr).collect::<Vec<_>>())
+            .collect()
+    };
+
+    let mut email = OutgoingEmail {
+        to: addresses("To"),
+        cc: addresses("Cc"),
+        bcc: addresses("Bcc"),
+        subject: parsed.headers.get_first_value("Subject").unwrap_or_default(),
+        body_text: String::new(),
+        body_html: None,
+        reply_to_message_id: parsed
+            .headers
+            .get_first_value("In-Reply-To")
+            .map(|id| id.trim().to_string())
+            .filter(|id| !id.is_empty()),
+        attachments: None,
+    };
+
+    let mut text = None;
+    let mut attachments = Vec::new();
+    collect_parts(&parsed, &mut text, &mut email.body_html, &mut attachments)?;
+    email.body_text = text.unwrap_or_default();
+    if !attachments.is_empty() {
+        email.attachments = Some(attachments);
+    }
+
+    Ok(email)
+}
+
+// The first text/plain and text/html part that is not an attachment make up the body,
+// every other leaf part is an attachment
+fn collect_parts(
+    part: &ParsedMail,
+    text: &mut Option<String>,
+    html: &mut Option<String>,
+    attachments: &mut Vec<OutgoingAttachment>,
+) -> Result<(), String> {
+    if !part.subparts.is_empty() {
+        for subpart in &part.subparts {
+            collect_parts(subpart, text, html, attachments)?;
+        }
+        return Ok(());
+    }
+
+    let disposition = part.get_content_disposition();
+    let mime_type = part.ctype.mimetype.to_lowercase();
+    if disposition.disposition != DispositionType::Attachment {
+        let slot = match mime_type.as_str() {
+            "text/plain" => Some(&mut *text),
+            "text/html" => Some(&mut *html),
+            _ => None,
+        };
+        if let Some(slot) = slot.filter(|slot| slot.is_none()) {
+            let body = part
+                .get_body()
+                .map_err(|e| format!("Failed to decode draft body: {}", e))?;
+            *slot = Some(body);
+            return Ok(());
+        }
+    }
+
+    let data = part
+        .get_body_raw()
+        .map_err(|e| format!("Failed to decode draft attachment: {}", e))?;
+    let filename = disposition
+        .params
+        .get("filename")
+        .or_else(|| part.ctype.params.get("name"))
+        .cloned()
+        .unwrap_or_else(|| "attachment".to_string());
+
+    attachments.push(OutgoingAttachment {
+        filename,
+        mime_type,
+        data: base64::engine::general_purpose::STANDARD.encode(data),
+    });
+    Ok(())
+}
+
+// "Name <address>" as the composer and lettre expect it
+fn format_addr(addr: &MailAddr) -> Vec<String> {
+    let singles = match addr {
+        MailAddr::Single(info) => vec![info],
+        MailAddr::Group(group) => group.addrs.iter().collect(),
+    };
+
+    singles
+        .into_iter()
+        .map(|info| match info.display_name.as_deref() {
+            Some(name) if name.contains(|c: char| ",;:<>@\"()".contains(c)) => {
+                format!("\"{}\" <{}>", name.replace('\\', "\\\\").replace('"', "\\\""), info.addr)
+            }
+            Some(name) if !name.is_empty() => format!("{} <{}>", name, info.addr),
+            _ => info.addr.clone(),
+        })
+        .collect()
+}
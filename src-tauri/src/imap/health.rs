@@ -0,0 +1,91 @@
+use serde::Serialize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter};
+
+use super::client::{establish_session, ImapSession, MailAccount};
+
+pub const CONNECTION_STATE_EVENT: &str = "imap-connection-state";
+
+// Probe the session with NOOP before using it if it was idle this long
+pub const HEALTH_CHECK_AFTER_SECS: u64 = 60;
+
+// A dead connection often doesn't fail but just never answers
+const NOOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+const MAX_ATTEMPTS: u32 = 5;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionState {
+    Connected,
+    Reconnecting,
+    Offline,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionStateEvent {
+    pub account_id: String,
+    pub state: ConnectionState,
+    pub attempt: u32,
+    pub error: Option<String>,
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+pub async fn is_alive(session: &mut ImapSession) -> bool {
+    matches!(tokio::time::timeout(NOOP_TIMEOUT, session.noop()).await, Ok(Ok(_)))
+}
+
+pub fn emit_state(app: Option<&AppHandle>, account_id: &str, state: ConnectionState, attempt: u32, error: Option<String>) {
+    let Some(app) = app else {
+        return;
+    };
+
+    let event = ConnectionStateEvent {
+        account_id: account_id.to_string(),
+        state,
+        attempt,
+        error,
+    };
+    if let Err(e) = app.emit(CONNECTION_STATE_EVENT, event) {
+        println!("[IMAP] Failed to emit connection state: {}", e);
+    }
+}
+
+// Open a replacement session with exponential backoff, reporting progress as events
+pub async fn reconnect(app: Option<&AppHandle>, account: &MailAccount) -> Result<ImapSession, String> {
+    let mut delay = INITIAL_BACKOFF;
+    let mut last_error = String::new();
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        emit_state(app, &account.username, ConnectionState::Reconnecting, attempt, None);
+
+        match establish_session(account).await {
+            Ok((session, _)) => {
+                println!("[IMAP] Reconnected {} (attempt {})", account.username, attempt);
+                emit_state(app, &account.username, ConnectionState::Connected, attempt, None);
+                return Ok(session);
+            }
+            Err(e) => {
+                println!("[IMAP] Reconnect attempt {} for {} failed: {}", attempt, account.username, e);
+                last_error = e;
+            }
+        }
+
+        if attempt < MAX_ATTEMPTS {
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(MAX_BACKOFF);
+        }
+    }
+
+    emit_state(app, &account.username, ConnectionState::Offline, MAX_ATTEMPTS, Some(last_error.clone()));
+    Err(format!("Connection lost and reconnecting failed: {}", last_error))
+}
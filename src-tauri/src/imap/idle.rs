@@ -0,0 +1,240 @@
+use async_imap::extensions::idle::IdleResponse;
+use async_imap::types::{Mailbox, UnsolicitedResponse};
+use imap_proto::types::{AttributeValue, MailboxDatum, Response};
+use serde::Serialize;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tokio::task::JoinHandle;
+
+use super::client::{encode_imap_utf7, open_session, ImapSession, MailAccount};
+
+// Servers may drop an IDLE connection after 30 minutes (RFC 2177), so re-issue well before that
+const IDLE_TIMEOUT: Duration = Duration::from_secs(25 * 60);
+// Used for servers without the IDLE capability
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+// Wait before reconnecting after the watcher session failed
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+pub const MAILBOX_CHANGED_EVENT: &str = "imap-mailbox-changed";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MailboxChange {
+    Exists,
+    Expunge,
+    Flags,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MailboxEvent {
+    pub account_id: String,
+    pub folder: String,
+    pub change: MailboxChange,
+    // Message count for EXISTS, sequence number for EXPUNGE and FETCH. Polling reports the
+    // message count for both and 0 without flags for flag changes.
+    pub value: u32,
+    pub flags: Vec<String>,
+}
+
+// Background task watching one folder of an account on its own IMAP session.
+// The task (and with it the session) is stopped when the watcher is dropped.
+pub struct IdleWatcher {
+    task: JoinHandle<()>,
+}
+
+impl IdleWatcher {
+    pub fn spawn(app: AppHandle, account_id: String, account: MailAccount, folder: String) -> Self {
+        let task = tokio::spawn(run_watcher(app, account_id, account, folder));
+        Self { task }
+    }
+}
+
+impl Drop for IdleWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn run_watcher(app: AppHandle, account_id: String, account: MailAccount, folder: String) {
+    loop {
+        if let Err(e) = watch_folder(&app, &account_id, &account, &folder).await {
+            println!("[IDLE] Watcher for {} ({}) failed: {}", account_id, folder, e);
+        }
+
+        tokio::time::sleep(RETRY_DELAY).await;
+    }
+}
+
+async fn watch_folder(
+    app: &AppHandle,
+    account_id: &str,
+    account: &MailAccount,
+    folder: &str,
+) -> Result<(), String> {
+    let mut session = open_session(account).await?;
+
+    // EXAMINE keeps the watcher from touching \Recent or other session state
+    let encoded_folder = encode_imap_utf7(folder);
+    session
+        .examine(&encoded_folder)
+        .await
+        .map_err(|e| format!("Failed to examine folder: {}", e))?;
+
+    let capabilities = session
+        .capabilities()
+        .await
+        .map_err(|e| format!("Failed to read capabilities: {}", e))?;
+
+    if !capabilities.has_str("IDLE") {
+        return poll_folder(app, account_id, folder, session, capabilities.has_str("CONDSTORE")).await;
+    }
+
+    // Responses to EXAMINE are no changes
+    while session.unsolicited_responses.try_recv().is_ok() {}
+
+    loop {
+        let mut idle = session.idle();
+        idle.init()
+            .await
+            .map_err(|e| format!("Failed to start IDLE: {}", e))?;
+
+        // Keep the stop source alive, dropping it interrupts the wait
+        let (wait, _interrupt) = idle.wait_with_timeout(IDLE_TIMEOUT);
+        let response = wait
+            .await
+            .map_err(|e| format!("IDLE failed: {}", e))?;
+
+        session = idle
+            .done()
+            .await
+            .map_err(|e| format!("Failed to end IDLE: {}", e))?;
+
+        if let IdleResponse::NewData(data) = response {
+            if let Some(change) = parse_change(data.parsed()) {
+                emit_change(app, account_id, folder, change);
+            }
+        }
+
+        // The wait ends at the first response. The rest of the wake-up was read while ending
+        // IDLE (or arrived while starting it) and waits among the unsolicited responses.
+        while let Ok(response) = session.unsolicited_responses.try_recv() {
+            if let Some(change) = parse_unsolicited(&response) {
+                emit_change(app, account_id, folder, change);
+            }
+        }
+    }
+}
+
+// What a poll compares. UIDNEXT shows arrivals even when as many messages were expunged,
+// HIGHESTMODSEQ (with CONDSTORE) shows flag changes.
+struct PollState {
+    exists: u32,
+    uid_next: Option<u32>,
+    highest_modseq: Option<u64>,
+}
+
+impl PollState {
+    fn of(mailbox: &Mailbox) -> Self {
+        Self {
+            exists: mailbox.exists,
+            uid_next: mailbox.uid_next,
+            highest_modseq: mailbox.highest_modseq,
+        }
+    }
+}
+
+async fn poll_folder(
+    app: &AppHandle,
+    account_id: &str,
+    folder: &str,
+    mut session: ImapSession,
+    condstore: bool,
+) -> Result<(), String> {
+    let encoded_folder = encode_imap_utf7(folder);
+
+    // HIGHESTMODSEQ is only reported once CONDSTORE is enabled (RFC 7162 3.1)
+    if condstore {
+        session
+            .run_command_and_check_ok("ENABLE CONDSTORE")
+            .await
+            .map_err(|e| format!("Failed to enable CONDSTORE: {}", e))?;
+    }
+    let mailbox = session
+        .examine(&encoded_folder)
+        .await
+        .map_err(|e| format!("Failed to poll folder: {}", e))?;
+    let mut last = PollState::of(&mailbox);
+
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+
+        let mailbox = session
+            .examine(&encoded_folder)
+            .await
+            .map_err(|e| format!("Failed to poll folder: {}", e))?;
+        let current = PollState::of(&mailbox);
+
+        let arrived = match (last.uid_next, current.uid_next) {
+            (Some(before), Some(now)) => now > before,
+            _ => current.exists > last.exists,
+        };
+        // New mail without a higher count means something was expunged as well
+        let expunged = current.exists < last.exists || (arrived && current.exists == last.exists);
+
+        if arrived {
+            emit_change(app, account_id, folder, (MailboxChange::Exists, current.exists, Vec::new()));
+        }
+        if expunged {
+            emit_change(app, account_id, folder, (MailboxChange::Expunge, current.exists, Vec::new()));
+        }
+        // Arrivals and expunges raise the mod-sequence too, otherwise flags changed
+        if !arrived && !expunged && current.highest_modseq != last.highest_modseq {
+            emit_change(app, account_id, folder, (MailboxChange::Flags, 0, Vec::new()));
+        }
+        last = current;
+    }
+}
+
+fn parse_change(response: &Response<'_>) -> Option<(MailboxChange, u32, Vec<String>)> {
+    match response {
+        Response::MailboxData(MailboxDatum::Exists(count)) => {
+            Some((MailboxChange::Exists, *count, Vec::new()))
+        }
+        Response::Expunge(seq) => Some((MailboxChange::Expunge, *seq, Vec::new())),
+        Response::Fetch(seq, attributes) => {
+            let flags = attributes.iter().find_map(|attr| match attr {
+                AttributeValue::Flags(flags) => {
+                    Some(flags.iter().map(|f| f.to_string()).collect::<Vec<_>>())
+                }
+                _ => None,
+            })?;
+            Some((MailboxChange::Flags, *seq, flags))
+        }
+        _ => None,
+    }
+}
+
+fn parse_unsolicited(response: &UnsolicitedResponse) -> Option<(MailboxChange, u32, Vec<String>)> {
+    match response {
+        UnsolicitedResponse::Exists(count) => Some((MailboxChange::Exists, *count, Vec::new())),
+        UnsolicitedResponse::Expunge(seq) => Some((MailboxChange::Expunge, *seq, Vec::new())),
+        UnsolicitedResponse::Other(data) => parse_change(data.parsed()),
+        _ => None,
+    }
+}
+
+fn emit_change(app: &AppHandle, account_id: &str, folder: &str, change: (MailboxChange, u32, Vec<String>)) {
+    let (change, value, flags) = change;
+    let event = MailboxEvent {
+        account_id: account_id.to_string(),
+        folder: folder.to_string(),
+        change,
+        value,
+        flags,
+    };
+
+    if let Err(e) = app.emit(MAILBOX_CHANGED_EVENT, event) {
+        println!("[IDLE] Failed to emit mailbox event: {}", e);
+    }
+}
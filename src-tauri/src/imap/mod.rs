@@ -1 +1,16 @@
+pub mod acl;
+pub mod append;
 pub mod client;
+pub mod compress;
+pub mod draft;
+pub mod health;
+pub mod idle;
+pub mod pool;
+pub mod read_marking;
+pub mod search;
+pub mod sort;
+pub mod special_use;
+pub mod status;
+pub mod stream;
+pub mod sync;
+pub mod thread;
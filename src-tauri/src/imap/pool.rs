@@ -0,0 +1,243 @@
+use async_imap::types::Mailbox;
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+use tauri::AppHandle;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use super::client::{encode_imap_utf7, establish_session, flag_name, ImapSession, MailAccount};
+use super::health;
+use crate::tags::PermanentFlags;
+
+// Sessions per account unless the account configures its own limit.
+// Most servers allow around 10 connections per user, IDLE needs one of them.
+pub const DEFAULT_MAX_CONNECTIONS: usize = 3;
+
+struct PooledSession {
+    session: ImapSession,
+    // Folder the session has selected, commands for it can skip the SELECT
+    selected: Option<String>,
+    // Unix time the session was last handed out, 0 forces a health check
+    last_used: u64,
+}
+
+// Authenticated sessions of one account. Commands for different folders run in parallel
+// on their own session, at most max_connections are open at the same time.
+pub struct SessionPool {
+    account: MailAccount,
+    // Receives connection state events, see imap::health
+    app: Option<AppHandle>,
+    idle: Mutex<Vec<PooledSession>>,
+    permits: Semaphore,
+    // PERMANENTFLAGS of each folder as of its last SELECT
+    permanent_flags: Mutex<HashMap<String, PermanentFlags>>,
+}
+
+impl SessionPool {
+    pub fn new(account: MailAccount, app: Option<AppHandle>, first: ImapSession) -> Self {
+        let max_connections = account
+            .max_connections
+            .unwrap_or(DEFAULT_MAX_CONNECTIONS)
+            .max(1);
+
+        Self {
+            account,
+            app,
+            idle: Mutex::new(vec![PooledSession {
+                session: first,
+                selected: None,
+                last_used: health::now_secs(),
+            }]),
+            permits: Semaphore::new(max_connections),
+            permanent_flags: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // A session for a command on the given folder. Waits while all connections are busy.
+    // Sessions that were idle for a while are probed with NOOP first and re-established
+    // if the connection is gone (sleep, NAT timeout, BYE).
+    pub async fn acquire(&self, folder: Option<&str>) -> Result<PoolGuard<'_>, String> {
+        let permit = self
+            .permits
+            .acquire()
+            .await
+            .map_err(|_| "Not connected".to_string())?;
+
+        let mut pooled = match self.take_idle(folder) {
+            Some(pooled) => pooled,
+            None => {
+                println!("[IMAP] Opening additional session for {}", self.account.username);
+                let (session, _) = establish_session(&self.account).await?;
+                PooledSession { session, selected: None, last_used: health::now_secs() }
+            }
+        };
+
+        let now = health::now_secs();
+        let idle_for = now.saturating_sub(pooled.last_used);
+        if idle_for >= health::HEALTH_CHECK_AFTER_SECS && !health::is_alive(&mut pooled.session).await {
+            println!("[IMAP] Session broken after {}s idle, reconnecting", idle_for);
+            pooled.session = health::reconnect(self.app.as_ref(), &self.account).await?;
+            pooled.selected = None;
+        }
+        pooled.last_used = now;
+
+        Ok(PoolGuard { pool: self, pooled: Some(pooled), _permit: permit })
+    }
+
+    // Prefer a session that already has the folder selected
+    fn take_idle(&self, folder: Option<&str>) -> Option<PooledSession> {
+        let mut idle = self.idle.lock().unwrap();
+        let matching = folder.and_then(|folder| {
+            idle.iter()
+                .position(|pooled| pooled.selected.as_deref() == Some(folder))
+        });
+
+        match matching {
+            Some(index) => Some(idle.remove(index)),
+            None => idle.pop(),
+        }
+    }
+
+    fn release(&self, pooled: PooledSession) {
+        self.idle.lock().unwrap().push(pooled);
+    }
+
+    // Probe the most recently used session after a failed command.
+    // Returns true if it was broken and re-established.
+    pub async fn recover(&self) -> Result<bool, String> {
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .map_err(|_| "Not connected".to_string())?;
+
+        let last = self.idle.lock().unwrap().pop();
+        let Some(mut pooled) = last else {
+            return Ok(false);
+        };
+
+        if health::is_alive(&mut pooled.session).await {
+            self.release(pooled);
+            return Ok(false);
+        }
+
+        // The other sessions most likely share the fate of this one
+        self.mark_suspect();
+
+        println!("[IMAP] Session broken, reconnecting");
+        let session = health::reconnect(self.app.as_ref(), &self.account).await?;
+        self.release(PooledSession { session, selected: None, last_used: health::now_secs() });
+        Ok(true)
+    }
+
+    // Check every idle session before its next use
+    pub fn mark_suspect(&self) {
+        for pooled in self.idle.lock().unwrap().iter_mut() {
+            pooled.last_used = 0;
+        }
+    }
+
+    pub fn forget_selection(&self, folder: &str) {
+        for pooled in self.idle.lock().unwrap().iter_mut() {
+            if pooled.selected.as_deref() == Some(folder) {
+                pooled.selected = None;
+            }
+        }
+        self.permanent_flags.lock().unwrap().remove(folder);
+    }
+
+    pub fn permanent_flags(&self, folder: &str) -> Option<PermanentFlags> {
+        self.permanent_flags.lock().unwrap().get(folder).cloned()
+    }
+
+    pub async fn close(&self) {
+        // Sessions still in use are dropped with their guard once the pool is gone
+        self.permits.close();
+
+        let sessions: Vec<PooledSession> = self.idle.lock().unwrap().drain(..).collect();
+        for mut pooled in sessions {
+            let _ = pooled.session.logout().await;
+        }
+    }
+}
+
+// A session borrowed from the pool, returned when dropped
+pub struct PoolGuard<'a> {
+    pool: &'a SessionPool,
+    pooled: Option<PooledSession>,
+    _permit: SemaphorePermit<'a>,
+}
+
+impl PoolGuard<'_> {
+    // SELECT the folder, always sent because the caller needs the current mailbox state
+    pub async fn select_folder(&mut self, folder: &str) -> Result<Mailbox, String> {
+        self.set_selected(None);
+        let mailbox = self
+            .select(&encode_imap_utf7(folder))
+            .await
+            .map_err(|e| format!("Failed to select folder: {}", e))?;
+        self.set_selected(Some(folder));
+
+        // Without PERMANENTFLAGS every flag in FLAGS is kept (RFC 3501 7.1). async-imap reports
+        // "PERMANENTFLAGS ()" as an empty list as well, so only a listed flag restricts.
+        let advertised = if mailbox.permanent_flags.is_empty() {
+            &mailbox.flags
+        } else {
+            &mailbox.permanent_flags
+        };
+        let flags: Vec<String> = advertised.iter().filter_map(flag_name).collect();
+        let permanent = if flags.is_empty() {
+            PermanentFlags::unrestricted()
+        } else {
+            PermanentFlags::from_flags(flags)
+        };
+        self.pool
+            .permanent_flags
+            .lock()
+            .unwrap()
+            .insert(folder.to_string(), permanent);
+        Ok(mailbox)
+    }
+
+    // SELECT the folder unless the session already has it selected
+    pub async fn ensure_selected(&mut self, folder: &str) -> Result<(), String> {
+        if self.selected() == Some(folder) {
+            return Ok(());
+        }
+        self.select_folder(folder).await.map(|_| ())
+    }
+
+    pub fn selected(&self) -> Option<&str> {
+        self.pooled.as_ref().and_then(|pooled| pooled.selected.as_deref())
+    }
+
+    // Record a SELECT (or the loss of the selection) done directly on the session
+    pub fn set_selected(&mut self, folder: Option<&str>) {
+        if let Some(pooled) = self.pooled.as_mut() {
+            pooled.selected = folder.map(|f| f.to_string());
+        }
+    }
+}
+
+impl Deref for PoolGuard<'_> {
+    type Target = ImapSession;
+
+    fn deref(&self) -> &ImapSession {
+        &self.pooled.as_ref().unwrap().session
+    }
+}
+
+impl DerefMut for PoolGuard<'_> {
+    fn deref_mut(&mut self) -> &mut ImapSession {
+        &mut self.pooled.as_mut().unwrap().session
+    }
+}
+
+impl Drop for PoolGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(mut pooled) = self.pooled.take() {
+            pooled.last_used = health::now_secs();
+            self.pool.release(pooled);
+        }
+    }
+}
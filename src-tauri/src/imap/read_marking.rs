@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tauri::{AppHandle, Emitter};
+
+pub const EMAIL_READ_EVENT: &str = "email-read";
+
+// When a message opened with open_email is marked as read. Content fetches never set
+// \Seen themselves, so this and the explicit flag commands are the only way it changes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "camelCase")]
+pub enum ReadMarking {
+    #[default]
+    Immediately,
+    // Only if no other message was opened in the meantime
+    Delayed { seconds: u32 },
+    Manual,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailReadEvent {
+    pub account_id: String,
+    pub folder: String,
+    pub uid: u32,
+}
+
+// The message currently open in an account. A delayed mark is dropped once another
+// message is opened.
+#[derive(Debug, Default)]
+pub struct OpenedMessage {
+    generation: AtomicU64,
+}
+
+impl OpenedMessage {
+    // Called for every opened message, returns the token of a delayed mark
+    pub fn opened(&self) -> u64 {
+        self.generation.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    pub fn is_current(&self, token: u64) -> bool {
+        self.generation.load(Ordering::SeqCst) == token
+    }
+}
+
+pub fn emit_read(app: Option<&AppHandle>, account_id: &str, folder: &str, uid: u32) {
+    let Some(app) = app else {
+        return;
+    };
+
+    let event = EmailReadEvent {
+        account_id: account_id.to_string(),
+        folder: folder.to_string(),
+        uid,
+    };
+    if let Err(e) = app.emit(EMAIL_READ_EVENT, event) {
+        println!("[IMAP] Failed to emit read event: {}", e);
+    }
+}
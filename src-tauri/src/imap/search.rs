@@ -0,0 +1,223 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use super::sync::quote;
+
+// Matches returned by a search unless the caller asks for more
+pub const DEFAULT_LIMIT: u32 = 200;
+
+// Structured search, translated to UID SEARCH criteria (RFC 3501 6.4.4)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SearchQuery {
+    And { terms: Vec<SearchQuery> },
+    Or { terms: Vec<SearchQuery> },
+    Not { term: Box<SearchQuery> },
+    From { value: String },
+    To { value: String },
+    Cc { value: String },
+    Subject { value: String },
+    Body { value: String },
+    // Headers and body
+    Text { value: String },
+    // Dates as YYYY-MM-DD, compared with the internal date of the message
+    Since { date: String },
+    Before { date: String },
+    On { date: String },
+    // System flag (\Seen, \Flagged, ...) or keyword
+    Flag { flag: String },
+    Larger { bytes: u32 },
+    Smaller { bytes: u32 },
+    HasAttachment,
+}
+
+// Search criteria ready to send, with the charset they need
+pub struct Criteria {
+    pub criteria: String,
+    pub utf8: bool,
+}
+
+impl SearchQuery {
+    pub fn to_criteria(&self) -> Result<Criteria, String> {
+        self.check_attachment_terms(false)?;
+        let criteria = self.render()?;
+        let utf8 = !criteria.is_ascii();
+        Ok(Criteria { criteria, utf8 })
+    }
+
+    // IMAP has no attachment criterion: the server only narrows down by headers and the
+    // matches are checked again on their BODYSTRUCTURE. That only works for a required
+    // attachment, so HasAttachment can't be negated or be one alternative of an OR.
+    pub fn requires_attachment(&self) -> bool {
+        match self {
+            SearchQuery::HasAttachment => true,
+            SearchQuery::And { terms } => terms.iter().any(|term| term.requires_attachment()),
+            _ => false,
+        }
+    }
+
+    fn check_attachment_terms(&self, nested: bool) -> Result<(), String> {
+        match self {
+            SearchQuery::HasAttachment if nested => {
+                Err("Attachments can only be searched for as a required term, not under OR or NOT".to_string())
+            }
+            SearchQuery::And { terms } => terms.iter().try_for_each(|term| term.check_attachment_terms(nested)),
+            SearchQuery::Or { terms } => terms.iter().try_for_each(|term| term.check_attachment_terms(true)),
+            SearchQuery::Not { term } => term.check_attachment_terms(true),
+            _ => Ok(()),
+        }
+    }
+
+    fn render(&self) -> Result<String, String> {
+        Ok(match self {
+            SearchQuery::And { terms } => match terms.len() {
+                0 => "ALL".to_string(),
+                1 => terms[0].render()?,
+                _ => format!(
+                    "({})",
+                    terms.iter().map(|t| t.render()).collect::<Result<Vec<_>, _>>()?.join(" ")
+                ),
+            },
+            // OR takes exactly two keys, longer lists are nested
+            SearchQuery::Or { terms } => {
+                let mut rendered = terms.iter().rev().map(|t| t.render());
+                let mut criteria = rendered.next().ok_or("Empty OR in search query")??;
+                for term in rendered {
+                    criteria = format!("OR {} {}", term?, criteria);
+                }
+                criteria
+            }
+            SearchQuery::Not { term } => format!("NOT {}", term.render()?),
+            SearchQuery::From { value } => format!("FROM {}", quote(value)),
+            SearchQuery::To { value } => format!("TO {}", quote(value)),
+            SearchQuery::Cc { value } => format!("CC {}", quote(value)),
+            SearchQuery::Subject { value } => format!("SUBJECT {}", quote(value)),
+            SearchQuery::Body { value } => format!("BODY {}", quote(value)),
+            SearchQuery::Text { value } => format!("TEXT {}", quote(value)),
+            SearchQuery::Since { date } => format!("SINCE {}", imap_date(date)?),
+            SearchQuery::Before { date } => format!("BEFORE {}", imap_date(date)?),
+            SearchQuery::On { date } => format!("ON {}", imap_date(date)?),
+            SearchQuery::Flag { flag } => flag_criterion(flag)?,
+            SearchQuery::Larger { bytes } => format!("LARGER {}", bytes),
+            SearchQuery::Smaller { bytes } => format!("SMALLER {}", bytes),
+            // Everything that may have an attachment: any multipart, a declared attachment
+            // and single parts that aren't text. BODYSTRUCTURE decides.
+            SearchQuery::HasAttachment => "OR OR HEADER Content-Type \"multipart/\" \
+                HEADER Content-Disposition \"attachment\" NOT HEADER Content-Type \"text/\""
+                .to_string(),
+        })
+    }
+}
+
+// 2024-03-01 -> 1-Mar-2024
+fn imap_date(date: &str) -> Result<String, String> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map(|d| d.format("%-d-%b-%Y").to_string())
+        .map_err(|e| format!("Invalid date in search query '{}': {}", date, e))
+}
+
+fn flag_criterion(flag: &str) -> Result<String, String> {
+    let system = match flag.to_ascii_lowercase().as_str() {
+        "\\seen" => Some("SEEN"),
+        "\\answered" => Some("ANSWERED"),
+        "\\flagged" => Some("FLAGGED"),
+        "\\deleted" => Some("DELETED"),
+        "\\draft" => Some("DRAFT"),
+        _ => None,
+    };
+    if let Some(system) = system {
+        return Ok(system.to_string());
+    }
+
+    // Keywords are atoms, quoting is not allowed
+    let valid = !flag.is_empty()
+        && flag
+            .chars()
+            .all(|c| c.is_ascii_graphic() && !"(){%*\"\\]".contains(c));
+    if !valid {
+        return Err(format!("Invalid flag in search query: {}", flag));
+    }
+    Ok(format!("KEYWORD {}", flag))
+}
+
+// Matches of an ESEARCH response (RFC 4731), also sent for ESORT (RFC 5267):
+// "ESEARCH (TAG "A5") UID ALL 1:3,7" -> [(1, 3), (7, 7)]. Ranges are kept as the server sent
+// them, for ESORT their order and direction ("9:7") is the sort order. The matches of PARTIAL
+// are returned the same way.
+pub fn parse_esearch(text: &str) -> Option<Vec<(u32, u32)>> {
+    let mut rest = text.trim_end();
+    if !rest.get(..7)?.eq_ignore_ascii_case("ESEARCH") {
+        return None;
+    }
+    rest = rest[7..].trim_start();
+
+    // Correlator of the command
+    if rest.starts_with('(') {
+        let end = rest.find(')')?;
+        rest = rest[end + 1..].trim_start();
+    }
+
+    let mut ranges = Vec::new();
+    while !rest.is_empty() {
+        let (name, tail) = rest.split_once(' ').unwrap_or((rest, ""));
+        let tail = tail.trim_start();
+        if name.eq_ignore_ascii_case("UID") {
+            rest = tail;
+            continue;
+        }
+
+        // Every other return item is a name with a value: ALL, PARTIAL, COUNT, MIN, MAX, MODSEQ
+        let value;
+        if tail.starts_with('(') {
+            let end = tail.find(')')?;
+            value = &tail[1..end];
+            rest = tail[end + 1..].trim_start();
+        } else {
+            let (token, next) = tail.split_once(' ').unwrap_or((tail, ""));
+            value = token;
+            rest = next.trim_start();
+        }
+
+        if name.eq_ignore_ascii_case("ALL") {
+            ranges.extend(parse_sequence_set(value)?);
+        } else if name.eq_ignore_ascii_case("PARTIAL") {
+            // "(1:50 4,7:9)": the requested range and the matches in it, NIL if there are none
+            let set = value.split_whitespace().nth(1)?;
+            if !set.eq_ignore_ascii_case("NIL") {
+                ranges.extend(parse_sequence_set(set)?);
+            }
+        }
+    }
+    Some(ranges)
+}
+
+fn parse_sequence_set(set: &str) -> Option<Vec<(u32, u32)>> {
+    set.split(',')
+        .map(|part| match part.split_once(':') {
+            Some((start, end)) => Some((start.parse().ok()?, end.parse().ok()?)),
+            None => part.parse().ok().map(|id| (id, id)),
+        })
+        .collect()
+}
+
+// The UIDs of a search result from the highest down, expanded only as far as they are used
+pub fn uids_descending(mut ranges: Vec<(u32, u32)>) -> impl Iterator<Item = u32> + Send {
+    for range in ranges.iter_mut() {
+        *range = (range.0.min(range.1), range.0.max(range.1));
+    }
+    ranges.sort_unstable_by(|a, b| b.1.cmp(&a.1));
+    ranges.into_iter().flat_map(|(start, end)| (start..=end).rev())
+}
+
+// The UIDs of an ESORT result in sort order, "9:7" is 9 8 7
+pub fn uids_in_order(ranges: &[(u32, u32)]) -> Vec<u32> {
+    let mut uids = Vec::new();
+    for &(start, end) in ranges {
+        if start <= end {
+            uids.extend(start..=end);
+        } else {
+            uids.extend((end..=start).rev());
+        }
+    }
+    uids
+}
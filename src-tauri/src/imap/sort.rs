@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+
+use super::client::EmailHeader;
+
+// Sort keys of RFC 5256 SORT that the cache can sort by as well
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SortKey {
+    // Order the messages arrived in the folder
+    Arrival,
+    // Date header
+    Date,
+    From,
+    To,
+    Subject,
+    Size,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SortOptions {
+    pub key: SortKey,
+    // Descending, e.g. newest or largest first
+    #[serde(default)]
+    pub reverse: bool,
+}
+
+impl SortOptions {
+    // Sort criteria of UID SORT, e.g. "REVERSE DATE"
+    pub fn criteria(&self) -> String {
+        let key = match self.key {
+            SortKey::Arrival => "ARRIVAL",
+            SortKey::Date => "DATE",
+            SortKey::From => "FROM",
+            SortKey::To => "TO",
+            SortKey::Subject => "SUBJECT",
+            SortKey::Size => "SIZE",
+        };
+        if self.reverse {
+            format!("REVERSE {}", key)
+        } else {
+            key.to_string()
+        }
+    }
+}
+
+// One page of a sorted listing. Pages are anchored at the last UID of the previous page
+// instead of an offset, so mail arriving in between does not shift them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeaderPage {
+    pub headers: Vec<EmailHeader>,
+    // Anchor of the next page, None on the last page
+    pub next_after_uid: Option<u32>,
+    // Messages in the folder
+    pub total: u32,
+}
+
+impl HeaderPage {
+    pub fn new(headers: Vec<EmailHeader>, count: u32, total: u32) -> Self {
+        let next_after_uid = if headers.len() as u32 >= count && count > 0 {
+            headers.last().map(|header| header.uid)
+        } else {
+            None
+        };
+        Self { headers, next_after_uid, total }
+    }
+}
+
+// The UIDs after the anchor in a sorted list. An anchor that is no longer in the folder
+// ends the listing, the caller starts over from the first page.
+pub fn page_after(sorted: &[u32], after_uid: Option<u32>, count: u32) -> Vec<u32> {
+    let start = match after_uid {
+        Some(anchor) => match sorted.iter().position(|&uid| uid == anchor) {
+            Some(index) => index + 1,
+            None => return Vec::new(),
+        },
+        None => 0,
+    };
+    sorted.iter().skip(start).take(count as usize).copied().collect()
+}
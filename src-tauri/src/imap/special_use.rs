@@ -0,0 +1,80 @@
+use imap_proto::types::NameAttribute;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::client::Folder;
+
+// Special-use mailbox roles (RFC 6154)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FolderRole {
+    Sent,
+    Trash,
+    Drafts,
+    Junk,
+    Archive,
+    All,
+    Flagged,
+}
+
+pub fn role_from_attributes(attributes: &[NameAttribute<'_>]) -> Option<FolderRole> {
+    attributes.iter().find_map(|attribute| match attribute {
+        NameAttribute::Sent => Some(FolderRole::Sent),
+        NameAttribute::Trash => Some(FolderRole::Trash),
+        NameAttribute::Drafts => Some(FolderRole::Drafts),
+        NameAttribute::Junk => Some(FolderRole::Junk),
+        NameAttribute::Archive => Some(FolderRole::Archive),
+        NameAttribute::All => Some(FolderRole::All),
+        NameAttribute::Flagged => Some(FolderRole::Flagged),
+        _ => None,
+    })
+}
+
+// Usual names on servers without SPECIAL-USE, compared with the last level of the folder name
+fn role_from_name(name: &str, delimiter: &str) -> Option<FolderRole> {
+    let leaf = if delimiter.is_empty() {
+        name
+    } else {
+        name.rsplit(delimiter).next().unwrap_or(name)
+    };
+
+    match leaf.to_lowercase().as_str() {
+        "sent" | "sent items" | "sent messages" | "sent mail" | "gesendet" | "gesendete elemente"
+        | "gesendete objekte" => Some(FolderRole::Sent),
+        "trash" | "deleted items" | "deleted messages" | "papierkorb" | "gelöschte elemente"
+        | "gelöschte objekte" => Some(FolderRole::Trash),
+        "drafts" | "draft" | "entwürfe" => Some(FolderRole::Drafts),
+        "junk" | "spam" | "junk e-mail" | "junk-e-mail" => Some(FolderRole::Junk),
+        "archive" | "archiv" => Some(FolderRole::Archive),
+        _ => None,
+    }
+}
+
+// Fill in the role of every folder: the user's overrides win, then the server's special-use
+// attributes, then the name, for roles no folder was marked with
+pub fn assign_roles(folders: &mut [Folder], overrides: &HashMap<FolderRole, String>) {
+    let mut marked: Vec<FolderRole> = folders.iter().filter_map(|folder| folder.role).collect();
+
+    for folder in folders.iter_mut() {
+        if folder.role.is_some() {
+            continue;
+        }
+        if let Some(role) = role_from_name(&folder.name, &folder.delimiter) {
+            if !marked.contains(&role) {
+                folder.role = Some(role);
+                marked.push(role);
+            }
+        }
+    }
+
+    for (&role, name) in overrides {
+        for folder in folders.iter_mut() {
+            if folder.role == Some(role) {
+                folder.role = None;
+            }
+        }
+        if let Some(folder) = folders.iter_mut().find(|folder| &folder.name == name) {
+            folder.role = Some(role);
+        }
+    }
+}
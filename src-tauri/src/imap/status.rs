@@ -0,0 +1,45 @@
+use imap_proto::types::{MailboxDatum, Response, StatusAttribute};
+use std::collections::HashMap;
+
+use super::client::ImapSession;
+use super::sync;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Counts {
+    pub total: u32,
+    pub unread: u32,
+}
+
+pub fn counts(status: &[StatusAttribute]) -> Counts {
+    let mut counts = Counts::default();
+    for attribute in status {
+        match attribute {
+            StatusAttribute::Messages(total) => counts.total = *total,
+            StatusAttribute::Unseen(unread) => counts.unread = *unread,
+            _ => {}
+        }
+    }
+    counts
+}
+
+// Message counts on servers without LIST-STATUS (RFC 5819), keyed by the mailbox name as
+// sent. A folder the server refuses STATUS for only lacks its counts.
+pub(crate) async fn status_batch(
+    session: &mut ImapSession,
+    mailboxes: &[String],
+) -> Result<HashMap<String, Counts>, String> {
+    let commands: Vec<String> = mailboxes
+        .iter()
+        .map(|mailbox| format!("STATUS {} (MESSAGES UNSEEN)", sync::quote(mailbox)))
+        .collect();
+
+    let mut result = HashMap::new();
+    sync::run_pipelined(session, &commands, |parsed| {
+        if let Response::MailboxData(MailboxDatum::Status { mailbox, status }) = parsed {
+            result.insert(mailbox.to_string(), counts(status));
+        }
+    })
+    .await?;
+
+    Ok(result)
+}
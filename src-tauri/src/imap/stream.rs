@@ -0,0 +1,346 @@
+use async_native_tls::TlsStream;
+use futures::io::{AsyncRead, AsyncWrite};
+use imap_proto::types::{Response, Status};
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::net::TcpStream;
+use tokio_util::compat::Compat;
+
+use super::compress::Deflate;
+
+// Transport of an IMAP session: TLS (implicit or after STARTTLS) or plain text
+#[derive(Debug)]
+pub enum Transport {
+    Plain(Compat<TcpStream>),
+    Tls(TlsStream<Compat<TcpStream>>),
+}
+
+// The session stream. Server responses pass through line by line so that responses
+// of extensions imap-proto cannot parse can be rewritten before async-imap sees them.
+#[derive(Debug)]
+pub struct ImapStream {
+    transport: Transport,
+    // Processed server data not yet read by async-imap
+    pending: Vec<u8>,
+    pending_pos: usize,
+    // Incomplete line of the current response
+    line: Vec<u8>,
+    // Bytes of a literal ({n}) still to pass through unchanged
+    literal_remaining: usize,
+    // The next line continues a response after a literal
+    continued: bool,
+    // Armed while COMPRESS DEFLATE waits for its tagged response
+    compress: CompressSwitch,
+    // Set once the server agreed to compress, see imap::compress
+    deflate: Option<Deflate>,
+    // Compressed data not yet written to the transport
+    outgoing: Vec<u8>,
+}
+
+// Kept by establish_session to turn on compression, since async-imap gives no access to the
+// stream of a session. The stream switches at the tagged response to the next command, which
+// has to be COMPRESS DEFLATE: the server compresses everything after its OK (RFC 4978), which
+// may arrive in the same read as the OK itself.
+#[derive(Debug, Clone, Default)]
+pub struct CompressSwitch(Arc<AtomicBool>);
+
+impl CompressSwitch {
+    pub fn arm(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    fn take(&self) -> bool {
+        self.0.swap(false, Ordering::SeqCst)
+    }
+}
+
+impl ImapStream {
+    pub fn plain(stream: Compat<TcpStream>) -> Self {
+        Self::new(Transport::Plain(stream))
+    }
+
+    pub fn tls(stream: TlsStream<Compat<TcpStream>>) -> Self {
+        Self::new(Transport::Tls(stream))
+    }
+
+    fn new(transport: Transport) -> Self {
+        Self {
+            transport,
+            pending: Vec::new(),
+            pending_pos: 0,
+            line: Vec::new(),
+            literal_remaining: 0,
+            continued: false,
+            compress: CompressSwitch::default(),
+            deflate: None,
+            outgoing: Vec::new(),
+        }
+    }
+
+    pub fn compress_switch(&self) -> CompressSwitch {
+        self.compress.clone()
+    }
+
+    fn receive(&mut self, data: &[u8]) -> io::Result<()> {
+        let rest = match self.deflate.as_mut() {
+            Some(deflate) => {
+                let data = deflate.inflate(data)?;
+                self.process(&data)
+            }
+            None => self.process(data),
+        };
+
+        // Anything after the OK to COMPRESS is compressed already
+        match rest {
+            Some(rest) if !rest.is_empty() => self.receive(&rest),
+            _ => Ok(()),
+        }
+    }
+
+    // Returns the data after the line that switched on compression
+    fn process(&mut self, mut data: &[u8]) -> Option<Vec<u8>> {
+        while !data.is_empty() {
+            if self.literal_remaining > 0 {
+                let take = self.literal_remaining.min(data.len());
+                self.pending.extend_from_slice(&data[..take]);
+                self.literal_remaining -= take;
+                data = &data[take..];
+                continue;
+            }
+
+            match data.iter().position(|&b| b == b'\n') {
+                Some(end) => {
+                    self.line.extend_from_slice(&data[..=end]);
+                    data = &data[end + 1..];
+                    if self.finish_line() {
+                        return Some(data.to_vec());
+                    }
+                }
+                None => {
+                    self.line.extend_from_slice(data);
+                    data = &[];
+                }
+            }
+        }
+        None
+    }
+
+    // True if the line was the OK to COMPRESS DEFLATE
+    fn finish_line(&mut self) -> bool {
+        let line = std::mem::take(&mut self.line);
+        let literal = literal_length(&line);
+        let tagged = !self.continued && is_tagged(&line);
+
+        if !self.continued && literal.is_none() {
+            if let Some(copy) = appenduid_copy(&line) {
+                self.pending.extend_from_slice(&copy);
+            }
+        }
+        let rewritten = if !self.continued && literal.is_none() {
+            rewrite_response(&line)
+        } else {
+            None
+        };
+        self.pending.extend_from_slice(rewritten.as_deref().unwrap_or(&line));
+
+        self.literal_remaining = literal.unwrap_or(0);
+        self.continued = literal.is_some();
+
+        if tagged && self.compress.take() && tagged_ok(&line) {
+            self.deflate = Some(Deflate::new());
+            return true;
+        }
+        false
+    }
+
+    // Write compressed data left over from earlier writes
+    fn poll_send_outgoing(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.outgoing.is_empty() {
+            match Pin::new(&mut self.transport).poll_write(cx, &self.outgoing) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                Poll::Ready(Ok(n)) => {
+                    self.outgoing.drain(..n);
+                }
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+// "... {123}\r\n" announces a literal of 123 bytes (LITERAL+ "{123+}" included)
+fn literal_length(line: &[u8]) -> Option<usize> {
+    let line = line.strip_suffix(b"\r\n").or_else(|| line.strip_suffix(b"\n"))?;
+    let line = line.strip_suffix(b"}")?;
+    let start = line.iter().rposition(|&b| b == b'{')?;
+    let digits = &line[start + 1..];
+    let digits = digits.strip_suffix(b"+").unwrap_or(digits);
+    std::str::from_utf8(digits).ok()?.parse().ok()
+}
+
+// Neither untagged ("* ") nor a continuation request ("+ ")
+fn is_tagged(line: &[u8]) -> bool {
+    !line.starts_with(b"*") && !line.starts_with(b"+")
+}
+
+fn tagged_ok(line: &[u8]) -> bool {
+    match line.iter().position(|&b| b == b' ') {
+        Some(space) => line[space + 1..].get(..2).is_some_and(|status| status.eq_ignore_ascii_case(b"OK")),
+        None => false,
+    }
+}
+
+// async-imap drops the tagged response of APPEND, so its APPENDUID code (RFC 4315) is
+// repeated as an untagged OK before it, which ends up in Session::unsolicited_responses
+fn appenduid_copy(line: &[u8]) -> Option<Vec<u8>> {
+    if !is_tagged(line) || !tagged_ok(line) {
+        return None;
+    }
+    let line = std::str::from_utf8(line).ok()?;
+    let start = line.find('[')?;
+    let end = start + line[start..].find(']')?;
+    let code = &line[start + 1..end];
+    code.get(..10)?
+        .eq_ignore_ascii_case("APPENDUID ")
+        .then(|| format!("* OK [{}] Appended\r\n", code).into_bytes())
+}
+
+// Untagged responses async-imap would fail on
+fn rewrite_response(line: &[u8]) -> Option<Vec<u8>> {
+    if !line.starts_with(b"* ") {
+        return None;
+    }
+    let line = std::str::from_utf8(line).ok()?;
+    let name = line[2..].split([' ', '\r', '\n']).next().unwrap_or("");
+    if TUNNELED_RESPONSES.iter().any(|r| name.eq_ignore_ascii_case(r)) {
+        return Some(format!("* OK {}{}", TUNNEL, &line[2..]).into_bytes());
+    }
+    None
+}
+
+// Responses without a parser in imap-proto pass through as "* OK X-TUNNELED <response>",
+// commands read them with run_raw and tunneled()
+const TUNNEL: &str = "X-TUNNELED ";
+const TUNNELED_RESPONSES: &[&str] = &["THREAD", "NAMESPACE", "ESEARCH"];
+
+// Original text of a tunneled response, e.g. "THREAD (1)(2 3)"
+pub fn tunneled<'a>(response: &'a Response<'_>) -> Option<&'a str> {
+    match response {
+        Response::Data { status: Status::Ok, code: None, information: Some(info) } => info.strip_prefix(TUNNEL),
+        _ => None,
+    }
+}
+
+impl AsyncRead for ImapStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let mut chunk = [0u8; 8192];
+
+        loop {
+            if this.pending_pos < this.pending.len() {
+                let available = &this.pending[this.pending_pos..];
+                let n = available.len().min(buf.len());
+                buf[..n].copy_from_slice(&available[..n]);
+                this.pending_pos += n;
+                if this.pending_pos == this.pending.len() {
+                    this.pending.clear();
+                    this.pending_pos = 0;
+                }
+                return Poll::Ready(Ok(n));
+            }
+
+            match Pin::new(&mut this.transport).poll_read(cx, &mut chunk) {
+                Poll::Ready(Ok(0)) => {
+                    // Connection closed: hand out what is left of an incomplete line
+                    if this.line.is_empty() {
+                        return Poll::Ready(Ok(0));
+                    }
+                    let line = std::mem::take(&mut this.line);
+                    this.pending.extend_from_slice(&line);
+                }
+                Poll::Ready(Ok(n)) => {
+                    if let Err(e) = this.receive(&chunk[..n]) {
+                        return Poll::Ready(Err(e));
+                    }
+                }
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl AsyncWrite for ImapStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        match this.poll_send_outgoing(cx) {
+            Poll::Ready(Ok(())) => {}
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            Poll::Pending => return Poll::Pending,
+        }
+
+        let deflate = match this.deflate.as_mut() {
+            Some(deflate) => deflate,
+            None => return Pin::new(&mut this.transport).poll_write(cx, buf),
+        };
+        deflate.deflate(buf, &mut this.outgoing)?;
+        // The data is taken even if the transport is busy, poll_flush sends the rest
+        if let Poll::Ready(Err(e)) = this.poll_send_outgoing(cx) {
+            return Poll::Ready(Err(e));
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        match this.poll_send_outgoing(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.transport).poll_flush(cx),
+            other => other,
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        match this.poll_send_outgoing(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.transport).poll_close(cx),
+            other => other,
+        }
+    }
+}
+
+impl AsyncRead for Transport {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Transport::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            Transport::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Transport {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Transport::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            Transport::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Transport::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            Transport::Tls(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Transport::Plain(stream) => Pin::new(stream).poll_close(cx),
+            Transport::Tls(stream) => Pin::new(stream).poll_close(cx),
+        }
+    }
+}
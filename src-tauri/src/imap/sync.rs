@@ -0,0 +1,370 @@
+use futures::StreamExt;
+use imap_proto::types::{AttributeValue, Response, Status};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::ops::RangeInclusive;
+
+use super::client::{encode_imap_utf7, parse_header, uids_to_sequence, EmailHeader, ImapSession, HEADER_FETCH_ITEMS};
+use crate::cache::{EmailCache, SyncState};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyncMode {
+    // RFC 7162 QRESYNC: changed flags and expunged UIDs from a single UID FETCH
+    Qresync,
+    // RFC 7162 CONDSTORE: changed flags via CHANGEDSINCE, expunges via UID SEARCH
+    Condstore,
+    // No extensions: compare UIDs and flags of the whole folder
+    Full,
+}
+
+// Everything the cache needs to catch up with the server for one folder
+#[derive(Debug, Clone)]
+pub struct FolderChanges {
+    pub uid_validity: Option<u32>,
+    pub uid_next: Option<u32>,
+    pub highest_modseq: Option<u64>,
+    pub highest_uid: u32,
+    // The cached rows of the folder are stale and have to be dropped first
+    pub full_resync: bool,
+    // Headers of these are fetched in chunks by sync_to_cache
+    pub new_uids: Vec<u32>,
+    pub flag_updates: Vec<(u32, Vec<String>)>,
+    pub vanished: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncResult {
+    pub folder: String,
+    pub new_messages: u32,
+    pub flag_changes: u32,
+    pub vanished: u32,
+    pub full_resync: bool,
+}
+
+impl FolderChanges {
+    pub fn summary(&self, folder: &str) -> SyncResult {
+        SyncResult {
+            folder: folder.to_string(),
+            new_messages: self.new_uids.len() as u32,
+            flag_changes: self.flag_updates.len() as u32,
+            vanished: self.vanished.len() as u32,
+            full_resync: self.full_resync,
+        }
+    }
+}
+
+pub async fn sync_folder(
+    session: &mut ImapSession,
+    folder: &str,
+    mode: SyncMode,
+    known: Option<&SyncState>,
+    cached_flags: &HashMap<u32, Vec<String>>,
+) -> Result<FolderChanges, String> {
+    let encoded_folder = encode_imap_utf7(folder);
+
+    // With QRESYNC enabled on the session a plain SELECT already reports HIGHESTMODSEQ
+    let mailbox = match mode {
+        SyncMode::Condstore => session.select_condstore(&encoded_folder).await,
+        _ => session.select(&encoded_folder).await,
+    }
+    .map_err(|e| format!("Failed to select folder: {}", e))?;
+
+    let mut changes = FolderChanges {
+        uid_validity: mailbox.uid_validity,
+        uid_next: mailbox.uid_next,
+        highest_modseq: mailbox.highest_modseq,
+        highest_uid: 0,
+        full_resync: false,
+        new_uids: Vec::new(),
+        flag_updates: Vec::new(),
+        vanished: Vec::new(),
+    };
+
+    // Decide how far the cached state can be trusted
+    let mut threshold = 0;
+    let mut mode = mode;
+    let mut since_modseq = None;
+    match known {
+        Some(state) if state.uid_validity.is_some() && mailbox.uid_validity.is_some() => {
+            if state.uid_validity != mailbox.uid_validity {
+                println!("[SYNC] UIDVALIDITY of {} changed, resyncing from scratch", folder);
+                changes.full_resync = true;
+                mode = SyncMode::Full;
+            } else {
+                threshold = state.synced_uid.unwrap_or(0);
+                since_modseq = state.highest_modseq;
+            }
+        }
+        // Checkpoints written before UIDVALIDITY was tracked only get a full diff
+        Some(state) => {
+            threshold = state.synced_uid.unwrap_or(0);
+            mode = SyncMode::Full;
+        }
+        None => mode = SyncMode::Full,
+    }
+
+    // Without a stored or reported mod-sequence there is nothing to ask CHANGEDSINCE about
+    let since_modseq = match (since_modseq, mailbox.highest_modseq) {
+        (Some(since), Some(_)) => since,
+        _ => {
+            mode = SyncMode::Full;
+            0
+        }
+    };
+
+    changes.highest_uid = threshold;
+
+    if mailbox.exists == 0 {
+        if !changes.full_resync {
+            changes.vanished = cached_flags.keys().copied().collect();
+        }
+        return Ok(changes);
+    }
+
+    if mode == SyncMode::Qresync
+        && Some(since_modseq) == mailbox.highest_modseq
+        && known.and_then(|state| state.uid_next) == mailbox.uid_next
+    {
+        // Nothing changed since the last sync
+        return Ok(changes);
+    }
+
+    let mut changed: Vec<(u32, Vec<String>)> = Vec::new();
+    match mode {
+        SyncMode::Qresync => {
+            let command = format!(
+                "UID FETCH 1:* (UID FLAGS) (CHANGEDSINCE {} VANISHED)",
+                since_modseq
+            );
+            let mut vanished_ranges: Vec<RangeInclusive<u32>> = Vec::new();
+            run_raw(session, &command, |response| match response {
+                Response::Fetch(_, attributes) => {
+                    if let Some(entry) = parse_flag_entry(attributes) {
+                        changed.push(entry);
+                    }
+                }
+                Response::Vanished { uids, .. } => vanished_ranges.extend(uids.iter().cloned()),
+                _ => {}
+            })
+            .await?;
+
+            changes.vanished = cached_flags
+                .keys()
+                .copied()
+                .filter(|uid| vanished_ranges.iter().any(|range| range.contains(uid)))
+                .collect();
+        }
+        SyncMode::Condstore => {
+            let command = format!("UID FETCH 1:* (UID FLAGS) (CHANGEDSINCE {})", since_modseq);
+            run_raw(session, &command, |response| {
+                if let Response::Fetch(_, attributes) = response {
+                    if let Some(entry) = parse_flag_entry(attributes) {
+                        changed.push(entry);
+                    }
+                }
+            })
+            .await?;
+
+            // CONDSTORE alone doesn't report expunges, compare the UID lists instead
+            let server_uids = session
+                .uid_search("ALL")
+                .await
+                .map_err(|e| format!("Failed to search folder: {}", e))?;
+            changes.vanished = cached_flags
+                .keys()
+                .copied()
+                .filter(|uid| !server_uids.contains(uid))
+                .collect();
+        }
+        SyncMode::Full => {
+            run_raw(session, "UID FETCH 1:* (UID FLAGS)", |response| {
+                if let Response::Fetch(_, attributes) = response {
+                    if let Some(entry) = parse_flag_entry(attributes) {
+                        changed.push(entry);
+                    }
+                }
+            })
+            .await?;
+
+            if !changes.full_resync {
+                let server_uids: HashSet<u32> = changed.iter().map(|(uid, _)| *uid).collect();
+                changes.vanished = cached_flags
+                    .keys()
+                    .copied()
+                    .filter(|uid| !server_uids.contains(uid))
+                    .collect();
+            }
+        }
+    }
+
+    // Messages below the checkpoint that are not cached were left out on purpose (e.g. cleanup)
+    for (uid, flags) in changed {
+        changes.highest_uid = changes.highest_uid.max(uid);
+
+        match cached_flags.get(&uid) {
+            Some(cached) if !changes.full_resync => {
+                if *cached != flags {
+                    changes.flag_updates.push((uid, flags));
+                }
+            }
+            _ if changes.full_resync || uid > threshold => changes.new_uids.push(uid),
+            _ => {}
+        }
+    }
+
+    println!(
+        "[SYNC] {} ({:?}): {} new, {} flag changes, {} vanished",
+        folder,
+        mode,
+        changes.new_uids.len(),
+        changes.flag_updates.len(),
+        changes.vanished.len()
+    );
+
+    Ok(changes)
+}
+
+// Headers of new messages are fetched and stored this many at a time, so the first sync of a
+// large folder neither sends one huge command nor holds the whole folder in memory
+const HEADER_FETCH_CHUNK: usize = 500;
+
+// Bring the cached copy of a folder up to date: expunges and flag changes first, then the
+// headers of new messages chunk by chunk. The checkpoint is written last, an interrupted sync
+// starts over from the previous one and skips the chunks already stored.
+pub async fn sync_to_cache(
+    session: &mut ImapSession,
+    account_id: &str,
+    folder: &str,
+    mode: SyncMode,
+) -> Result<SyncResult, String> {
+    // The cache connection must not be held across the IMAP round trips
+    let (known, cached_flags) = {
+        let cache = EmailCache::new(account_id)?;
+        (cache.get_sync_state(folder)?, cache.get_folder_flags(folder)?)
+    };
+
+    let changes = sync_folder(session, folder, mode, known.as_ref(), &cached_flags).await?;
+    EmailCache::new(account_id)?.apply_folder_changes(folder, &changes)?;
+
+    // Newest first, so the top of the listing is cached early
+    for chunk in changes.new_uids.rchunks(HEADER_FETCH_CHUNK) {
+        let messages: Vec<_> = session
+            .uid_fetch(uids_to_sequence(chunk), HEADER_FETCH_ITEMS)
+            .await
+            .map_err(|e| format!("Failed to fetch messages: {}", e))?
+            .filter_map(|result| async { result.ok() })
+            .collect()
+            .await;
+        let headers: Vec<EmailHeader> = messages.iter().map(parse_header).collect();
+        EmailCache::new(account_id)?.store_headers(folder, &headers)?;
+    }
+
+    EmailCache::new(account_id)?.record_sync(folder, &changes)?;
+    Ok(changes.summary(folder))
+}
+
+// Flags as stored in the cache: without the session-only \Recent, in a stable order
+pub fn normalize_flags(mut flags: Vec<String>) -> Vec<String> {
+    flags.retain(|flag| !flag.eq_ignore_ascii_case("\\Recent"));
+    flags.sort();
+    flags.dedup();
+    flags
+}
+
+fn parse_flag_entry(attributes: &[AttributeValue<'_>]) -> Option<(u32, Vec<String>)> {
+    let mut uid = None;
+    let mut flags = Vec::new();
+
+    for attribute in attributes {
+        match attribute {
+            AttributeValue::Uid(value) => uid = Some(*value),
+            AttributeValue::Flags(values) => {
+                flags = values.iter().map(|flag| flag.to_string()).collect()
+            }
+            _ => {}
+        }
+    }
+
+    uid.map(|uid| (uid, normalize_flags(flags)))
+}
+
+// Run a command async-imap has no helper for and hand every untagged response to `handle`
+pub(crate) async fn run_raw<F>(session: &mut ImapSession, command: &str, mut handle: F) -> Result<(), String>
+where
+    F: FnMut(&Response<'_>),
+{
+    let id = session
+        .run_command(command)
+        .await
+        .map_err(|e| format!("Failed to send command: {}", e))?;
+
+    loop {
+        let response = match session.read_response().await {
+            Some(Ok(response)) => response,
+            Some(Err(e)) => return Err(format!("Failed to read response: {}", e)),
+            None => return Err("Connection closed by server".to_string()),
+        };
+
+        match response.parsed() {
+            Response::Done { tag, status, information, .. } if *tag == id => {
+                return match status {
+                    Status::Ok => Ok(()),
+                    _ => Err(format!(
+                        "Command failed: {}",
+                        information.as_deref().unwrap_or("no details")
+                    )),
+                };
+            }
+            parsed => handle(parsed),
+        }
+    }
+}
+
+// Commands sent before their responses are read
+const PIPELINE_BATCH: usize = 20;
+
+// Run many independent commands, e.g. STATUS for every folder. The commands of a batch are
+// sent at once, so a batch costs one round trip. A failing command does not stop the others,
+// it just produces no untagged data for `handle`.
+pub(crate) async fn run_pipelined<F>(session: &mut ImapSession, commands: &[String], mut handle: F) -> Result<(), String>
+where
+    F: FnMut(&Response<'_>),
+{
+    for batch in commands.chunks(PIPELINE_BATCH) {
+        let mut pending = Vec::new();
+        for command in batch {
+            let id = session
+                .run_command(command)
+                .await
+                .map_err(|e| format!("Failed to send command: {}", e))?;
+            pending.push(id);
+        }
+
+        while !pending.is_empty() {
+            let response = match session.read_response().await {
+                Some(Ok(response)) => response,
+                Some(Err(e)) => return Err(format!("Failed to read response: {}", e)),
+                None => return Err("Connection closed by server".to_string()),
+            };
+
+            match response.parsed() {
+                Response::Done { tag, .. } => pending.retain(|id| id != tag),
+                parsed => handle(parsed),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// A mailbox name, ACL identifier or search term as a quoted string. Line breaks can't be
+// quoted (RFC 3501 4.3) and are dropped.
+pub(crate) fn quote(value: &str) -> String {
+    let escaped = value
+        .replace(['\r', '\n'], "")
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"");
+    format!("\"{}\"", escaped)
+}
+
@@ -0,0 +1,377 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+use super::client::EmailHeader;
+
+// One message of a conversation tree
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThreadNode {
+    // None for a message that is referenced but not in the folder
+    pub uid: Option<u32>,
+    pub children: Vec<ThreadNode>,
+}
+
+impl ThreadNode {
+    pub fn latest_uid(&self) -> u32 {
+        self.children
+            .iter()
+            .map(|child| child.latest_uid())
+            .chain(self.uid)
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn earliest_uid(&self) -> u32 {
+        self.children
+            .iter()
+            .map(|child| child.earliest_uid())
+            .chain(self.uid)
+            .min()
+            .unwrap_or(u32::MAX)
+    }
+
+    // UIDs depth first with their depth in the tree
+    pub fn flatten(&self, depth: u32, out: &mut Vec<(u32, u32)>) {
+        let child_depth = match self.uid {
+            Some(uid) => {
+                out.push((uid, depth));
+                depth + 1
+            }
+            None => depth,
+        };
+        for child in &self.children {
+            child.flatten(child_depth, out);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThreadMessage {
+    pub header: EmailHeader,
+    pub depth: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Conversation {
+    pub subject: String,
+    // Thread order: every reply follows the message it answers
+    pub messages: Vec<ThreadMessage>,
+    pub message_count: u32,
+    pub unread_count: u32,
+    pub latest_uid: u32,
+}
+
+// Conversations with the most recent activity first
+pub fn sort_newest_first(threads: &mut [ThreadNode]) {
+    threads.sort_by_key(|thread| std::cmp::Reverse(thread.latest_uid()));
+}
+
+// Attach the headers to the thread trees, messages without a header are left out
+pub fn conversations(threads: &[ThreadNode], headers: &HashMap<u32, EmailHeader>) -> Vec<Conversation> {
+    let mut result = Vec::new();
+
+    for thread in threads {
+        let mut uids = Vec::new();
+        thread.flatten(0, &mut uids);
+
+        let messages: Vec<ThreadMessage> = uids
+            .into_iter()
+            .filter_map(|(uid, depth)| {
+                headers.get(&uid).map(|header| ThreadMessage { header: header.clone(), depth })
+            })
+            .collect();
+        if messages.is_empty() {
+            continue;
+        }
+
+        result.push(Conversation {
+            subject: messages[0].header.subject.clone(),
+            message_count: messages.len() as u32,
+            unread_count: messages.iter().filter(|m| !m.header.is_read).count() as u32,
+            latest_uid: thread.latest_uid(),
+            messages,
+        });
+    }
+
+    result
+}
+
+// Parse the response of UID THREAD (RFC 5256): "THREAD (2)(3 6 (4 23)(44 7 96))"
+pub fn parse_thread_response(text: &str) -> Result<Vec<ThreadNode>, String> {
+    let body = text
+        .get(..6)
+        .filter(|name| name.eq_ignore_ascii_case("THREAD"))
+        .map(|_| &text[6..])
+        .ok_or_else(|| format!("Unexpected THREAD response: {}", text))?;
+
+    let bytes = body.as_bytes();
+    let mut pos = 0;
+    let mut threads = Vec::new();
+
+    loop {
+        skip_spaces(bytes, &mut pos);
+        if pos >= bytes.len() {
+            break;
+        }
+        threads.push(parse_thread_list(bytes, &mut pos)?);
+    }
+
+    Ok(threads)
+}
+
+// "(" numbers [nested lists] ")", the numbers form a chain of replies
+fn parse_thread_list(bytes: &[u8], pos: &mut usize) -> Result<ThreadNode, String> {
+    if bytes.get(*pos) != Some(&b'(') {
+        return Err("Invalid THREAD response".to_string());
+    }
+    *pos += 1;
+
+    let mut uids = Vec::new();
+    let mut nested = Vec::new();
+    loop {
+        skip_spaces(bytes, pos);
+        match bytes.get(*pos) {
+            Some(b')') => {
+                *pos += 1;
+                break;
+            }
+            Some(b'(') => nested.push(parse_thread_list(bytes, pos)?),
+            Some(b) if b.is_ascii_digit() => {
+                let start = *pos;
+                while bytes.get(*pos).is_some_and(|b| b.is_ascii_digit()) {
+                    *pos += 1;
+                }
+                let uid = std::str::from_utf8(&bytes[start..*pos])
+                    .ok()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or("Invalid THREAD response")?;
+                uids.push(uid);
+            }
+            _ => return Err("Invalid THREAD response".to_string()),
+        }
+    }
+
+    // Build the chain from its end: the last message gets the nested branches
+    let mut node = match uids.pop() {
+        Some(uid) => ThreadNode { uid: Some(uid), children: nested },
+        None => return Ok(ThreadNode { uid: None, children: nested }),
+    };
+    while let Some(uid) = uids.pop() {
+        node = ThreadNode { uid: Some(uid), children: vec![node] };
+    }
+    Ok(node)
+}
+
+fn skip_spaces(bytes: &[u8], pos: &mut usize) {
+    while bytes.get(*pos).is_some_and(|b| b.is_ascii_whitespace()) {
+        *pos += 1;
+    }
+}
+
+struct Container {
+    uid: Option<u32>,
+    subject: String,
+    is_reply: bool,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+// Local threading for servers without THREAD, following Jamie Zawinski's algorithm
+// (https://www.jwz.org/doc/threading.html) on Message-ID, In-Reply-To and References
+pub fn thread_headers(headers: &[EmailHeader]) -> Vec<ThreadNode> {
+    let mut containers: Vec<Container> = Vec::new();
+    let mut by_id: HashMap<String, usize> = HashMap::new();
+
+    for header in headers {
+        // Messages without or with a duplicate Message-ID get one of their own
+        let id = match header.message_id {
+            Some(ref id) if !by_id.get(id).is_some_and(|&c| containers[c].uid.is_some()) => id.clone(),
+            _ => format!("<uid-{}@local>", header.uid),
+        };
+        let this = container_for(&mut containers, &mut by_id, &id);
+        let (subject, is_reply) = normalize_subject(&header.subject);
+        containers[this].uid = Some(header.uid);
+        containers[this].subject = subject;
+        containers[this].is_reply = is_reply;
+
+        let mut references = header.references.clone();
+        if let Some(ref in_reply_to) = header.in_reply_to {
+            if references.last() != Some(in_reply_to) {
+                references.push(in_reply_to.clone());
+            }
+        }
+
+        // Link the references to each other unless they already have a parent
+        let mut previous: Option<usize> = None;
+        for reference in &references {
+            let current = container_for(&mut containers, &mut by_id, reference);
+            if let Some(parent) = previous {
+                if containers[current].parent.is_none() && !creates_loop(&containers, parent, current) {
+                    link(&mut containers, parent, current);
+                }
+            }
+            previous = Some(current);
+        }
+
+        // The message itself always belongs under its last reference
+        if let Some(parent) = previous {
+            if !creates_loop(&containers, parent, this) {
+                unlink(&mut containers, this);
+                link(&mut containers, parent, this);
+            }
+        }
+    }
+
+    let roots: Vec<usize> = (0..containers.len())
+        .filter(|&c| containers[c].parent.is_none())
+        .collect();
+    let roots = prune(&mut containers, roots, true);
+    let roots = group_by_subject(&mut containers, roots);
+
+    let mut threads: Vec<ThreadNode> = roots.iter().map(|&root| to_node(&containers, root)).collect();
+    sort_newest_first(&mut threads);
+    threads
+}
+
+fn container_for(containers: &mut Vec<Container>, by_id: &mut HashMap<String, usize>, id: &str) -> usize {
+    *by_id.entry(id.to_string()).or_insert_with(|| {
+        containers.push(Container {
+            uid: None,
+            subject: String::new(),
+            is_reply: false,
+            parent: None,
+            children: Vec::new(),
+        });
+        containers.len() - 1
+    })
+}
+
+// Making `child` a child of `parent` must not create a cycle
+fn creates_loop(containers: &[Container], parent: usize, child: usize) -> bool {
+    let mut current = Some(parent);
+    while let Some(c) = current {
+        if c == child {
+            return true;
+        }
+        current = containers[c].parent;
+    }
+    false
+}
+
+fn link(containers: &mut [Container], parent: usize, child: usize) {
+    containers[child].parent = Some(parent);
+    containers[parent].children.push(child);
+}
+
+fn unlink(containers: &mut [Container], child: usize) {
+    if let Some(parent) = containers[child].parent.take() {
+        containers[parent].children.retain(|&c| c != child);
+    }
+}
+
+// Drop placeholders without children and replace placeholders by their children,
+// at the top level only if that is a single child
+fn prune(containers: &mut [Container], ids: Vec<usize>, top_level: bool) -> Vec<usize> {
+    let mut result = Vec::new();
+
+    for id in ids {
+        let children = containers[id].children.clone();
+        let children = prune(containers, children, false);
+        containers[id].children = children.clone();
+
+        if containers[id].uid.is_none() {
+            if children.is_empty() {
+                continue;
+            }
+            if !top_level || children.len() == 1 {
+                for &child in &children {
+                    containers[child].parent = containers[id].parent;
+                }
+                result.extend(children);
+                continue;
+            }
+        }
+        result.push(id);
+    }
+
+    result
+}
+
+// Threads broken by clients that drop References: a reply joins the thread with the same
+// subject. Placeholders take their subject from the first child.
+fn group_by_subject(containers: &mut [Container], roots: Vec<usize>) -> Vec<usize> {
+    let mut by_subject: HashMap<String, usize> = HashMap::new();
+    let mut result: Vec<usize> = Vec::new();
+
+    for root in roots {
+        let subject = match containers[root].uid {
+            Some(_) => containers[root].subject.clone(),
+            None => containers[root]
+                .children
+                .first()
+                .map(|&c| containers[c].subject.clone())
+                .unwrap_or_default(),
+        };
+        if subject.is_empty() {
+            result.push(root);
+            continue;
+        }
+
+        match by_subject.get(&subject).copied() {
+            Some(existing) if containers[existing].uid.is_none() || (containers[root].is_reply && !containers[existing].is_reply) => {
+                link(containers, existing, root);
+            }
+            Some(existing) if containers[existing].is_reply && !containers[root].is_reply && containers[root].uid.is_some() => {
+                result.retain(|&r| r != existing);
+                link(containers, root, existing);
+                by_subject.insert(subject, root);
+                result.push(root);
+            }
+            Some(_) => result.push(root),
+            None => {
+                by_subject.insert(subject, root);
+                result.push(root);
+            }
+        }
+    }
+
+    result
+}
+
+// Lowercase subject without reply and forward prefixes, and whether it was a reply
+fn normalize_subject(subject: &str) -> (String, bool) {
+    const REPLY: &[&str] = &["re:", "aw:"];
+    const FORWARD: &[&str] = &["fwd:", "fw:", "wg:"];
+
+    let mut rest = subject.trim().to_lowercase();
+    let mut is_reply = false;
+    loop {
+        let prefix = REPLY
+            .iter()
+            .chain(FORWARD)
+            .find(|prefix| rest.starts_with(*prefix))
+            .copied();
+        match prefix {
+            Some(prefix) => {
+                is_reply |= REPLY.contains(&prefix);
+                rest = rest[prefix.len()..].trim_start().to_string();
+            }
+            None => break,
+        }
+    }
+    (rest, is_reply)
+}
+
+fn to_node(containers: &[Container], id: usize) -> ThreadNode {
+    let mut children: Vec<ThreadNode> = containers[id]
+        .children
+        .iter()
+        .map(|&child| to_node(containers, child))
+        .collect();
+    children.sort_by_key(|child| child.earliest_uid());
+
+    ThreadNode { uid: containers[id].uid, children }
+}
@@ -5,7 +5,14 @@ use jmap_client::email::{self, Property as EmailProperty};
 use jmap_client::mailbox::{self, Property as MailboxProperty, Role};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use chrono::{Utc, TimeZone};
+use chrono::{DateTime, FixedOffset, Utc, TimeZone};
+
+use crate::quota::{Quota, QuotaUsage};
+use crate::tags;
+use crate::transfer::RawMessage;
+use crate::trust;
+
+const QUOTA_CAPABILITY: &str = "urn:ietf:params:jmap:quota";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -43,6 +50,8 @@ pub struct JmapEmailHeader {
     pub is_flagged: bool,
     pub is_answered: bool,
     pub is_draft: bool,
+    // Keywords in IMAP notation (\Seen, $label1), the same as EmailHeader.flags
+    pub flags: Vec<String>,
     pub has_attachments: bool,
     pub size: u64,
     pub preview: String,
@@ -96,6 +105,8 @@ pub struct JmapOutgoingEmail {
 pub struct JmapClient {
     client: Option<Arc<Client>>,
     account: Option<JmapAccount>,
+    // For methods jmap-client has no support for, e.g. Quota/get
+    http: Option<reqwest::Client>,
 }
 
 impl JmapClient {
@@ -103,19 +114,31 @@ impl JmapClient {
         Self {
             client: None,
             account: None,
+            http: None,
         }
     }
 
     pub async fn connect(&mut self, account: JmapAccount) -> Result<(), String> {
+        // jmap-client can only accept any certificate or none, it can't be held to a pin.
+        // A certificate that only matches the pin would leave the session open to anyone
+        // answering in its place, so such servers are refused.
+        if trust::probe_url(&account.username, &account.jmap_url).await?.is_some() {
+            return Err(format!(
+                "The certificate of {} is not trusted by the system. JMAP accounts need a certificate \
+                 that verifies normally, pinning a self-signed one is not supported.",
+                account.jmap_url
+            ));
+        }
         let client = Client::new()
             .credentials(Credentials::basic(&account.username, &account.password))
-            .accept_invalid_certs(true)
             .connect(&account.jmap_url)
             .await
             .map_err(|e| format!("JMAP connection failed: {}", e))?;
+        let http = trust::http_client(&account.username, &account.jmap_url).await?;
 
         self.client = Some(Arc::new(client));
         self.account = Some(account);
+        self.http = Some(http);
 
         Ok(())
     }
@@ -123,6 +146,7 @@ impl JmapClient {
     pub async fn disconnect(&mut self) -> Result<(), String> {
         self.client = None;
         self.account = None;
+        self.http = None;
         Ok(())
     }
 
@@ -278,6 +302,7 @@ impl JmapClient {
                     is_flagged,
                     is_answered,
                     is_draft,
+                    flags: keywords.iter().map(|keyword| tags::jmap_to_imap(keyword)).collect(),
                     has_attachments: email.has_attachment(),
                     size: email.size() as u64,
                     preview: email.preview().unwrap_or("").to_string(),
@@ -460,6 +485,16 @@ impl JmapClient {
         Ok(())
     }
 
+    // Set or clear a keyword, given in IMAP notation like the flags of a header
+    pub async fn set_keyword(&self, email_id: &str, flag: &str, set: bool) -> Result<(), String> {
+        let client = self.client.as_ref().ok_or("Not connected")?;
+        client
+            .email_set_keyword(email_id, &tags::imap_to_jmap(flag), set)
+            .await
+            .map_err(|e| format!("Failed to update keyword {}: {}", flag, e))?;
+        Ok(())
+    }
+
     pub async fn delete_email(&self, email_id: &str) -> Result<(), String> {
         let client = self.client.as_ref().ok_or("Not connected")?;
         client
@@ -505,6 +540,54 @@ impl JmapClient {
         Ok(())
     }
 
+    // The message as another account needs it for a copy
+    pub async fn fetch_raw_message(&self, email_id: &str) -> Result<RawMessage, String> {
+        let client = self.client.as_ref().ok_or("Not connected")?;
+
+        let email = client
+            .email_get(
+                email_id,
+                Some(vec![EmailProperty::Id, EmailProperty::BlobId, EmailProperty::Keywords, EmailProperty::ReceivedAt]),
+            )
+            .await
+            .map_err(|e| format!("Failed to get email: {}", e))?
+            .ok_or("Email not found")?;
+
+        let blob_id = email.blob_id().ok_or("Email has no blob")?;
+        Ok(RawMessage {
+            content: self.download_blob(blob_id).await?,
+            flags: email.keywords().iter().map(|keyword| tags::jmap_to_imap(keyword)).collect(),
+            internal_date: email
+                .received_at()
+                .and_then(|ts| Utc.timestamp_opt(ts, 0).single())
+                .map(DateTime::<FixedOffset>::from),
+        })
+    }
+
+    // Email/import of a message from another account, keeps its keywords and receivedAt.
+    // Returns the id of the new email.
+    pub async fn import_message(&self, mailbox_id: &str, message: &RawMessage) -> Result<String, String> {
+        let client = self.client.as_ref().ok_or("Not connected")?;
+
+        let keywords: Vec<String> = message
+            .flags_to_copy()
+            .into_iter()
+            .map(tags::imap_to_jmap)
+            .filter(|keyword| !keyword.starts_with('\\'))
+            .collect();
+        let received_at = message.internal_date.map(|date| date.timestamp());
+
+        let email = client
+            .email_import(message.content.clone(), [mailbox_id], Some(keywords), received_at)
+            .await
+            .map_err(|e| format!("Failed to import email: {}", e))?;
+
+        email
+            .id()
+            .map(|id| id.to_string())
+            .ok_or_else(|| "Server returned no id for the imported email".to_string())
+    }
+
     pub async fn download_blob(&self, blob_id: &str) -> Result<Vec<u8>, String> {
         let client = self.client.as_ref().ok_or("Not connected")?;
         let blob = client
@@ -514,6 +597,56 @@ impl JmapClient {
         Ok(blob)
     }
 
+    // Mail quotas of the account (RFC 9425). Servers without the quota capability have none.
+    pub async fn get_quota(&self) -> Result<Vec<Quota>, String> {
+        let client = self.client.as_ref().ok_or("Not connected")?;
+        let account = self.account.as_ref().ok_or("No account")?;
+        let http = self.http.as_ref().ok_or("Not connected")?;
+
+        let request = serde_json::json!({
+            "using": ["urn:ietf:params:jmap:core", QUOTA_CAPABILITY],
+            "methodCalls": [["Quota/get", { "accountId": client.default_account_id(), "ids": null }, "0"]],
+        });
+
+        let response = http
+            .post(client.session().api_url())
+            .basic_auth(&account.username, Some(&account.password))
+            .header("Content-Type", "application/json")
+            .body(request.to_string())
+            .send()
+            .await
+            .map_err(|e| format!("Failed to fetch quota: {}", e))?;
+
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|e| format!("Failed to read quota response: {}", e))?;
+        if !status.is_success() {
+            let problem: serde_json::Value = serde_json::from_str(&text).unwrap_or_default();
+            if problem["type"].as_str().is_some_and(|ty| ty.ends_with("unknownCapability")) {
+                return Ok(Vec::new());
+            }
+            return Err(format!("Quota request failed with status: {}", status));
+        }
+
+        let body: serde_json::Value = serde_json::from_str(&text)
+            .map_err(|e| format!("Failed to parse quota response: {}", e))?;
+        let method = &body["methodResponses"][0];
+        if method[0] != "Quota/get" {
+            return Err(format!(
+                "Failed to fetch quota: {}",
+                method[1]["type"].as_str().unwrap_or("unexpected response")
+            ));
+        }
+
+        let quotas = method[1]["list"]
+            .as_array()
+            .map(|list| list.iter().filter_map(jmap_quota).collect())
+            .unwrap_or_default();
+        Ok(quotas)
+    }
+
     pub async fn send_email(&self, email: JmapOutgoingEmail) -> Result<String, String> {
         let client = self.client.as_ref().ok_or("Not connected")?;
         let account = self.account.as_ref().ok_or("No account")?;
@@ -805,6 +938,7 @@ impl JmapClient {
                     is_flagged,
                     is_answered,
                     is_draft,
+                    flags: keywords.iter().map(|keyword| tags::jmap_to_imap(keyword)).collect(),
                     has_attachments: email.has_attachment(),
                     size: email.size() as u64,
                     preview: email.preview().unwrap_or("").to_string(),
@@ -816,6 +950,33 @@ impl JmapClient {
     }
 }
 
+// A JMAP quota covers a single resource. Quotas that don't count mail are skipped.
+fn jmap_quota(value: &serde_json::Value) -> Option<Quota> {
+    let counts_mail = match value["types"].as_array() {
+        Some(types) => types.iter().any(|ty| ty == "Mail"),
+        None => true,
+    };
+    if !counts_mail {
+        return None;
+    }
+
+    let usage = QuotaUsage {
+        used: value["used"].as_u64()?,
+        limit: value["hardLimit"].as_u64()?,
+    };
+    let name = value["name"]
+        .as_str()
+        .or_else(|| value["id"].as_str())
+        .unwrap_or_default()
+        .to_string();
+
+    match value["resourceType"].as_str()? {
+        "octets" => Some(Quota { name, storage: Some(usage), messages: None }),
+        "count" => Some(Quota { name, storage: None, messages: Some(usage) }),
+        _ => None,
+    }
+}
+
 fn format_email_address(addr: &jmap_client::email::EmailAddress) -> String {
     let name = addr.name();
     let email = addr.email();
@@ -2,26 +2,43 @@ mod autoconfig;
 mod cache;
 mod caldav;
 mod carddav;
+mod credentials;
 mod imap;
 mod jmap;
+mod oauth;
+mod quota;
 mod sieve;
 mod smtp;
 mod storage;
+mod tags;
+mod transfer;
+mod trust;
 
 use autoconfig::AutoConfigResult;
-use cache::{EmailCache, CacheStats};
+use cache::{EmailCache, CacheStats, FolderHeader};
 use caldav::client::{CalDavClient, Calendar, CalendarEvent, CalDavTask};
 use carddav::client::{CardDavClient, Contact};
+use imap::acl::{AclEntry, MyRights, Namespaces};
 use imap::client::{Email, EmailHeader, Folder, ImapClient, MailAccount};
+use imap::draft::SavedDraft;
+use imap::idle::IdleWatcher;
+use imap::search::{self, SearchQuery};
+use imap::sort::{HeaderPage, SortKey, SortOptions};
+use imap::special_use::FolderRole;
+use imap::thread::{self, Conversation};
+use imap::sync::SyncResult;
+use quota::{Quota, QuotaSummary};
 use jmap::client::{JmapClient, JmapAccount, JmapMailbox, JmapEmailHeader, JmapEmail, JmapOutgoingEmail};
 use sieve::client::{SieveClient, SieveScript, SieveRule, rules_to_sieve_script, parse_sieve_script};
 use smtp::client::{OutgoingEmail, SmtpClient};
 use storage::SavedAccount;
+use tags::{PermanentFlags, Tag};
+use transfer::{MessageLocation, RawMessage, TransferResult, TransferTarget};
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::io::Write;
 use serde::{Deserialize, Serialize};
-use tauri::State;
+use tauri::{AppHandle, State};
 use tokio::sync::Mutex;
 
 fn log_to_file(msg: &str) {
@@ -47,6 +64,8 @@ pub struct ConnectedAccount {
     pub id: String,
     pub display_name: String,
     pub email: String,
+    // None if the server reports no quota
+    pub quota: Option<QuotaSummary>,
 }
 
 pub struct AppState {
@@ -54,6 +73,8 @@ pub struct AppState {
     imap_clients: Arc<Mutex<HashMap<String, ImapClient>>>,
     // Multiple JMAP clients indexed by account ID
     jmap_clients: Arc<Mutex<HashMap<String, JmapClient>>>,
+    // Background IDLE watchers for the INBOX of each IMAP account
+    idle_watchers: Arc<Mutex<HashMap<String, IdleWatcher>>>,
 }
 
 impl Default for AppState {
@@ -61,31 +82,43 @@ impl Default for AppState {
         Self {
             imap_clients: Arc::new(Mutex::new(HashMap::new())),
             jmap_clients: Arc::new(Mutex::new(HashMap::new())),
+            idle_watchers: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 }
 
 #[tauri::command]
-async fn connect(state: State<'_, AppState>, account: MailAccount) -> Result<ConnectedAccount, String> {
+async fn connect(app: AppHandle, state: State<'_, AppState>, account: MailAccount) -> Result<ConnectedAccount, String> {
     let account_id = account.username.clone();
     let display_name = account.display_name.clone();
     let email = account.username.clone();
 
     let mut client = ImapClient::new();
-    client.connect(account).await?;
+    client.set_app_handle(app.clone());
+    client.connect(account.clone()).await?;
+
+    let quota = quota_summary(&client).await;
 
     let mut clients = state.imap_clients.lock().await;
     clients.insert(account_id.clone(), client);
+    drop(clients);
+
+    // Push new mail notifications for the INBOX (replaces a watcher from a previous connect)
+    let watcher = IdleWatcher::spawn(app, account_id.clone(), account, "INBOX".to_string());
+    state.idle_watchers.lock().await.insert(account_id.clone(), watcher);
 
     Ok(ConnectedAccount {
         id: account_id,
         display_name,
         email,
+        quota,
     })
 }
 
 #[tauri::command]
 async fn disconnect(state: State<'_, AppState>, account_id: String) -> Result<(), String> {
+    state.idle_watchers.lock().await.remove(&account_id);
+
     let mut clients = state.imap_clients.lock().await;
     if let Some(mut client) = clients.remove(&account_id) {
         client.disconnect().await?;
@@ -95,6 +128,8 @@ async fn disconnect(state: State<'_, AppState>, account_id: String) -> Result<()
 
 #[tauri::command]
 async fn disconnect_all(state: State<'_, AppState>) -> Result<(), String> {
+    state.idle_watchers.lock().await.clear();
+
     let mut clients = state.imap_clients.lock().await;
     for (_, mut client) in clients.drain() {
         let _ = client.disconnect().await;
@@ -104,31 +139,122 @@ async fn disconnect_all(state: State<'_, AppState>) -> Result<(), String> {
 
 #[tauri::command]
 async fn get_connected_accounts(state: State<'_, AppState>) -> Result<Vec<ConnectedAccount>, String> {
-    let clients = state.imap_clients.lock().await;
-    let accounts: Vec<ConnectedAccount> = clients
+    let clients: Vec<(String, ImapClient)> = state
+        .imap_clients
+        .lock()
+        .await
         .iter()
-        .filter_map(|(id, client)| {
-            client.get_account().map(|acc| ConnectedAccount {
-                id: id.clone(),
-                display_name: acc.display_name.clone(),
-                email: acc.username.clone(),
-            })
-        })
+        .map(|(id, client)| (id.clone(), client.clone()))
         .collect();
+
+    let mut accounts = Vec::new();
+    for (id, client) in clients {
+        let Some(acc) = client.get_account() else {
+            continue;
+        };
+        accounts.push(ConnectedAccount {
+            id,
+            display_name: acc.display_name.clone(),
+            email: acc.username.clone(),
+            quota: quota_summary(&client).await,
+        });
+    }
     Ok(accounts)
 }
 
+// Quota next to the account, a failed lookup only leaves it out
+async fn quota_summary(client: &ImapClient) -> Option<QuotaSummary> {
+    match client.get_quota().await {
+        Ok(quotas) => quota::summarize(&quotas),
+        Err(e) => {
+            println!("[IMAP] Failed to fetch quota: {}", e);
+            None
+        }
+    }
+}
+
 #[tauri::command]
-async fn list_folders(state: State<'_, AppState>, account_id: String) -> Result<Vec<Folder>, String> {
+async fn get_quota(state: State<'_, AppState>, account_id: String) -> Result<Vec<Quota>, String> {
+    let client = imap_client(&state, &account_id).await?;
+    client.get_quota().await
+}
+
+// Clone of a connected client. The client map is not locked while a command runs,
+// so commands of one account can use several pooled sessions in parallel.
+async fn imap_client(state: &State<'_, AppState>, account_id: &str) -> Result<ImapClient, String> {
     let clients = state.imap_clients.lock().await;
-    let client = clients.get(&account_id).ok_or("Account not connected")?;
-    client.list_folders().await
+    clients
+        .get(account_id)
+        .cloned()
+        .ok_or_else(|| "Account not connected".to_string())
+}
+
+#[tauri::command]
+async fn list_folders(
+    state: State<'_, AppState>,
+    account_id: String,
+    subscribed_only: Option<bool>,
+) -> Result<Vec<Folder>, String> {
+    let client = imap_client(&state, &account_id).await?;
+    client.list_folders(subscribed_only).await
+}
+
+#[tauri::command]
+async fn subscribe_folder(state: State<'_, AppState>, account_id: String, folder_name: String) -> Result<(), String> {
+    let client = imap_client(&state, &account_id).await?;
+    client.subscribe_folder(&folder_name).await
+}
+
+#[tauri::command]
+async fn unsubscribe_folder(state: State<'_, AppState>, account_id: String, folder_name: String) -> Result<(), String> {
+    let client = imap_client(&state, &account_id).await?;
+    client.unsubscribe_folder(&folder_name).await
+}
+
+#[tauri::command]
+async fn get_namespaces(state: State<'_, AppState>, account_id: String) -> Result<Namespaces, String> {
+    let client = imap_client(&state, &account_id).await?;
+    client.get_namespaces().await
+}
+
+#[tauri::command]
+async fn get_acl(state: State<'_, AppState>, account_id: String, folder_name: String) -> Result<Vec<AclEntry>, String> {
+    let client = imap_client(&state, &account_id).await?;
+    client.get_acl(&folder_name).await
+}
+
+#[tauri::command]
+async fn my_rights(state: State<'_, AppState>, account_id: String, folder_name: String) -> Result<MyRights, String> {
+    let client = imap_client(&state, &account_id).await?;
+    client.my_rights(&folder_name).await
+}
+
+#[tauri::command]
+async fn set_acl(
+    state: State<'_, AppState>,
+    account_id: String,
+    folder_name: String,
+    identifier: String,
+    rights: String,
+) -> Result<(), String> {
+    let client = imap_client(&state, &account_id).await?;
+    client.set_acl(&folder_name, &identifier, &rights).await
+}
+
+#[tauri::command]
+async fn delete_acl(
+    state: State<'_, AppState>,
+    account_id: String,
+    folder_name: String,
+    identifier: String,
+) -> Result<(), String> {
+    let client = imap_client(&state, &account_id).await?;
+    client.delete_acl(&folder_name, &identifier).await
 }
 
 #[tauri::command]
 async fn select_folder(state: State<'_, AppState>, account_id: String, folder: String) -> Result<(u32, u32), String> {
-    let clients = state.imap_clients.lock().await;
-    let client = clients.get(&account_id).ok_or("Account not connected")?;
+    let client = imap_client(&state, &account_id).await?;
     client.select_folder(&folder).await
 }
 
@@ -140,11 +266,53 @@ async fn fetch_headers(
     start: u32,
     count: u32,
 ) -> Result<Vec<EmailHeader>, String> {
-    let clients = state.imap_clients.lock().await;
-    let client = clients.get(&account_id).ok_or("Account not connected")?;
+    let client = imap_client(&state, &account_id).await?;
     client.fetch_headers(&folder, start, count).await
 }
 
+// Headers in the given order, paged after the last UID of the previous page. Sorted by the
+// server if it supports SORT, otherwise from the cache, which is synced for the first page.
+#[tauri::command]
+async fn fetch_sorted_headers(
+    state: State<'_, AppState>,
+    account_id: String,
+    folder: String,
+    sort: SortOptions,
+    after_uid: Option<u32>,
+    count: u32,
+) -> Result<HeaderPage, String> {
+    let client = imap_client(&state, &account_id).await?;
+    if client.has_capability("SORT") {
+        return client.fetch_sorted_headers(&folder, &sort, after_uid, count).await;
+    }
+
+    if after_uid.is_none() {
+        // The first sync of a folder fetches every header. Newest first only needs the newest
+        // messages, so that page comes straight from the server and the cache fills afterwards.
+        // SELECT alone already creates the sync state, only a completed sync sets synced_uid
+        let synced = EmailCache::new(&account_id)?
+            .get_sync_state(&folder)?
+            .is_some_and(|state| state.synced_uid.is_some());
+        if !synced && sort.key == SortKey::Arrival && sort.reverse {
+            let (total, _) = client.select_folder(&folder).await?;
+            let mut headers = client.fetch_headers(&folder, 0, count).await?;
+            headers.truncate(count as usize);
+            client.spawn_sync(&folder);
+            return Ok(HeaderPage::new(headers, count, total));
+        }
+        client.sync_folder(&folder).await?;
+    }
+    let cache = EmailCache::new(&account_id)?;
+    cache.get_sorted_headers(&folder, &sort, after_uid, count)
+}
+
+// Bring the cached copy of a folder up to date with the server (flags, expunges, new mail)
+#[tauri::command]
+async fn sync_folder(state: State<'_, AppState>, account_id: String, folder: String) -> Result<SyncResult, String> {
+    let client = imap_client(&state, &account_id).await?;
+    client.sync_folder(&folder).await
+}
+
 #[tauri::command]
 async fn fetch_email(
     state: State<'_, AppState>,
@@ -152,23 +320,39 @@ async fn fetch_email(
     folder: String,
     uid: u32,
 ) -> Result<Email, String> {
-    let clients = state.imap_clients.lock().await;
-    let client = clients.get(&account_id).ok_or("Account not connected")?;
+    let client = imap_client(&state, &account_id).await?;
     client.fetch_email(&folder, uid).await
 }
 
+// A message the user opens for reading, marked read according to the account's read marking
+#[tauri::command]
+async fn open_email(
+    state: State<'_, AppState>,
+    account_id: String,
+    folder: String,
+    uid: u32,
+) -> Result<Email, String> {
+    let client = imap_client(&state, &account_id).await?;
+    client.open_email(&folder, uid).await
+}
+
 #[tauri::command]
 async fn mark_read(state: State<'_, AppState>, account_id: String, folder: String, uid: u32) -> Result<(), String> {
-    let clients = state.imap_clients.lock().await;
-    let client = clients.get(&account_id).ok_or("Account not connected")?;
+    let client = imap_client(&state, &account_id).await?;
     client.mark_read(&folder, uid).await
 }
 
+// Moves the message to Trash, unless it is in Trash already or `permanent` is set
 #[tauri::command]
-async fn delete_email(state: State<'_, AppState>, account_id: String, folder: String, uid: u32) -> Result<(), String> {
-    let clients = state.imap_clients.lock().await;
-    let client = clients.get(&account_id).ok_or("Account not connected")?;
-    client.delete_email(&folder, uid).await
+async fn delete_email(
+    state: State<'_, AppState>,
+    account_id: String,
+    folder: String,
+    uid: u32,
+    permanent: Option<bool>,
+) -> Result<(), String> {
+    let client = imap_client(&state, &account_id).await?;
+    client.delete_email(&folder, uid, permanent.unwrap_or(false)).await
 }
 
 #[tauri::command]
@@ -179,30 +363,26 @@ async fn move_email(
     uid: u32,
     target_folder: String,
 ) -> Result<(), String> {
-    let clients = state.imap_clients.lock().await;
-    let client = clients.get(&account_id).ok_or("Account not connected")?;
+    let client = imap_client(&state, &account_id).await?;
     client.move_email(&folder, uid, &target_folder).await
 }
 
 // Flag operations
 #[tauri::command]
 async fn mark_flagged(state: State<'_, AppState>, account_id: String, folder: String, uid: u32) -> Result<(), String> {
-    let clients = state.imap_clients.lock().await;
-    let client = clients.get(&account_id).ok_or("Account not connected")?;
+    let client = imap_client(&state, &account_id).await?;
     client.mark_flagged(&folder, uid).await
 }
 
 #[tauri::command]
 async fn unmark_flagged(state: State<'_, AppState>, account_id: String, folder: String, uid: u32) -> Result<(), String> {
-    let clients = state.imap_clients.lock().await;
-    let client = clients.get(&account_id).ok_or("Account not connected")?;
+    let client = imap_client(&state, &account_id).await?;
     client.unmark_flagged(&folder, uid).await
 }
 
 #[tauri::command]
 async fn mark_unread(state: State<'_, AppState>, account_id: String, folder: String, uid: u32) -> Result<(), String> {
-    let clients = state.imap_clients.lock().await;
-    let client = clients.get(&account_id).ok_or("Account not connected")?;
+    let client = imap_client(&state, &account_id).await?;
     client.mark_unread(&folder, uid).await
 }
 
@@ -214,8 +394,7 @@ async fn add_flags(
     uid: u32,
     flags: Vec<String>,
 ) -> Result<(), String> {
-    let clients = state.imap_clients.lock().await;
-    let client = clients.get(&account_id).ok_or("Account not connected")?;
+    let client = imap_client(&state, &account_id).await?;
     let flag_refs: Vec<&str> = flags.iter().map(|s| s.as_str()).collect();
     client.add_flags(&folder, uid, &flag_refs).await
 }
@@ -228,8 +407,7 @@ async fn remove_flags(
     uid: u32,
     flags: Vec<String>,
 ) -> Result<(), String> {
-    let clients = state.imap_clients.lock().await;
-    let client = clients.get(&account_id).ok_or("Account not connected")?;
+    let client = imap_client(&state, &account_id).await?;
     let flag_refs: Vec<&str> = flags.iter().map(|s| s.as_str()).collect();
     client.remove_flags(&folder, uid, &flag_refs).await
 }
@@ -237,15 +415,13 @@ async fn remove_flags(
 // Folder operations
 #[tauri::command]
 async fn create_folder(state: State<'_, AppState>, account_id: String, folder_name: String) -> Result<(), String> {
-    let clients = state.imap_clients.lock().await;
-    let client = clients.get(&account_id).ok_or("Account not connected")?;
+    let client = imap_client(&state, &account_id).await?;
     client.create_folder(&folder_name).await
 }
 
 #[tauri::command]
 async fn delete_folder(state: State<'_, AppState>, account_id: String, folder_name: String) -> Result<(), String> {
-    let clients = state.imap_clients.lock().await;
-    let client = clients.get(&account_id).ok_or("Account not connected")?;
+    let client = imap_client(&state, &account_id).await?;
     client.delete_folder(&folder_name).await
 }
 
@@ -256,11 +432,28 @@ async fn rename_folder(
     old_name: String,
     new_name: String,
 ) -> Result<(), String> {
-    let clients = state.imap_clients.lock().await;
-    let client = clients.get(&account_id).ok_or("Account not connected")?;
+    let client = imap_client(&state, &account_id).await?;
     client.rename_folder(&old_name, &new_name).await
 }
 
+// Use a folder for a special use (Sent, Trash, Drafts, ...) instead of the one the server
+// marks, None goes back to the server's choice
+#[tauri::command]
+async fn set_folder_role(
+    state: State<'_, AppState>,
+    account_id: String,
+    role: FolderRole,
+    folder: Option<String>,
+) -> Result<(), String> {
+    storage::set_folder_role(&account_id, role, folder.as_deref())?;
+
+    let mut clients = state.imap_clients.lock().await;
+    if let Some(client) = clients.get_mut(&account_id) {
+        client.set_folder_role(role, folder);
+    }
+    Ok(())
+}
+
 // Attachment operations
 #[tauri::command]
 async fn download_attachment(
@@ -271,8 +464,7 @@ async fn download_attachment(
     part_id: String,
     filename: String,
 ) -> Result<String, String> {
-    let clients = state.imap_clients.lock().await;
-    let client = clients.get(&account_id).ok_or("Account not connected")?;
+    let client = imap_client(&state, &account_id).await?;
 
     // Get the attachment data
     let data = client.get_attachment(&folder, uid, &part_id).await?;
@@ -325,8 +517,7 @@ async fn bulk_mark_read(
     folder: String,
     uids: Vec<u32>,
 ) -> Result<(), String> {
-    let clients = state.imap_clients.lock().await;
-    let client = clients.get(&account_id).ok_or("Account not connected")?;
+    let client = imap_client(&state, &account_id).await?;
     client.bulk_mark_read(&folder, &uids).await
 }
 
@@ -337,8 +528,7 @@ async fn bulk_mark_unread(
     folder: String,
     uids: Vec<u32>,
 ) -> Result<(), String> {
-    let clients = state.imap_clients.lock().await;
-    let client = clients.get(&account_id).ok_or("Account not connected")?;
+    let client = imap_client(&state, &account_id).await?;
     client.bulk_mark_unread(&folder, &uids).await
 }
 
@@ -349,8 +539,7 @@ async fn bulk_mark_flagged(
     folder: String,
     uids: Vec<u32>,
 ) -> Result<(), String> {
-    let clients = state.imap_clients.lock().await;
-    let client = clients.get(&account_id).ok_or("Account not connected")?;
+    let client = imap_client(&state, &account_id).await?;
     client.bulk_mark_flagged(&folder, &uids).await
 }
 
@@ -360,10 +549,10 @@ async fn bulk_delete(
     account_id: String,
     folder: String,
     uids: Vec<u32>,
+    permanent: Option<bool>,
 ) -> Result<(), String> {
-    let clients = state.imap_clients.lock().await;
-    let client = clients.get(&account_id).ok_or("Account not connected")?;
-    client.bulk_delete(&folder, &uids).await
+    let client = imap_client(&state, &account_id).await?;
+    client.bulk_delete(&folder, &uids, permanent.unwrap_or(false)).await
 }
 
 #[tauri::command]
@@ -374,51 +563,149 @@ async fn bulk_move(
     uids: Vec<u32>,
     target_folder: String,
 ) -> Result<(), String> {
-    let clients = state.imap_clients.lock().await;
-    let client = clients.get(&account_id).ok_or("Account not connected")?;
+    let client = imap_client(&state, &account_id).await?;
     client.bulk_move(&folder, &uids, &target_folder).await
 }
 
+// Copy or move messages into another account, IMAP or JMAP on either side. A source is
+// removed only once the target has its copy, a failure stops at that message.
+#[tauri::command]
+async fn transfer_messages(
+    state: State<'_, AppState>,
+    sources: Vec<MessageLocation>,
+    target: TransferTarget,
+    remove_source: bool,
+) -> Result<Vec<TransferResult>, String> {
+    let total = sources.len();
+    let mut results = Vec::new();
+
+    for source in sources {
+        println!("[TRANSFER] {} -> {}", source.account_id(), target.account_id());
+        let result = transfer_message(&state, source, &target, remove_source).await;
+        match result {
+            Ok(result) => results.push(result),
+            Err(e) if results.is_empty() => return Err(e),
+            Err(e) => return Err(format!("{} of {} messages transferred, then: {}", results.len(), total, e)),
+        }
+    }
+
+    Ok(results)
+}
+
+async fn transfer_message(
+    state: &State<'_, AppState>,
+    source: MessageLocation,
+    target: &TransferTarget,
+    remove_source: bool,
+) -> Result<TransferResult, String> {
+    let message: RawMessage = match &source {
+        MessageLocation::Imap { account_id, folder, uid } => {
+            let client = imap_client(state, account_id).await?;
+            client.fetch_raw_message(folder, *uid).await?
+        }
+        MessageLocation::Jmap { account_id, email_id } => {
+            let clients = state.jmap_clients.lock().await;
+            let client = clients.get(account_id).ok_or("JMAP account not connected")?;
+            client.fetch_raw_message(email_id).await?
+        }
+    };
+
+    let (uid, email_id) = match target {
+        TransferTarget::Imap { account_id, folder } => {
+            let client = imap_client(state, account_id).await?;
+            (client.import_message(folder, &message).await?, None)
+        }
+        TransferTarget::Jmap { account_id, mailbox_id } => {
+            let clients = state.jmap_clients.lock().await;
+            let client = clients.get(account_id).ok_or("JMAP account not connected")?;
+            (None, Some(client.import_message(mailbox_id, &message).await?))
+        }
+    };
+
+    if remove_source {
+        let removed = match &source {
+            MessageLocation::Imap { account_id, folder, uid } => {
+                let client = imap_client(state, account_id).await?;
+                client.delete_email(folder, *uid, true).await
+            }
+            MessageLocation::Jmap { account_id, email_id } => {
+                let clients = state.jmap_clients.lock().await;
+                let client = clients.get(account_id).ok_or("JMAP account not connected")?;
+                client.delete_email(email_id).await
+            }
+        };
+        removed.map_err(|e| format!("Message copied, but the original could not be removed: {}", e))?;
+    }
+
+    Ok(TransferResult { source, uid, email_id })
+}
+
 #[tauri::command]
 async fn send_email(state: State<'_, AppState>, account_id: String, email: OutgoingEmail) -> Result<(), String> {
     log_to_file(&format!("send_email called for account: {}", account_id));
 
-    let clients = state.imap_clients.lock().await;
-    let client = clients.get(&account_id).ok_or("Account not connected")?;
+    let client = imap_client(&state, &account_id).await?;
 
     let account = client
         .get_account()
         .ok_or("Account not configured")?;
     log_to_file(&format!("Got account: {}:{}", account.smtp_host, account.smtp_port));
 
-    let smtp_client = SmtpClient::new(
+    let mut smtp_client = SmtpClient::new(
         account.smtp_host.clone(),
         account.smtp_port,
+        account.smtp_security_mode(),
         account.username.clone(),
         account.password.clone(),
         account.display_name.clone(),
     );
+    let username = account.username.clone();
+    let oauth_config = account.oauth.clone();
 
-    // Release the lock before sending SMTP
-    drop(clients);
-    log_to_file("Released lock, starting send...");
+    log_to_file("Starting send...");
+
+    if let Some(ref config) = oauth_config {
+        smtp_client = smtp_client.with_oauth_token(oauth::access_token(&username, config).await?);
+    }
 
     // Send email and get raw message bytes
     let raw_message = smtp_client.send_email(email).await?;
     log_to_file("Email sent via SMTP, saving to Sent folder...");
 
     // Save to Sent folder via IMAP
-    let clients = state.imap_clients.lock().await;
-    if let Some(client) = clients.get(&account_id) {
-        match client.append_to_sent(&raw_message).await {
-            Ok(_) => log_to_file("Saved to Sent folder"),
-            Err(e) => log_to_file(&format!("Failed to save to Sent: {}", e)),
-        }
+    match client.append_to_sent(&raw_message).await {
+        Ok(Some(uid)) => log_to_file(&format!("Saved to Sent folder as UID {}", uid)),
+        Ok(None) => log_to_file("Saved to Sent folder"),
+        Err(e) => log_to_file(&format!("Failed to save to Sent: {}", e)),
     }
 
     Ok(())
 }
 
+#[tauri::command]
+async fn save_draft(
+    state: State<'_, AppState>,
+    account_id: String,
+    email: OutgoingEmail,
+    replace_uid: Option<u32>,
+) -> Result<SavedDraft, String> {
+    let client = imap_client(&state, &account_id).await?;
+
+    let account = client
+        .get_account()
+        .ok_or("Account not configured")?;
+    let from = format!("{} <{}>", account.display_name, account.username);
+    let message = smtp::client::build_message(&from, &email, true)?;
+
+    client.save_draft(&message.formatted(), replace_uid).await
+}
+
+#[tauri::command]
+async fn load_draft(state: State<'_, AppState>, account_id: String, folder: String, uid: u32) -> Result<OutgoingEmail, String> {
+    let client = imap_client(&state, &account_id).await?;
+    client.load_draft(&folder, uid).await
+}
+
 #[tauri::command]
 async fn lookup_autoconfig(email: String) -> Result<AutoConfigResult, String> {
     autoconfig::lookup_autoconfig(&email).await
@@ -431,7 +718,15 @@ fn get_saved_accounts() -> Result<Vec<SavedAccount>, String> {
 
 #[tauri::command]
 fn save_account(account: SavedAccount) -> Result<(), String> {
-    storage::save_account(account)
+    let username = account.username.clone();
+    let uses_oauth = account.oauth.is_some();
+    storage::save_account(account)?;
+
+    // The account may have been authorized before it was saved
+    if uses_oauth {
+        oauth::persist_refresh_token(&username)?;
+    }
+    Ok(())
 }
 
 #[tauri::command]
@@ -455,66 +750,120 @@ fn delete_saved_jmap_account(account_id: String) -> Result<(), String> {
     storage::delete_jmap_account(&account_id)
 }
 
-// Sieve commands
+// Credential store commands
 #[tauri::command]
-async fn sieve_list_scripts(host: String, port: u16, username: String, password: String) -> Result<Vec<SieveScript>, String> {
+fn get_credential_store_status() -> Result<credentials::CredentialStatus, String> {
+    credentials::status()
+}
+
+#[tauri::command]
+fn unlock_credential_store(passphrase: String) -> Result<(), String> {
+    credentials::unlock(&passphrase)
+}
+
+#[tauri::command]
+fn lock_credential_store() {
+    credentials::lock()
+}
+
+// OAuth2 commands
+#[tauri::command]
+async fn oauth_authorize(username: String, config: oauth::OAuthConfig) -> Result<(), String> {
+    oauth::authorize(&username, &config).await
+}
+
+#[tauri::command]
+fn oauth_sign_out(username: String) -> Result<(), String> {
+    oauth::sign_out(&username)
+}
+
+// Remove a pinned certificate so a renewed self-signed certificate is pinned again on next connect
+#[tauri::command]
+fn forget_pinned_certificate(username: String, host: String, port: u16) -> Result<(), String> {
+    trust::forget_pin(&username, &host, port)
+}
+
+// DAV and Sieve commands use the password sent by the frontend, otherwise the stored one
+fn resolve_password(username: &str, password: Option<String>) -> Result<String, String> {
+    match password.filter(|p| !p.is_empty()) {
+        Some(password) => Ok(password),
+        None => storage::get_saved_password(username)?
+            .ok_or_else(|| format!("No saved password for {}", username)),
+    }
+}
+
+// Accounts set up with OAuth authenticate with a bearer token, all others with the password
+async fn connect_sieve(host: String, port: u16, username: &str, password: Option<String>) -> Result<SieveClient, String> {
     let mut client = SieveClient::new(host, port);
-    client.connect(&username, &password).await.map_err(|e| e.to_string())?;
+    match storage::get_saved_oauth_config(username)? {
+        Some(config) => {
+            let token = oauth::access_token(username, &config).await?;
+            client
+                .connect_oauth(username, config.mechanism, &token)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        None => {
+            let password = resolve_password(username, password)?;
+            client.connect(username, &password).await.map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(client)
+}
+
+// Sieve commands
+#[tauri::command]
+async fn sieve_list_scripts(host: String, port: u16, username: String, password: Option<String>) -> Result<Vec<SieveScript>, String> {
+    let mut client = connect_sieve(host, port, &username, password).await?;
     let scripts = client.list_scripts().await.map_err(|e| e.to_string())?;
     let _ = client.disconnect().await;
     Ok(scripts)
 }
 
 #[tauri::command]
-async fn sieve_get_script(host: String, port: u16, username: String, password: String, name: String) -> Result<String, String> {
-    let mut client = SieveClient::new(host, port);
-    client.connect(&username, &password).await.map_err(|e| e.to_string())?;
+async fn sieve_get_script(host: String, port: u16, username: String, password: Option<String>, name: String) -> Result<String, String> {
+    let mut client = connect_sieve(host, port, &username, password).await?;
     let content = client.get_script(&name).await.map_err(|e| e.to_string())?;
     let _ = client.disconnect().await;
     Ok(content)
 }
 
 #[tauri::command]
-async fn sieve_save_script(host: String, port: u16, username: String, password: String, name: String, content: String) -> Result<(), String> {
-    let mut client = SieveClient::new(host, port);
-    client.connect(&username, &password).await.map_err(|e| e.to_string())?;
+async fn sieve_save_script(host: String, port: u16, username: String, password: Option<String>, name: String, content: String) -> Result<(), String> {
+    let mut client = connect_sieve(host, port, &username, password).await?;
     client.put_script(&name, &content).await.map_err(|e| e.to_string())?;
     let _ = client.disconnect().await;
     Ok(())
 }
 
 #[tauri::command]
-async fn sieve_activate_script(host: String, port: u16, username: String, password: String, name: String) -> Result<(), String> {
-    let mut client = SieveClient::new(host, port);
-    client.connect(&username, &password).await.map_err(|e| e.to_string())?;
+async fn sieve_activate_script(host: String, port: u16, username: String, password: Option<String>, name: String) -> Result<(), String> {
+    let mut client = connect_sieve(host, port, &username, password).await?;
     client.activate_script(&name).await.map_err(|e| e.to_string())?;
     let _ = client.disconnect().await;
     Ok(())
 }
 
 #[tauri::command]
-async fn sieve_delete_script(host: String, port: u16, username: String, password: String, name: String) -> Result<(), String> {
-    let mut client = SieveClient::new(host, port);
-    client.connect(&username, &password).await.map_err(|e| e.to_string())?;
+async fn sieve_delete_script(host: String, port: u16, username: String, password: Option<String>, name: String) -> Result<(), String> {
+    let mut client = connect_sieve(host, port, &username, password).await?;
     client.delete_script(&name).await.map_err(|e| e.to_string())?;
     let _ = client.disconnect().await;
     Ok(())
 }
 
 #[tauri::command]
-async fn sieve_get_rules(host: String, port: u16, username: String, password: String, script_name: String) -> Result<Vec<SieveRule>, String> {
-    let mut client = SieveClient::new(host, port);
-    client.connect(&username, &password).await.map_err(|e| e.to_string())?;
+async fn sieve_get_rules(host: String, port: u16, username: String, password: Option<String>, script_name: String) -> Result<Vec<SieveRule>, String> {
+    let mut client = connect_sieve(host, port, &username, password).await?;
     let content = client.get_script(&script_name).await.map_err(|e| e.to_string())?;
     let _ = client.disconnect().await;
     Ok(parse_sieve_script(&content))
 }
 
 #[tauri::command]
-async fn sieve_save_rules(host: String, port: u16, username: String, password: String, script_name: String, rules: Vec<SieveRule>) -> Result<(), String> {
+async fn sieve_save_rules(host: String, port: u16, username: String, password: Option<String>, script_name: String, rules: Vec<SieveRule>) -> Result<(), String> {
     let content = rules_to_sieve_script(&rules);
-    let mut client = SieveClient::new(host, port);
-    client.connect(&username, &password).await.map_err(|e| e.to_string())?;
+    let mut client = connect_sieve(host, port, &username, password).await?;
     client.put_script(&script_name, &content).await.map_err(|e| e.to_string())?;
     let _ = client.disconnect().await;
     Ok(())
@@ -522,48 +871,54 @@ async fn sieve_save_rules(host: String, port: u16, username: String, password: S
 
 // CardDAV commands
 #[tauri::command]
-async fn fetch_contacts(host: String, username: String, password: String) -> Result<Vec<Contact>, String> {
+async fn fetch_contacts(host: String, username: String, password: Option<String>) -> Result<Vec<Contact>, String> {
+    let password = resolve_password(&username, password)?;
     let carddav_url = CardDavClient::discover_url(&host, &username);
-    let client = CardDavClient::new(&carddav_url, &username, &password);
+    let client = CardDavClient::new(&carddav_url, &username, &password).await?;
     client.fetch_contacts().await
 }
 
 #[tauri::command]
-async fn test_carddav_connection(host: String, username: String, password: String) -> Result<bool, String> {
+async fn test_carddav_connection(host: String, username: String, password: Option<String>) -> Result<bool, String> {
+    let password = resolve_password(&username, password)?;
     let carddav_url = CardDavClient::discover_url(&host, &username);
-    let client = CardDavClient::new(&carddav_url, &username, &password);
+    let client = CardDavClient::new(&carddav_url, &username, &password).await?;
     client.test_connection().await
 }
 
 #[tauri::command]
-async fn create_contact(host: String, username: String, password: String, contact: Contact) -> Result<String, String> {
+async fn create_contact(host: String, username: String, password: Option<String>, contact: Contact) -> Result<String, String> {
+    let password = resolve_password(&username, password)?;
     let carddav_url = CardDavClient::discover_url(&host, &username);
-    let client = CardDavClient::new(&carddav_url, &username, &password);
+    let client = CardDavClient::new(&carddav_url, &username, &password).await?;
     client.create_contact(&contact).await
 }
 
 #[tauri::command]
-async fn update_contact(host: String, username: String, password: String, contact: Contact) -> Result<(), String> {
+async fn update_contact(host: String, username: String, password: Option<String>, contact: Contact) -> Result<(), String> {
+    let password = resolve_password(&username, password)?;
     let carddav_url = CardDavClient::discover_url(&host, &username);
-    let client = CardDavClient::new(&carddav_url, &username, &password);
+    let client = CardDavClient::new(&carddav_url, &username, &password).await?;
     client.update_contact(&contact).await
 }
 
 #[tauri::command]
-async fn delete_contact(host: String, username: String, password: String, contact_id: String) -> Result<(), String> {
+async fn delete_contact(host: String, username: String, password: Option<String>, contact_id: String) -> Result<(), String> {
+    let password = resolve_password(&username, password)?;
     let carddav_url = CardDavClient::discover_url(&host, &username);
-    let client = CardDavClient::new(&carddav_url, &username, &password);
+    let client = CardDavClient::new(&carddav_url, &username, &password).await?;
     client.delete_contact(&contact_id).await
 }
 
 // CalDAV commands
 #[tauri::command]
-async fn fetch_calendars(host: String, username: String, password: String) -> Result<Vec<Calendar>, String> {
+async fn fetch_calendars(host: String, username: String, password: Option<String>) -> Result<Vec<Calendar>, String> {
+    let password = resolve_password(&username, password)?;
     log_to_file(&format!("[CalDAV] fetch_calendars called with host: {}, username: {}", host, username));
     let caldav_url = CalDavClient::discover_url(&host, &username);
     log_to_file(&format!("[CalDAV] Constructed URL: {}", caldav_url));
     println!("[CalDAV] Fetching calendars from URL: {}", caldav_url);
-    let client = CalDavClient::new(&caldav_url, &username, &password);
+    let client = CalDavClient::new(&caldav_url, &username, &password).await?;
     let result = client.fetch_calendars().await;
     log_to_file(&format!("[CalDAV] Result: {:?}", result));
     println!("[CalDAV] Result: {:?}", result);
@@ -574,13 +929,14 @@ async fn fetch_calendars(host: String, username: String, password: String) -> Re
 async fn fetch_calendar_events(
     host: String,
     username: String,
-    password: String,
+    password: Option<String>,
     calendar_id: String,
     start: String,
     end: String
 ) -> Result<Vec<CalendarEvent>, String> {
+    let password = resolve_password(&username, password)?;
     let caldav_url = CalDavClient::discover_url(&host, &username);
-    let client = CalDavClient::new(&caldav_url, &username, &password);
+    let client = CalDavClient::new(&caldav_url, &username, &password).await?;
     client.fetch_events(&calendar_id, &start, &end).await
 }
 
@@ -588,12 +944,13 @@ async fn fetch_calendar_events(
 async fn create_calendar_event(
     host: String,
     username: String,
-    password: String,
+    password: Option<String>,
     calendar_id: String,
     event: CalendarEvent
 ) -> Result<String, String> {
+    let password = resolve_password(&username, password)?;
     let caldav_url = CalDavClient::discover_url(&host, &username);
-    let client = CalDavClient::new(&caldav_url, &username, &password);
+    let client = CalDavClient::new(&caldav_url, &username, &password).await?;
     client.create_event(&calendar_id, &event).await
 }
 
@@ -601,12 +958,13 @@ async fn create_calendar_event(
 async fn update_calendar_event(
     host: String,
     username: String,
-    password: String,
+    password: Option<String>,
     calendar_id: String,
     event: CalendarEvent
 ) -> Result<(), String> {
+    let password = resolve_password(&username, password)?;
     let caldav_url = CalDavClient::discover_url(&host, &username);
-    let client = CalDavClient::new(&caldav_url, &username, &password);
+    let client = CalDavClient::new(&caldav_url, &username, &password).await?;
     client.update_event(&calendar_id, &event).await
 }
 
@@ -614,12 +972,13 @@ async fn update_calendar_event(
 async fn delete_calendar_event(
     host: String,
     username: String,
-    password: String,
+    password: Option<String>,
     calendar_id: String,
     event_id: String
 ) -> Result<(), String> {
+    let password = resolve_password(&username, password)?;
     let caldav_url = CalDavClient::discover_url(&host, &username);
-    let client = CalDavClient::new(&caldav_url, &username, &password);
+    let client = CalDavClient::new(&caldav_url, &username, &password).await?;
     client.delete_event(&calendar_id, &event_id).await
 }
 
@@ -628,11 +987,12 @@ async fn delete_calendar_event(
 async fn fetch_caldav_tasks(
     host: String,
     username: String,
-    password: String,
+    password: Option<String>,
     calendar_id: String
 ) -> Result<Vec<CalDavTask>, String> {
+    let password = resolve_password(&username, password)?;
     let caldav_url = CalDavClient::discover_url(&host, &username);
-    let client = CalDavClient::new(&caldav_url, &username, &password);
+    let client = CalDavClient::new(&caldav_url, &username, &password).await?;
     client.fetch_tasks(&calendar_id).await
 }
 
@@ -640,12 +1000,13 @@ async fn fetch_caldav_tasks(
 async fn create_caldav_task(
     host: String,
     username: String,
-    password: String,
+    password: Option<String>,
     calendar_id: String,
     task: CalDavTask
 ) -> Result<String, String> {
+    let password = resolve_password(&username, password)?;
     let caldav_url = CalDavClient::discover_url(&host, &username);
-    let client = CalDavClient::new(&caldav_url, &username, &password);
+    let client = CalDavClient::new(&caldav_url, &username, &password).await?;
     client.create_task(&calendar_id, &task).await
 }
 
@@ -653,12 +1014,13 @@ async fn create_caldav_task(
 async fn update_caldav_task(
     host: String,
     username: String,
-    password: String,
+    password: Option<String>,
     calendar_id: String,
     task: CalDavTask
 ) -> Result<(), String> {
+    let password = resolve_password(&username, password)?;
     let caldav_url = CalDavClient::discover_url(&host, &username);
-    let client = CalDavClient::new(&caldav_url, &username, &password);
+    let client = CalDavClient::new(&caldav_url, &username, &password).await?;
     client.update_task(&calendar_id, &task).await
 }
 
@@ -666,12 +1028,13 @@ async fn update_caldav_task(
 async fn delete_caldav_task(
     host: String,
     username: String,
-    password: String,
+    password: Option<String>,
     calendar_id: String,
     task_id: String
 ) -> Result<(), String> {
+    let password = resolve_password(&username, password)?;
     let caldav_url = CalDavClient::discover_url(&host, &username);
-    let client = CalDavClient::new(&caldav_url, &username, &password);
+    let client = CalDavClient::new(&caldav_url, &username, &password).await?;
     client.delete_task(&calendar_id, &task_id).await
 }
 
@@ -706,18 +1069,90 @@ fn update_cache_read_status(account_id: String, folder: String, uid: u32, is_rea
     cache.update_read_status(&folder, uid, is_read)
 }
 
+#[tauri::command]
+fn update_cached_flags(account_id: String, folder: String, uid: u32, flags: Vec<String>) -> Result<(), String> {
+    let cache = EmailCache::new(&account_id)?;
+    cache.update_flags(&folder, uid, &flags)
+}
+
 #[tauri::command]
 fn delete_cached_email(account_id: String, folder: String, uid: u32) -> Result<(), String> {
     let cache = EmailCache::new(&account_id)?;
     cache.delete_email(&folder, uid)
 }
 
+// Cached messages carrying a tag, across all folders
+#[tauri::command]
+fn get_cached_emails_by_tag(account_id: String, keyword: String, limit: Option<u32>) -> Result<Vec<FolderHeader>, String> {
+    let cache = EmailCache::new(&account_id)?;
+    cache.get_headers_with_flag(&keyword, limit.unwrap_or(500))
+}
+
+// Tag commands
+#[tauri::command]
+fn get_tags(account_id: String) -> Result<Vec<Tag>, String> {
+    storage::get_tags(&account_id)
+}
+
+// Returns the list as stored, with keywords filled in and normalized
+#[tauri::command]
+fn save_tags(account_id: String, tags: Vec<Tag>) -> Result<Vec<Tag>, String> {
+    let tags = tags::normalize_tags(tags)?;
+    if !storage::set_tags(&account_id, tags.clone())? {
+        return Err("Account is not saved".to_string());
+    }
+    Ok(tags)
+}
+
+#[tauri::command]
+async fn get_permanent_flags(state: State<'_, AppState>, account_id: String, folder: String) -> Result<PermanentFlags, String> {
+    let client = imap_client(&state, &account_id).await?;
+    client.get_permanent_flags(&folder).await
+}
+
 #[tauri::command]
 fn search_cached_emails(account_id: String, query: String) -> Result<Vec<EmailHeader>, String> {
     let cache = EmailCache::new(&account_id)?;
     cache.search(&query)
 }
 
+// Search on the server, also finds mail that was never cached
+#[tauri::command]
+async fn search_emails(
+    state: State<'_, AppState>,
+    account_id: String,
+    folder: String,
+    query: SearchQuery,
+    limit: Option<u32>,
+) -> Result<Vec<EmailHeader>, String> {
+    let client = imap_client(&state, &account_id).await?;
+    client.search(&folder, &query, limit.unwrap_or(search::DEFAULT_LIMIT)).await
+}
+
+// Conversations of a folder, paged by thread
+#[tauri::command]
+async fn list_threads(
+    state: State<'_, AppState>,
+    account_id: String,
+    folder: String,
+    start: u32,
+    count: u32,
+) -> Result<Vec<Conversation>, String> {
+    let client = imap_client(&state, &account_id).await?;
+    client.list_threads(&folder, start, count).await
+}
+
+// Conversations from the cached headers, threaded locally
+#[tauri::command]
+fn get_cached_threads(account_id: String, folder: String) -> Result<Vec<Conversation>, String> {
+    let cache = EmailCache::new(&account_id)?;
+    let headers = cache.get_headers(&folder, 0, u32::MAX)?;
+    let threads = thread::thread_headers(&headers);
+
+    let by_uid: HashMap<u32, EmailHeader> = headers.into_iter().map(|h| (h.uid, h)).collect();
+    Ok(thread::conversations(&threads, &by_uid))
+}
+
 #[tauri::command]
 fn get_cache_stats(account_id: String) -> Result<CacheStats, String> {
     let cache = EmailCache::new(&account_id)?;
@@ -762,6 +1197,7 @@ pub struct JmapConnectedAccount {
     pub display_name: String,
     pub email: String,
     pub protocol: String,
+    pub quota: Option<QuotaSummary>,
 }
 
 #[tauri::command]
@@ -773,6 +1209,14 @@ async fn jmap_connect(state: State<'_, AppState>, account: JmapAccount) -> Resul
     let mut client = JmapClient::new();
     client.connect(account).await?;
 
+    let quota = match client.get_quota().await {
+        Ok(quotas) => quota::summarize(&quotas),
+        Err(e) => {
+            println!("[JMAP] Failed to fetch quota: {}", e);
+            None
+        }
+    };
+
     let mut clients = state.jmap_clients.lock().await;
     clients.insert(account_id.clone(), client);
 
@@ -781,6 +1225,7 @@ async fn jmap_connect(state: State<'_, AppState>, account: JmapAccount) -> Resul
         display_name,
         email,
         protocol: "jmap".to_string(),
+        quota,
     })
 }
 
@@ -793,6 +1238,13 @@ async fn jmap_disconnect(state: State<'_, AppState>, account_id: String) -> Resu
     Ok(())
 }
 
+#[tauri::command]
+async fn jmap_get_quota(state: State<'_, AppState>, account_id: String) -> Result<Vec<Quota>, String> {
+    let clients = state.jmap_clients.lock().await;
+    let client = clients.get(&account_id).ok_or("JMAP account not connected")?;
+    client.get_quota().await
+}
+
 #[tauri::command]
 async fn jmap_list_mailboxes(state: State<'_, AppState>, account_id: String) -> Result<Vec<JmapMailbox>, String> {
     let clients = state.jmap_clients.lock().await;
@@ -852,6 +1304,19 @@ async fn jmap_unmark_flagged(state: State<'_, AppState>, account_id: String, ema
     client.unmark_flagged(&email_id).await
 }
 
+#[tauri::command]
+async fn jmap_set_keyword(
+    state: State<'_, AppState>,
+    account_id: String,
+    email_id: String,
+    keyword: String,
+    set: bool,
+) -> Result<(), String> {
+    let clients = state.jmap_clients.lock().await;
+    let client = clients.get(&account_id).ok_or("JMAP account not connected")?;
+    client.set_keyword(&email_id, &keyword, set).await
+}
+
 #[tauri::command]
 async fn jmap_delete_email(state: State<'_, AppState>, account_id: String, email_id: String) -> Result<(), String> {
     let clients = state.jmap_clients.lock().await;
@@ -1048,10 +1513,14 @@ pub fn run() {
             disconnect,
             disconnect_all,
             get_connected_accounts,
+            get_quota,
             list_folders,
             select_folder,
             fetch_headers,
+            fetch_sorted_headers,
+            sync_folder,
             fetch_email,
+            open_email,
             mark_read,
             delete_email,
             move_email,
@@ -1061,10 +1530,22 @@ pub fn run() {
             mark_unread,
             add_flags,
             remove_flags,
+            get_permanent_flags,
+            // Tags
+            get_tags,
+            save_tags,
             // Folder operations
             create_folder,
             delete_folder,
             rename_folder,
+            set_folder_role,
+            subscribe_folder,
+            unsubscribe_folder,
+            get_namespaces,
+            get_acl,
+            my_rights,
+            set_acl,
+            delete_acl,
             // Attachment operations
             download_attachment,
             // Bulk operations
@@ -1073,7 +1554,10 @@ pub fn run() {
             bulk_mark_flagged,
             bulk_delete,
             bulk_move,
+            transfer_messages,
             send_email,
+            save_draft,
+            load_draft,
             lookup_autoconfig,
             get_saved_accounts,
             save_account,
@@ -1081,6 +1565,12 @@ pub fn run() {
             get_saved_jmap_accounts,
             save_jmap_account,
             delete_saved_jmap_account,
+            get_credential_store_status,
+            unlock_credential_store,
+            lock_credential_store,
+            oauth_authorize,
+            oauth_sign_out,
+            forget_pinned_certificate,
             sieve_list_scripts,
             sieve_get_script,
             sieve_save_script,
@@ -1100,8 +1590,13 @@ pub fn run() {
             cache_headers,
             cache_email,
             update_cache_read_status,
+            update_cached_flags,
             delete_cached_email,
             search_cached_emails,
+            get_cached_emails_by_tag,
+            search_emails,
+            list_threads,
+            get_cached_threads,
             get_cache_stats,
             clear_cache,
             cleanup_old_cache,
@@ -1122,12 +1617,14 @@ pub fn run() {
             jmap_connect,
             jmap_disconnect,
             jmap_list_mailboxes,
+            jmap_get_quota,
             jmap_fetch_email_list,
             jmap_fetch_email,
             jmap_mark_read,
             jmap_mark_unread,
             jmap_mark_flagged,
             jmap_unmark_flagged,
+            jmap_set_keyword,
             jmap_delete_email,
             jmap_move_email,
             jmap_create_mailbox,
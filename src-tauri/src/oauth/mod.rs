@@ -0,0 +1,289 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::time::timeout;
+use uuid::Uuid;
+
+use crate::storage;
+
+// How long the user has to sign in in the browser
+const AUTHORIZATION_TIMEOUT: Duration = Duration::from_secs(300);
+// Refresh access tokens that expire within this margin
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum OAuthMechanism {
+    #[default]
+    #[serde(rename = "XOAUTH2")]
+    Xoauth2,
+    #[serde(rename = "OAUTHBEARER")]
+    OAuthBearer,
+}
+
+impl OAuthMechanism {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OAuthMechanism::Xoauth2 => "XOAUTH2",
+            OAuthMechanism::OAuthBearer => "OAUTHBEARER",
+        }
+    }
+}
+
+// Provider settings. Endpoints are not hardcoded so any provider (or a local mock server) works.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OAuthConfig {
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub client_id: String,
+    #[serde(default)]
+    pub client_secret: Option<String>,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    // Fixed loopback port for providers that need an exact redirect URI, 0 picks a free port
+    #[serde(default)]
+    pub redirect_port: u16,
+    #[serde(default)]
+    pub mechanism: OAuthMechanism,
+}
+
+#[derive(Debug, Clone)]
+struct Tokens {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_at: Option<Instant>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    refresh_token: Option<String>,
+    #[serde(default)]
+    expires_in: Option<u64>,
+}
+
+// Tokens per username. Refresh tokens are also written to the credential store for saved accounts.
+fn token_cache() -> &'static Mutex<HashMap<String, Tokens>> {
+    static TOKENS: OnceLock<Mutex<HashMap<String, Tokens>>> = OnceLock::new();
+    TOKENS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// Authorization code flow with PKCE (RFC 7636), the code is received on a loopback redirect
+pub async fn authorize(username: &str, config: &OAuthConfig) -> Result<(), String> {
+    let listener = TcpListener::bind(("127.0.0.1", config.redirect_port))
+        .await
+        .map_err(|e| format!("Failed to start OAuth redirect listener: {}", e))?;
+    let port = listener
+        .local_addr()
+        .map_err(|e| format!("Failed to start OAuth redirect listener: {}", e))?
+        .port();
+    let redirect_uri = format!("http://127.0.0.1:{}/", port);
+
+    let verifier = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+    let state = Uuid::new_v4().simple().to_string();
+
+    let mut auth_url = Url::parse(&config.authorization_endpoint)
+        .map_err(|e| format!("Invalid authorization endpoint: {}", e))?;
+    auth_url
+        .query_pairs_mut()
+        .append_pair("response_type", "code")
+        .append_pair("client_id", &config.client_id)
+        .append_pair("redirect_uri", &redirect_uri)
+        .append_pair("scope", &config.scopes.join(" "))
+        .append_pair("state", &state)
+        .append_pair("code_challenge", &challenge)
+        .append_pair("code_challenge_method", "S256")
+        .append_pair("login_hint", username);
+
+    println!("[OAuth] Opening authorization page for {}", username);
+    if let Err(e) = tauri_plugin_opener::open_url(auth_url.as_str(), None::<&str>) {
+        println!("[OAuth] Could not open browser ({}), open manually: {}", e, auth_url);
+    }
+
+    let code = timeout(AUTHORIZATION_TIMEOUT, wait_for_code(&listener, &state))
+        .await
+        .map_err(|_| "OAuth authorization timed out".to_string())??;
+
+    let mut params = vec![
+        ("grant_type", "authorization_code"),
+        ("code", code.as_str()),
+        ("redirect_uri", redirect_uri.as_str()),
+        ("client_id", config.client_id.as_str()),
+        ("code_verifier", verifier.as_str()),
+    ];
+    if let Some(ref secret) = config.client_secret {
+        params.push(("client_secret", secret.as_str()));
+    }
+
+    let response = request_tokens(config, &params).await?;
+    store_tokens(username, response, None);
+    println!("[OAuth] Authorized {}", username);
+    Ok(())
+}
+
+// Valid access token for the user, refreshed if it expired
+pub async fn access_token(username: &str, config: &OAuthConfig) -> Result<String, String> {
+    let cached = token_cache().lock().unwrap().get(username).cloned();
+
+    if let Some(ref tokens) = cached {
+        let fresh = tokens
+            .expires_at
+            .map(|at| at > Instant::now() + EXPIRY_MARGIN)
+            .unwrap_or(true);
+        if fresh {
+            return Ok(tokens.access_token.clone());
+        }
+    }
+
+    let refresh_token = match cached.and_then(|t| t.refresh_token) {
+        Some(refresh_token) => refresh_token,
+        None => storage::get_oauth_refresh_token(username)?
+            .ok_or_else(|| format!("{} is not signed in with OAuth, authorize the account first", username))?,
+    };
+
+    println!("[OAuth] Refreshing access token for {}", username);
+    let mut params = vec![
+        ("grant_type", "refresh_token"),
+        ("refresh_token", refresh_token.as_str()),
+        ("client_id", config.client_id.as_str()),
+    ];
+    if let Some(ref secret) = config.client_secret {
+        params.push(("client_secret", secret.as_str()));
+    }
+
+    let response = request_tokens(config, &params).await?;
+    Ok(store_tokens(username, response, Some(refresh_token)))
+}
+
+// Write the refresh token of a session to the credential store once the account was saved
+pub fn persist_refresh_token(username: &str) -> Result<(), String> {
+    let refresh_token = token_cache()
+        .lock()
+        .unwrap()
+        .get(username)
+        .and_then(|t| t.refresh_token.clone());
+
+    if let Some(refresh_token) = refresh_token {
+        storage::set_oauth_refresh_token(username, Some(&refresh_token))?;
+    }
+    Ok(())
+}
+
+pub fn sign_out(username: &str) -> Result<(), String> {
+    token_cache().lock().unwrap().remove(username);
+    storage::set_oauth_refresh_token(username, None)?;
+    Ok(())
+}
+
+// Initial client response for AUTHENTICATE, before base64 encoding
+pub fn sasl_response(mechanism: OAuthMechanism, username: &str, host: &str, port: u16, token: &str) -> String {
+    match mechanism {
+        OAuthMechanism::Xoauth2 => format!("user={}\x01auth=Bearer {}\x01\x01", username, token),
+        // RFC 7628
+        OAuthMechanism::OAuthBearer => format!(
+            "n,a={},\x01host={}\x01port={}\x01auth=Bearer {}\x01\x01",
+            username, host, port, token
+        ),
+    }
+}
+
+fn store_tokens(username: &str, response: TokenResponse, previous_refresh_token: Option<String>) -> String {
+    // Providers may or may not rotate the refresh token
+    let refresh_token = response.refresh_token.or(previous_refresh_token);
+    if let Some(ref refresh_token) = refresh_token {
+        // Unsaved accounts or a locked store: the token is kept for this session only
+        if let Err(e) = storage::set_oauth_refresh_token(username, Some(refresh_token)) {
+            println!("[OAuth] Could not store refresh token for {}: {}", username, e);
+        }
+    }
+
+    let tokens = Tokens {
+        access_token: response.access_token.clone(),
+        refresh_token,
+        expires_at: response.expires_in.map(|secs| Instant::now() + Duration::from_secs(secs)),
+    };
+    token_cache().lock().unwrap().insert(username.to_string(), tokens);
+
+    response.access_token
+}
+
+async fn request_tokens(config: &OAuthConfig, params: &[(&str, &str)]) -> Result<TokenResponse, String> {
+    let response = reqwest::Client::new()
+        .post(&config.token_endpoint)
+        .form(params)
+        .send()
+        .await
+        .map_err(|e| format!("Token request failed: {}", e))?;
+
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read token response: {}", e))?;
+    if !status.is_success() {
+        return Err(format!("Token request failed ({}): {}", status, body));
+    }
+
+    serde_json::from_str(&body).map_err(|e| format!("Invalid token response: {}", e))
+}
+
+// Serve the loopback redirect until the browser delivers the code (or an error)
+async fn wait_for_code(listener: &TcpListener, state: &str) -> Result<String, String> {
+    loop {
+        let (stream, _) = listener
+            .accept()
+            .await
+            .map_err(|e| format!("OAuth redirect listener failed: {}", e))?;
+
+        let mut reader = BufReader::new(stream);
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line).await.is_err() {
+            continue;
+        }
+
+        // "GET /?code=...&state=... HTTP/1.1"
+        let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+        let params: HashMap<String, String> = match Url::parse(&format!("http://127.0.0.1{}", path)) {
+            Ok(url) => url.query_pairs().into_owned().collect(),
+            Err(_) => HashMap::new(),
+        };
+
+        let result = if let Some(error) = params.get("error") {
+            Some(Err(format!("OAuth authorization failed: {}", error)))
+        } else if let Some(code) = params.get("code") {
+            if params.get("state").map(|s| s.as_str()) == Some(state) {
+                Some(Ok(code.clone()))
+            } else {
+                Some(Err("OAuth state mismatch".to_string()))
+            }
+        } else {
+            // Favicon and other requests of the browser
+            None
+        };
+
+        let (status, page) = match &result {
+            Some(Ok(_)) => ("200 OK", "Sign-in complete. You can close this window."),
+            Some(Err(_)) => ("400 Bad Request", "Sign-in failed. You can close this window."),
+            None => ("404 Not Found", ""),
+        };
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            page.len(),
+            page
+        );
+        let _ = reader.get_mut().write_all(response.as_bytes()).await;
+
+        if let Some(result) = result {
+            return result;
+        }
+    }
+}
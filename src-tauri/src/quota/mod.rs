@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+
+// Usage and limit of one resource. Storage is counted in bytes, messages one by one.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuotaUsage {
+    pub used: u64,
+    pub limit: u64,
+}
+
+impl QuotaUsage {
+    fn fraction(&self) -> f64 {
+        if self.limit == 0 {
+            return 0.0;
+        }
+        self.used as f64 / self.limit as f64
+    }
+}
+
+// One IMAP quota root (RFC 9208) or JMAP quota object (RFC 9425)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Quota {
+    pub name: String,
+    pub storage: Option<QuotaUsage>,
+    pub messages: Option<QuotaUsage>,
+}
+
+// Quota shown next to the account. An account can be under several quotas, for each
+// resource the one closest to its limit is the one that matters.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuotaSummary {
+    pub storage: Option<QuotaUsage>,
+    pub messages: Option<QuotaUsage>,
+}
+
+pub fn summarize(quotas: &[Quota]) -> Option<QuotaSummary> {
+    let tightest = |usages: Vec<QuotaUsage>| {
+        usages
+            .into_iter()
+            .max_by(|a, b| a.fraction().total_cmp(&b.fraction()))
+    };
+
+    let summary = QuotaSummary {
+        storage: tightest(quotas.iter().filter_map(|quota| quota.storage).collect()),
+        messages: tightest(quotas.iter().filter_map(|quota| quota.messages).collect()),
+    };
+    if summary.storage.is_none() && summary.messages.is_none() {
+        return None;
+    }
+    Some(summary)
+}
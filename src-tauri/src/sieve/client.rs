@@ -8,6 +8,9 @@ use tokio::net::TcpStream;
 use tokio::time::timeout;
 use tokio_native_tls::TlsStream;
 
+use crate::oauth::{self, OAuthMechanism};
+use crate::trust;
+
 const READ_TIMEOUT: Duration = Duration::from_secs(30);
 
 // ManageSieve protocol commands
@@ -79,6 +82,16 @@ impl SieveClient {
     }
 
     pub async fn connect(&mut self, username: &str, password: &str) -> io::Result<()> {
+        self.open(username).await?;
+        self.authenticate(username, password).await
+    }
+
+    pub async fn connect_oauth(&mut self, username: &str, mechanism: OAuthMechanism, token: &str) -> io::Result<()> {
+        self.open(username).await?;
+        self.authenticate_oauth(username, mechanism, token).await
+    }
+
+    async fn open(&mut self, username: &str) -> io::Result<()> {
         let tcp_stream = TcpStream::connect(format!("{}:{}", self.host, self.port)).await?;
 
         // Read initial server greeting
@@ -91,17 +104,19 @@ impl SieveClient {
             let tcp_stream = reader.into_inner();
             let tcp_stream = self.send_starttls(tcp_stream).await?;
 
-            // Upgrade to TLS
-            let connector = TlsConnector::builder()
-                .danger_accept_invalid_certs(true)
-                .build()
-                .map_err(|e| Error::new(ErrorKind::Other, e))?;
-
-            let connector = tokio_native_tls::TlsConnector::from(connector);
-            let tls_stream = connector
-                .connect(&self.host, tcp_stream)
-                .await
-                .map_err(|e| Error::new(ErrorKind::Other, e))?;
+            // Upgrade to TLS, self-signed certificates are only accepted if pinned
+            let tls_stream = match self.upgrade_tls(tcp_stream, false).await {
+                Ok(tls_stream) => tls_stream,
+                Err(e) => {
+                    println!("[SIEVE] Certificate of {}:{} not verified ({}), checking pin", self.host, self.port, e);
+
+                    // The failed handshake used up the connection, start over
+                    let tcp_stream = self.open_starttls().await?;
+                    let tls_stream = self.upgrade_tls(tcp_stream, true).await?;
+                    self.check_pinned_certificate(username, &tls_stream)?;
+                    tls_stream
+                }
+            };
 
             let mut tls_reader = BufReader::new(tls_stream);
 
@@ -112,12 +127,43 @@ impl SieveClient {
             self.stream = Some(StreamType::Plain(reader));
         }
 
-        // Authenticate
-        self.authenticate(username, password).await?;
-
         Ok(())
     }
 
+    async fn open_starttls(&self) -> io::Result<TcpStream> {
+        let tcp_stream = TcpStream::connect(format!("{}:{}", self.host, self.port)).await?;
+        let mut reader = BufReader::new(tcp_stream);
+        self.read_capabilities(&mut reader).await?;
+        self.send_starttls(reader.into_inner()).await
+    }
+
+    async fn upgrade_tls(&self, stream: TcpStream, accept_invalid_certs: bool) -> io::Result<TlsStream<TcpStream>> {
+        let connector = TlsConnector::builder()
+            .danger_accept_invalid_certs(accept_invalid_certs)
+            .build()
+            .map_err(|e| Error::new(ErrorKind::Other, e))?;
+
+        let connector = tokio_native_tls::TlsConnector::from(connector);
+        connector
+            .connect(&self.host, stream)
+            .await
+            .map_err(|e| Error::new(ErrorKind::Other, e))
+    }
+
+    fn check_pinned_certificate(&self, username: &str, tls_stream: &TlsStream<TcpStream>) -> io::Result<()> {
+        let certificate = tls_stream
+            .get_ref()
+            .peer_certificate()
+            .map_err(|e| Error::new(ErrorKind::Other, e))?
+            .ok_or_else(|| Error::new(ErrorKind::Other, "Server did not send a certificate"))?;
+        let der = certificate
+            .to_der()
+            .map_err(|e| Error::new(ErrorKind::Other, e))?;
+
+        trust::check_pin(username, &self.host, self.port, &trust::fingerprint(&der))
+            .map_err(|e| Error::new(ErrorKind::Other, e))
+    }
+
     async fn send_starttls(&self, mut stream: TcpStream) -> io::Result<TcpStream> {
         stream
             .write_all(format!("{}\r\n", CMD_STARTTLS).as_bytes())
@@ -174,6 +220,28 @@ impl SieveClient {
         }
     }
 
+    async fn authenticate_oauth(&mut self, username: &str, mechanism: OAuthMechanism, token: &str) -> io::Result<()> {
+        let initial_response = oauth::sasl_response(mechanism, username, &self.host, self.port, token);
+        let encoded = BASE64.encode(initial_response.as_bytes());
+
+        let command = format!("{} \"{}\" \"{}\"\r\n", CMD_AUTHENTICATE, mechanism.as_str(), encoded);
+        self.send_command(&command).await?;
+
+        loop {
+            let line = self.read_line().await?;
+            let trimmed = line.trim();
+
+            if trimmed.starts_with(RESP_OK) {
+                return Ok(());
+            } else if trimmed.starts_with(RESP_NO) || trimmed.starts_with(RESP_BYE) {
+                return Err(Error::new(ErrorKind::PermissionDenied, trimmed.to_string()));
+            } else if !trimmed.is_empty() && !trimmed.starts_with('{') {
+                // Error challenge with details, an empty response lets the server answer NO
+                self.send_command("\"\"\r\n").await?;
+            }
+        }
+    }
+
     pub async fn disconnect(&mut self) -> io::Result<()> {
         if self.stream.is_some() {
             let _ = self.send_command(&format!("{}\r\n", CMD_LOGOUT)).await;
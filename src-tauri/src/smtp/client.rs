@@ -1,12 +1,16 @@
 use base64::Engine;
 use lettre::{
+    address::Envelope,
     message::{header::ContentType, Mailbox, MultiPart, SinglePart, Attachment, Body},
-    transport::smtp::authentication::Credentials,
+    transport::smtp::authentication::{Credentials, Mechanism},
     AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
 };
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
+use crate::imap::client::SecurityMode;
+use crate::trust::{self, Handshake};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct OutgoingAttachment {
@@ -31,15 +35,18 @@ pub struct OutgoingEmail {
 pub struct SmtpClient {
     host: String,
     port: u16,
+    security: SecurityMode,
     username: String,
     password: String,
     display_name: String,
+    oauth_token: Option<String>,
 }
 
 impl SmtpClient {
     pub fn new(
         host: String,
         port: u16,
+        security: SecurityMode,
         username: String,
         password: String,
         display_name: String,
@@ -47,161 +54,73 @@ impl SmtpClient {
         Self {
             host,
             port,
+            security,
             username,
             password,
             display_name,
+            oauth_token: None,
         }
     }
 
+    // Authenticate with an OAuth2 access token (XOAUTH2) instead of the password
+    pub fn with_oauth_token(mut self, token: String) -> Self {
+        self.oauth_token = Some(token);
+        self
+    }
+
     pub async fn send_email(&self, email: OutgoingEmail) -> Result<Vec<u8>, String> {
         println!("[SMTP] Building message...");
 
-        // Build the from address
-        let from_mailbox: Mailbox = format!("{} <{}>", self.display_name, self.username)
-            .parse()
-            .map_err(|e| format!("Invalid from address: {}", e))?;
-        println!("[SMTP] From: {:?}", from_mailbox);
-
-        // Start building the message
-        let mut message_builder = Message::builder()
-            .from(from_mailbox)
-            .subject(&email.subject);
-
-        // Add recipients
-        for to in &email.to {
-            let mailbox: Mailbox = to
-                .parse()
-                .map_err(|e| format!("Invalid to address '{}': {}", to, e))?;
-            message_builder = message_builder.to(mailbox);
-        }
-
-        for cc in &email.cc {
-            let mailbox: Mailbox = cc
-                .parse()
-                .map_err(|e| format!("Invalid cc address '{}': {}", cc, e))?;
-            message_builder = message_builder.cc(mailbox);
-        }
-
-        for bcc in &email.bcc {
-            let mailbox: Mailbox = bcc
-                .parse()
-                .map_err(|e| format!("Invalid bcc address '{}': {}", bcc, e))?;
-            message_builder = message_builder.bcc(mailbox);
-        }
-
-        // Add reply-to header if replying
-        if let Some(ref message_id) = email.reply_to_message_id {
-            message_builder = message_builder.in_reply_to(message_id.clone());
-        }
+        let from = format!("{} <{}>", self.display_name, self.username);
+        let message = build_message(&from, &email, false)?;
 
-        // Build the body
-        let has_attachments = email.attachments.as_ref().map(|a| !a.is_empty()).unwrap_or(false);
-
-        let message = if has_attachments {
-            // Build the text/html alternative part
-            let body_part = if let Some(ref html) = email.body_html {
-                MultiPart::alternative()
-                    .singlepart(
-                        SinglePart::builder()
-                            .header(ContentType::TEXT_PLAIN)
-                            .body(email.body_text.clone()),
-                    )
-                    .singlepart(
-                        SinglePart::builder()
-                            .header(ContentType::TEXT_HTML)
-                            .body(html.clone()),
-                    )
-            } else {
-                MultiPart::alternative()
-                    .singlepart(
-                        SinglePart::builder()
-                            .header(ContentType::TEXT_PLAIN)
-                            .body(email.body_text.clone()),
-                    )
-            };
-
-            // Start with mixed multipart (body + attachments)
-            let mut mixed = MultiPart::mixed().multipart(body_part);
-
-            // Add attachments
-            if let Some(ref attachments) = email.attachments {
-                for att in attachments {
-                    // Decode base64 data
-                    let data = base64::Engine::decode(
-                        &base64::engine::general_purpose::STANDARD,
-                        &att.data
-                    ).map_err(|e| format!("Failed to decode attachment data: {}", e))?;
-
-                    // Parse content type
-                    let content_type: ContentType = att.mime_type.parse()
-                        .unwrap_or(ContentType::parse("application/octet-stream").unwrap());
-
-                    // Create attachment
-                    let attachment = Attachment::new(att.filename.clone())
-                        .body(Body::new(data), content_type);
-
-                    mixed = mixed.singlepart(attachment);
-                }
-            }
+        println!("[SMTP] Message built, creating transport...");
 
-            message_builder
-                .multipart(mixed)
-                .map_err(|e| format!("Failed to build message: {}", e))?
-        } else if let Some(ref html) = email.body_html {
-            // Multipart message with text and HTML (no attachments)
-            message_builder
-                .multipart(
-                    MultiPart::alternative()
-                        .singlepart(
-                            SinglePart::builder()
-                                .header(ContentType::TEXT_PLAIN)
-                                .body(email.body_text.clone()),
-                        )
-                        .singlepart(
-                            SinglePart::builder()
-                                .header(ContentType::TEXT_HTML)
-                                .body(html.clone()),
-                        ),
-                )
-                .map_err(|e| format!("Failed to build message: {}", e))?
-        } else {
-            // Plain text only
-            message_builder
-                .body(email.body_text.clone())
-                .map_err(|e| format!("Failed to build message: {}", e))?
+        // Create SMTP transport
+        // lettre only implements XOAUTH2, so it is used for OAUTHBEARER accounts as well
+        let (creds, mechanisms) = match self.oauth_token {
+            Some(ref token) => (
+                Credentials::new(self.username.clone(), token.clone()),
+                vec![Mechanism::Xoauth2],
+            ),
+            None => (
+                Credentials::new(self.username.clone(), self.password.clone()),
+                vec![Mechanism::Plain, Mechanism::Login],
+            ),
         };
 
-        println!("[SMTP] Message built, creating transport...");
-
-        // Create SMTP transport (accept self-signed certificates)
-        let creds = Credentials::new(self.username.clone(), self.password.clone());
+        // lettre can't check a pin itself: a self-signed certificate that matches the pin
+        // on a probe connection becomes an additional root for the real one
+        let pinned = match self.security {
+            SecurityMode::None => None,
+            SecurityMode::StartTls => trust::probe(&self.username, &self.host, self.port, Handshake::SmtpStartTls).await?,
+            SecurityMode::Tls => trust::probe(&self.username, &self.host, self.port, Handshake::Implicit).await?,
+        };
 
-        let tls_params = lettre::transport::smtp::client::TlsParameters::builder(self.host.clone())
-            .dangerous_accept_invalid_certs(true)
+        let mut tls_builder = lettre::transport::smtp::client::TlsParameters::builder(self.host.clone());
+        if let Some(der) = pinned {
+            let certificate = lettre::transport::smtp::client::Certificate::from_der(der)
+                .map_err(|e| format!("Failed to load pinned certificate: {}", e))?;
+            tls_builder = tls_builder.add_root_certificate(certificate);
+        }
+        let tls_params = tls_builder
             .build()
             .map_err(|e| format!("Failed to build TLS parameters: {}", e))?;
-        println!("[SMTP] TLS params built, port: {}", self.port);
-
-        // Port 465 uses implicit SSL, Port 587 uses STARTTLS
-        let mailer: AsyncSmtpTransport<Tokio1Executor> = if self.port == 465 {
-            // Implicit TLS (SSL) for port 465
-            AsyncSmtpTransport::<Tokio1Executor>::relay(&self.host)
-                .map_err(|e| format!("Failed to create SMTP transport: {}", e))?
-                .port(self.port)
-                .credentials(creds)
-                .tls(lettre::transport::smtp::client::Tls::Wrapper(tls_params))
-                .timeout(Some(Duration::from_secs(30)))
-                .build()
-        } else {
-            // STARTTLS for port 587 and others
-            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.host)
-                .map_err(|e| format!("Failed to create SMTP transport: {}", e))?
-                .port(self.port)
-                .credentials(creds)
-                .tls(lettre::transport::smtp::client::Tls::Required(tls_params))
-                .timeout(Some(Duration::from_secs(30)))
-                .build()
+        println!("[SMTP] TLS params built, port: {}, security: {:?}", self.port, self.security);
+
+        let tls = match self.security {
+            SecurityMode::None => lettre::transport::smtp::client::Tls::None,
+            SecurityMode::StartTls => lettre::transport::smtp::client::Tls::Required(tls_params),
+            SecurityMode::Tls => lettre::transport::smtp::client::Tls::Wrapper(tls_params),
         };
+
+        let mailer: AsyncSmtpTransport<Tokio1Executor> = AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&self.host)
+            .port(self.port)
+            .credentials(creds)
+            .authentication(mechanisms)
+            .tls(tls)
+            .timeout(Some(Duration::from_secs(30)))
+            .build();
         println!("[SMTP] Mailer created, sending...");
 
         // Get raw message bytes before sending
@@ -217,3 +136,132 @@ impl SmtpClient {
         Ok(raw_message)
     }
 }
+
+// Build the MIME message of an outgoing email, from is "Name <address>". Drafts are stored
+// in the Drafts folder instead of sent.
+pub fn build_message(from: &str, email: &OutgoingEmail, draft: bool) -> Result<Message, String> {
+    // Build the from address
+    let from_mailbox: Mailbox = from
+        .parse()
+        .map_err(|e| format!("Invalid from address: {}", e))?;
+    println!("[SMTP] From: {:?}", from_mailbox);
+
+    // Start building the message
+    let mut message_builder = Message::builder()
+        .from(from_mailbox.clone())
+        .subject(&email.subject);
+
+    // A draft keeps its Bcc header and may have no recipients yet, so it gets an
+    // envelope of its own instead of one derived from the recipients
+    if draft {
+        let envelope = Envelope::new(Some(from_mailbox.email.clone()), vec![from_mailbox.email.clone()])
+            .map_err(|e| format!("Failed to build envelope: {}", e))?;
+        message_builder = message_builder.keep_bcc().envelope(envelope);
+    }
+
+    // Add recipients
+    for to in &email.to {
+        let mailbox: Mailbox = to
+            .parse()
+            .map_err(|e| format!("Invalid to address '{}': {}", to, e))?;
+        message_builder = message_builder.to(mailbox);
+    }
+
+    for cc in &email.cc {
+        let mailbox: Mailbox = cc
+            .parse()
+            .map_err(|e| format!("Invalid cc address '{}': {}", cc, e))?;
+        message_builder = message_builder.cc(mailbox);
+    }
+
+    for bcc in &email.bcc {
+        let mailbox: Mailbox = bcc
+            .parse()
+            .map_err(|e| format!("Invalid bcc address '{}': {}", bcc, e))?;
+        message_builder = message_builder.bcc(mailbox);
+    }
+
+    // Add reply-to header if replying
+    if let Some(ref message_id) = email.reply_to_message_id {
+        message_builder = message_builder.in_reply_to(message_id.clone());
+    }
+
+    // Build the body
+    let has_attachments = email.attachments.as_ref().map(|a| !a.is_empty()).unwrap_or(false);
+
+    let message = if has_attachments {
+        // Build the text/html alternative part
+        let body_part = if let Some(ref html) = email.body_html {
+            MultiPart::alternative()
+                .singlepart(
+                    SinglePart::builder()
+                        .header(ContentType::TEXT_PLAIN)
+                        .body(email.body_text.clone()),
+                )
+                .singlepart(
+                    SinglePart::builder()
+                        .header(ContentType::TEXT_HTML)
+                        .body(html.clone()),
+                )
+        } else {
+            MultiPart::alternative()
+                .singlepart(
+                    SinglePart::builder()
+                        .header(ContentType::TEXT_PLAIN)
+                        .body(email.body_text.clone()),
+                )
+        };
+
+        // Start with mixed multipart (body + attachments)
+        let mut mixed = MultiPart::mixed().multipart(body_part);
+
+        // Add attachments
+        if let Some(ref attachments) = email.attachments {
+            for att in attachments {
+                // Decode base64 data
+                let data = base64::Engine::decode(
+                    &base64::engine::general_purpose::STANDARD,
+                    &att.data
+                ).map_err(|e| format!("Failed to decode attachment data: {}", e))?;
+
+                // Parse content type
+                let content_type: ContentType = att.mime_type.parse()
+                    .unwrap_or(ContentType::parse("application/octet-stream").unwrap());
+
+                // Create attachment
+                let attachment = Attachment::new(att.filename.clone())
+                    .body(Body::new(data), content_type);
+
+                mixed = mixed.singlepart(attachment);
+            }
+        }
+
+        message_builder
+            .multipart(mixed)
+            .map_err(|e| format!("Failed to build message: {}", e))?
+    } else if let Some(ref html) = email.body_html {
+        // Multipart message with text and HTML (no attachments)
+        message_builder
+            .multipart(
+                MultiPart::alternative()
+                    .singlepart(
+                        SinglePart::builder()
+                            .header(ContentType::TEXT_PLAIN)
+                            .body(email.body_text.clone()),
+                    )
+                    .singlepart(
+                        SinglePart::builder()
+                            .header(ContentType::TEXT_HTML)
+                            .body(html.clone()),
+                    ),
+            )
+            .map_err(|e| format!("Failed to build message: {}", e))?
+    } else {
+        // Plain text only
+        message_builder
+            .body(email.body_text.clone())
+            .map_err(|e| format!("Failed to build message: {}", e))?
+    };
+
+    Ok(message)
+}
@@ -1,7 +1,15 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 
+use crate::credentials;
+use crate::imap::client::SecurityMode;
+use crate::imap::read_marking::ReadMarking;
+use crate::imap::special_use::FolderRole;
+use crate::oauth::OAuthConfig;
+use crate::tags::{self, Tag};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct EmailSignature {
@@ -44,7 +52,8 @@ pub struct SavedAccount {
     pub imap_port: u16,
     pub smtp_host: String,
     pub smtp_port: u16,
-    // Password is optional - user can choose to save it or not
+    // Password is optional - user can choose to save it or not.
+    // It lives in the credential store, the field is only filled in when loading.
     #[serde(default)]
     pub password: Option<String>,
     // Cache settings
@@ -62,6 +71,35 @@ pub struct SavedAccount {
     // Vacation settings
     #[serde(default)]
     pub vacation: Option<VacationSettings>,
+    // SHA-256 fingerprints of self-signed certificates trusted on first use, keyed by "host:port"
+    #[serde(default)]
+    pub pinned_certificates: HashMap<String, String>,
+    // OAuth2 sign-in instead of a password, the refresh token is kept in the credential store
+    #[serde(default)]
+    pub oauth: Option<OAuthConfig>,
+    // Unset for accounts saved before the security mode was configurable
+    #[serde(default)]
+    pub imap_security: Option<SecurityMode>,
+    #[serde(default)]
+    pub smtp_security: Option<SecurityMode>,
+    // Parallel IMAP connections, unset uses the default of the session pool
+    #[serde(default)]
+    pub max_connections: Option<usize>,
+    // When opened mail is marked read, unset means immediately
+    #[serde(default)]
+    pub read_marking: Option<ReadMarking>,
+    // Folders picked by the user for special uses, overriding the server's markings
+    #[serde(default)]
+    pub folder_roles: HashMap<FolderRole, String>,
+    // Folder pane shows only subscribed folders
+    #[serde(default)]
+    pub subscribed_only: bool,
+    // Compress the IMAP connection, for slow links
+    #[serde(default)]
+    pub compress: bool,
+    // Tags with their keyword and colour, unset means the default labels
+    #[serde(default)]
+    pub tags: Option<Vec<Tag>>,
 }
 
 fn default_cache_days() -> u32 { 30 }
@@ -75,7 +113,8 @@ pub struct SavedJmapAccount {
     pub display_name: String,
     pub username: String,
     pub jmap_url: String,
-    // Password is optional - user can choose to save it or not
+    // Password is optional - user can choose to save it or not.
+    // It lives in the credential store, the field is only filled in when loading.
     #[serde(default)]
     pub password: Option<String>,
     // Protocol marker for frontend
@@ -86,6 +125,11 @@ pub struct SavedJmapAccount {
     // Vacation settings
     #[serde(default)]
     pub vacation: Option<VacationSettings>,
+    // SHA-256 fingerprints of self-signed certificates trusted on first use, keyed by "host:port"
+    #[serde(default)]
+    pub pinned_certificates: HashMap<String, String>,
+    #[serde(default)]
+    pub tags: Option<Vec<Tag>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -111,23 +155,147 @@ fn get_config_path() -> Result<PathBuf, String> {
     Ok(config_dir.join("accounts.json"))
 }
 
-pub fn load_accounts() -> Result<Vec<SavedAccount>, String> {
+fn read_config() -> Result<AppConfig, String> {
     let config_path = get_config_path()?;
 
     if !config_path.exists() {
-        return Ok(vec![]);
+        return Ok(AppConfig::default());
     }
 
     let content = fs::read_to_string(&config_path)
         .map_err(|e| format!("Failed to read config: {}", e))?;
 
-    let config: AppConfig = serde_json::from_str(&content)
-        .map_err(|e| format!("Failed to parse config: {}", e))?;
+    serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse config: {}", e))
+}
+
+fn write_config(config: &AppConfig) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(config)
+        .map_err(|e| format!("Failed to serialize config: {}", e))?;
+
+    fs::write(get_config_path()?, content)
+        .map_err(|e| format!("Failed to write config: {}", e))
+}
+
+pub fn load_accounts() -> Result<Vec<SavedAccount>, String> {
+    let mut config = read_config()?;
+
+    // Move plaintext passwords of older versions into the credential store
+    let mut migrated = false;
+    for account in config.accounts.iter_mut() {
+        if move_password_to_store(&account.id, &mut account.password) {
+            migrated = true;
+        }
+    }
+    if migrated {
+        write_config(&config)?;
+    }
+
+    for account in config.accounts.iter_mut() {
+        fill_password(&account.id, &mut account.password);
+    }
 
     Ok(config.accounts)
 }
 
-pub fn save_account(account: SavedAccount) -> Result<(), String> {
+// Returns true if a plaintext password was moved out of the config
+fn move_password_to_store(account_id: &str, password: &mut Option<String>) -> bool {
+    let plaintext = match password.as_ref().filter(|p| !p.is_empty()) {
+        Some(plaintext) => plaintext,
+        None => return false,
+    };
+
+    match credentials::set_password(account_id, plaintext) {
+        Ok(()) => {
+            println!("[Storage] Moved password of account {} into the credential store", account_id);
+            *password = None;
+            true
+        }
+        Err(e) => {
+            // Stays in the config until the store can be written (e.g. after unlocking)
+            println!("[Storage] Could not migrate password of account {}: {}", account_id, e);
+            false
+        }
+    }
+}
+
+fn fill_password(account_id: &str, password: &mut Option<String>) {
+    if password.is_some() {
+        return;
+    }
+    match credentials::get_password(account_id) {
+        Ok(stored) => *password = stored,
+        Err(e) => println!("[Storage] Could not load password of account {}: {}", account_id, e),
+    }
+}
+
+// Store or remove the account's password and clear it so it isn't written to the config
+fn store_password(account_id: &str, password: &mut Option<String>) -> Result<(), String> {
+    match password.take().filter(|p| !p.is_empty()) {
+        Some(password) => credentials::set_password(account_id, &password),
+        None => {
+            if let Err(e) = credentials::delete_password(account_id) {
+                println!("[Storage] Could not remove password of account {}: {}", account_id, e);
+            }
+            Ok(())
+        }
+    }
+}
+
+fn oauth_key(account_id: &str) -> String {
+    format!("{}:oauth", account_id)
+}
+
+// OAuth refresh tokens live in the credential store next to the password of the saved account
+pub fn get_oauth_refresh_token(username: &str) -> Result<Option<String>, String> {
+    match read_config()?.accounts.into_iter().find(|a| a.username == username) {
+        Some(account) => credentials::get_password(&oauth_key(&account.id)),
+        None => Ok(None),
+    }
+}
+
+// Set (Some) or remove (None) the refresh token. Returns false if the user has no saved account.
+pub fn set_oauth_refresh_token(username: &str, refresh_token: Option<&str>) -> Result<bool, String> {
+    let account = match read_config()?.accounts.into_iter().find(|a| a.username == username) {
+        Some(account) => account,
+        None => return Ok(false),
+    };
+
+    match refresh_token {
+        Some(refresh_token) => credentials::set_password(&oauth_key(&account.id), refresh_token)?,
+        None => credentials::delete_password(&oauth_key(&account.id))?,
+    }
+    Ok(true)
+}
+
+pub fn get_saved_oauth_config(username: &str) -> Result<Option<OAuthConfig>, String> {
+    Ok(read_config()?
+        .accounts
+        .into_iter()
+        .find(|a| a.username == username)
+        .and_then(|a| a.oauth))
+}
+
+// Password for DAV/Sieve requests, looked up by the username of a saved account
+pub fn get_saved_password(username: &str) -> Result<Option<String>, String> {
+    if let Some(account) = read_config()?.accounts.into_iter().find(|a| a.username == username) {
+        let mut password = account.password;
+        fill_password(&account.id, &mut password);
+        return Ok(password);
+    }
+
+    if let Some(account) = read_jmap_config()?.accounts.into_iter().find(|a| a.username == username) {
+        let mut password = account.password;
+        fill_password(&account.id, &mut password);
+        return Ok(password);
+    }
+
+    Ok(None)
+}
+
+pub fn save_account(mut account: SavedAccount) -> Result<(), String> {
+    store_password(&account.id, &mut account.password)?;
+
     let config_path = get_config_path()?;
 
     let mut config = if config_path.exists() {
@@ -141,6 +309,18 @@ pub fn save_account(account: SavedAccount) -> Result<(), String> {
     // Update existing or add new
     let existing_idx = config.accounts.iter().position(|a| a.id == account.id);
     if let Some(idx) = existing_idx {
+        // Pins are recorded by the backend, don't lose them when the settings form is saved
+        if account.pinned_certificates.is_empty() {
+            account.pinned_certificates = config.accounts[idx].pinned_certificates.clone();
+        }
+        // Same for the folder roles, they are set from the folder list
+        if account.folder_roles.is_empty() {
+            account.folder_roles = config.accounts[idx].folder_roles.clone();
+        }
+        // And the tags, they have their own command
+        if account.tags.is_none() {
+            account.tags = config.accounts[idx].tags.clone();
+        }
         config.accounts[idx] = account;
     } else {
         config.accounts.push(account);
@@ -156,6 +336,12 @@ pub fn save_account(account: SavedAccount) -> Result<(), String> {
 }
 
 pub fn delete_account(account_id: &str) -> Result<(), String> {
+    for key in [account_id.to_string(), oauth_key(account_id)] {
+        if let Err(e) = credentials::delete_password(&key) {
+            println!("[Storage] Could not remove credentials of account {}: {}", account_id, e);
+        }
+    }
+
     let config_path = get_config_path()?;
 
     if !config_path.exists() {
@@ -193,23 +379,52 @@ fn get_jmap_config_path() -> Result<PathBuf, String> {
     Ok(config_dir.join("jmap_accounts.json"))
 }
 
-pub fn load_jmap_accounts() -> Result<Vec<SavedJmapAccount>, String> {
+fn read_jmap_config() -> Result<JmapAppConfig, String> {
     let config_path = get_jmap_config_path()?;
 
     if !config_path.exists() {
-        return Ok(vec![]);
+        return Ok(JmapAppConfig::default());
     }
 
     let content = fs::read_to_string(&config_path)
         .map_err(|e| format!("Failed to read JMAP config: {}", e))?;
 
-    let config: JmapAppConfig = serde_json::from_str(&content)
-        .map_err(|e| format!("Failed to parse JMAP config: {}", e))?;
+    serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse JMAP config: {}", e))
+}
+
+fn write_jmap_config(config: &JmapAppConfig) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(config)
+        .map_err(|e| format!("Failed to serialize JMAP config: {}", e))?;
+
+    fs::write(get_jmap_config_path()?, content)
+        .map_err(|e| format!("Failed to write JMAP config: {}", e))
+}
+
+pub fn load_jmap_accounts() -> Result<Vec<SavedJmapAccount>, String> {
+    let mut config = read_jmap_config()?;
+
+    // Move plaintext passwords of older versions into the credential store
+    let mut migrated = false;
+    for account in config.accounts.iter_mut() {
+        if move_password_to_store(&account.id, &mut account.password) {
+            migrated = true;
+        }
+    }
+    if migrated {
+        write_jmap_config(&config)?;
+    }
+
+    for account in config.accounts.iter_mut() {
+        fill_password(&account.id, &mut account.password);
+    }
 
     Ok(config.accounts)
 }
 
-pub fn save_jmap_account(account: SavedJmapAccount) -> Result<(), String> {
+pub fn save_jmap_account(mut account: SavedJmapAccount) -> Result<(), String> {
+    store_password(&account.id, &mut account.password)?;
+
     let config_path = get_jmap_config_path()?;
 
     let mut config = if config_path.exists() {
@@ -223,6 +438,13 @@ pub fn save_jmap_account(account: SavedJmapAccount) -> Result<(), String> {
     // Update existing or add new
     let existing_idx = config.accounts.iter().position(|a| a.id == account.id);
     if let Some(idx) = existing_idx {
+        // Pins are recorded by the backend, don't lose them when the settings form is saved
+        if account.pinned_certificates.is_empty() {
+            account.pinned_certificates = config.accounts[idx].pinned_certificates.clone();
+        }
+        if account.tags.is_none() {
+            account.tags = config.accounts[idx].tags.clone();
+        }
         config.accounts[idx] = account;
     } else {
         config.accounts.push(account);
@@ -238,6 +460,10 @@ pub fn save_jmap_account(account: SavedJmapAccount) -> Result<(), String> {
 }
 
 pub fn delete_jmap_account(account_id: &str) -> Result<(), String> {
+    if let Err(e) = credentials::delete_password(account_id) {
+        println!("[Storage] Could not remove password of account {}: {}", account_id, e);
+    }
+
     let config_path = get_jmap_config_path()?;
 
     if !config_path.exists() {
@@ -260,3 +486,125 @@ pub fn delete_jmap_account(account_id: &str) -> Result<(), String> {
 
     Ok(())
 }
+
+// Set (Some) or remove (None) the folder for a role. Returns false if the account is not saved.
+pub fn set_folder_role(account_id: &str, role: FolderRole, folder: Option<&str>) -> Result<bool, String> {
+    let mut config = read_config()?;
+    let Some(account) = config.accounts.iter_mut().find(|a| a.id == account_id) else {
+        return Ok(false);
+    };
+
+    match folder {
+        Some(folder) => {
+            account.folder_roles.insert(role, folder.to_string());
+        }
+        None => {
+            account.folder_roles.remove(&role);
+        }
+    }
+    write_config(&config)?;
+    Ok(true)
+}
+
+// Tags of an IMAP or JMAP account, the default labels if it has none or is not saved
+pub fn get_tags(account_id: &str) -> Result<Vec<Tag>, String> {
+    let saved = match read_config()?.accounts.into_iter().find(|a| a.id == account_id) {
+        Some(account) => account.tags,
+        None => read_jmap_config()?
+            .accounts
+            .into_iter()
+            .find(|a| a.id == account_id)
+            .and_then(|a| a.tags),
+    };
+    Ok(saved.unwrap_or_else(tags::default_tags))
+}
+
+// Returns false if the account is not saved
+pub fn set_tags(account_id: &str, tags: Vec<Tag>) -> Result<bool, String> {
+    let mut config = read_config()?;
+    if let Some(account) = config.accounts.iter_mut().find(|a| a.id == account_id) {
+        account.tags = Some(tags);
+        write_config(&config)?;
+        return Ok(true);
+    }
+
+    let mut config = read_jmap_config()?;
+    if let Some(account) = config.accounts.iter_mut().find(|a| a.id == account_id) {
+        account.tags = Some(tags);
+        write_jmap_config(&config)?;
+        return Ok(true);
+    }
+
+    Ok(false)
+}
+
+// Certificate pins are stored with the saved account of the user they were first seen for
+pub fn get_pinned_certificate(username: &str, host_key: &str) -> Result<Option<String>, String> {
+    let pinned = read_config()?
+        .accounts
+        .into_iter()
+        .find(|a| a.username == username)
+        .and_then(|a| a.pinned_certificates.get(host_key).cloned());
+
+    if pinned.is_some() {
+        return Ok(pinned);
+    }
+
+    Ok(read_jmap_config()?
+        .accounts
+        .into_iter()
+        .find(|a| a.username == username)
+        .and_then(|a| a.pinned_certificates.get(host_key).cloned()))
+}
+
+// Set (Some) or remove (None) a pin. Returns false if the user has no saved account.
+pub fn set_pinned_certificate(username: &str, host_key: &str, fingerprint: Option<&str>) -> Result<bool, String> {
+    let update = |pins: &mut HashMap<String, String>| match fingerprint {
+        Some(fingerprint) => {
+            pins.insert(host_key.to_string(), fingerprint.to_string());
+        }
+        None => {
+            pins.remove(host_key);
+        }
+    };
+
+    let config_path = get_config_path()?;
+    if config_path.exists() {
+        let content = fs::read_to_string(&config_path)
+            .map_err(|e| format!("Failed to read config: {}", e))?;
+        let mut config: AppConfig = serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse config: {}", e))?;
+
+        if let Some(account) = config.accounts.iter_mut().find(|a| a.username == username) {
+            update(&mut account.pinned_certificates);
+
+            let content = serde_json::to_string_pretty(&config)
+                .map_err(|e| format!("Failed to serialize config: {}", e))?;
+            fs::write(&config_path, content)
+                .map_err(|e| format!("Failed to write config: {}", e))?;
+
+            return Ok(true);
+        }
+    }
+
+    let config_path = get_jmap_config_path()?;
+    if config_path.exists() {
+        let content = fs::read_to_string(&config_path)
+            .map_err(|e| format!("Failed to read JMAP config: {}", e))?;
+        let mut config: JmapAppConfig = serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse JMAP config: {}", e))?;
+
+        if let Some(account) = config.accounts.iter_mut().find(|a| a.username == username) {
+            update(&mut account.pinned_certificates);
+
+            let content = serde_json::to_string_pretty(&config)
+                .map_err(|e| format!("Failed to serialize JMAP config: {}", e))?;
+            fs::write(&config_path, content)
+                .map_err(|e| format!("Failed to write JMAP config: {}", e))?;
+
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
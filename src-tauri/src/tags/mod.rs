@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+
+// A tag the user can put on messages. It is stored on the server as an IMAP keyword
+// (RFC 3501 2.3.2) or JMAP keyword (RFC 8621 4.1.1) under the same name, so other
+// clients see it too.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tag {
+    pub keyword: String,
+    pub label: String,
+    // CSS colour, e.g. "#ff0000"
+    pub color: String,
+}
+
+// Flags a folder keeps across sessions, from PERMANENTFLAGS on SELECT. Without
+// custom_allowed ("\*") only the listed keywords can be set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermanentFlags {
+    pub flags: Vec<String>,
+    pub custom_allowed: bool,
+}
+
+impl PermanentFlags {
+    pub fn from_flags(flags: Vec<String>) -> Self {
+        let custom_allowed = flags.iter().any(|flag| flag == "\\*");
+        Self {
+            flags: flags.into_iter().filter(|flag| flag != "\\*").collect(),
+            custom_allowed,
+        }
+    }
+
+    // For servers that say nothing about it
+    pub fn unrestricted() -> Self {
+        Self {
+            flags: ["\\Seen", "\\Answered", "\\Flagged", "\\Deleted", "\\Draft"]
+                .iter()
+                .map(|flag| flag.to_string())
+                .collect(),
+            custom_allowed: true,
+        }
+    }
+
+    pub fn allows(&self, flag: &str) -> bool {
+        let listed = self.flags.iter().any(|known| known.eq_ignore_ascii_case(flag));
+        listed || (self.custom_allowed && !flag.starts_with('\\'))
+    }
+}
+
+// Thunderbird's labels, used until the user edits the list
+pub fn default_tags() -> Vec<Tag> {
+    [
+        ("$label1", "Important", "#ff0000"),
+        ("$label2", "Work", "#ff9900"),
+        ("$label3", "Personal", "#009900"),
+        ("$label4", "To Do", "#3333ff"),
+        ("$label5", "Later", "#993399"),
+    ]
+    .into_iter()
+    .map(|(keyword, label, color)| Tag {
+        keyword: keyword.to_string(),
+        label: label.to_string(),
+        color: color.to_string(),
+    })
+    .collect()
+}
+
+// Keywords are atoms without the characters IMAP reserves. JMAP servers return them
+// lower-cased, so they are lower-cased here as well to compare equal on both protocols.
+pub fn normalize_keyword(keyword: &str) -> Result<String, String> {
+    let keyword = keyword.trim().to_lowercase();
+    let valid = !keyword.is_empty()
+        && !keyword.starts_with('\\')
+        && keyword
+            .chars()
+            .all(|c| c.is_ascii_graphic() && !"(){%*\"\\]".contains(c));
+    if valid {
+        Ok(keyword)
+    } else {
+        Err(format!("Invalid keyword: {}", keyword))
+    }
+}
+
+// A keyword for a new tag, derived from its label the way Thunderbird does
+pub fn keyword_for_label(label: &str) -> String {
+    let keyword: String = label
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_graphic() && !"(){%*\"\\]".contains(c) { c } else { '_' })
+        .collect();
+    if keyword.is_empty() {
+        "tag".to_string()
+    } else {
+        keyword
+    }
+}
+
+// Check and complete the user's list: keywords normalized, missing ones derived from the
+// label, duplicates dropped
+pub fn normalize_tags(tags: Vec<Tag>) -> Result<Vec<Tag>, String> {
+    let mut result: Vec<Tag> = Vec::new();
+    for mut tag in tags {
+        tag.keyword = if tag.keyword.trim().is_empty() {
+            keyword_for_label(&tag.label)
+        } else {
+            normalize_keyword(&tag.keyword)?
+        };
+        if !result.iter().any(|known| known.keyword == tag.keyword) {
+            result.push(tag);
+        }
+    }
+    Ok(result)
+}
+
+// System flags have a JMAP keyword of their own (RFC 8621 4.1.1)
+const SYSTEM_FLAGS: &[(&str, &str)] = &[
+    ("\\Seen", "$seen"),
+    ("\\Flagged", "$flagged"),
+    ("\\Answered", "$answered"),
+    ("\\Draft", "$draft"),
+];
+
+pub fn imap_to_jmap(flag: &str) -> String {
+    SYSTEM_FLAGS
+        .iter()
+        .find(|(imap, _)| imap.eq_ignore_ascii_case(flag))
+        .map(|(_, jmap)| jmap.to_string())
+        .unwrap_or_else(|| flag.to_lowercase())
+}
+
+pub fn jmap_to_imap(keyword: &str) -> String {
+    SYSTEM_FLAGS
+        .iter()
+        .find(|(_, jmap)| jmap.eq_ignore_ascii_case(keyword))
+        .map(|(imap, _)| imap.to_string())
+        .unwrap_or_else(|| keyword.to_string())
+}
@@ -0,0 +1,74 @@
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+
+// A message copied between accounts: the RFC 822 source with what APPEND and
+// Email/import can carry over
+#[derive(Debug, Clone)]
+pub struct RawMessage {
+    pub content: Vec<u8>,
+    // IMAP notation (\Seen, $label1), see tags::imap_to_jmap for JMAP
+    pub flags: Vec<String>,
+    pub internal_date: Option<DateTime<FixedOffset>>,
+}
+
+impl RawMessage {
+    // Flags to set on the copy. \Recent is the server's, \Deleted would make it vanish
+    // with the next expunge.
+    pub fn flags_to_copy(&self) -> Vec<&str> {
+        self.flags
+            .iter()
+            .map(|flag| flag.as_str())
+            .filter(|flag| !flag.eq_ignore_ascii_case("\\Recent") && !flag.eq_ignore_ascii_case("\\Deleted"))
+            .collect()
+    }
+}
+
+// A message in one of the connected accounts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "protocol")]
+pub enum MessageLocation {
+    #[serde(rename_all = "camelCase")]
+    Imap { account_id: String, folder: String, uid: u32 },
+    #[serde(rename_all = "camelCase")]
+    Jmap { account_id: String, email_id: String },
+}
+
+impl MessageLocation {
+    pub fn account_id(&self) -> &str {
+        match self {
+            MessageLocation::Imap { account_id, .. } | MessageLocation::Jmap { account_id, .. } => account_id,
+        }
+    }
+}
+
+// Folder or mailbox to copy into
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "protocol")]
+pub enum TransferTarget {
+    #[serde(rename_all = "camelCase")]
+    Imap { account_id: String, folder: String },
+    #[serde(rename_all = "camelCase")]
+    Jmap { account_id: String, mailbox_id: String },
+}
+
+impl TransferTarget {
+    pub fn account_id(&self) -> &str {
+        match self {
+            TransferTarget::Imap { account_id, .. } | TransferTarget::Jmap { account_id, .. } => account_id,
+        }
+    }
+}
+
+// Where a transferred message ended up. The UID is unknown on IMAP servers without UIDPLUS.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferResult {
+    pub source: MessageLocation,
+    pub uid: Option<u32>,
+    pub email_id: Option<String>,
+}
+
+// date-time of APPEND (RFC 3501 9), e.g. "17-Oct-2026 09:30:00 +0200"
+pub fn imap_internal_date(date: &DateTime<FixedOffset>) -> String {
+    date.format("%d-%b-%Y %H:%M:%S %z").to_string()
+}
@@ -0,0 +1,249 @@
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::{Mutex, OnceLock};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+use crate::storage;
+
+// Pins of accounts that are not saved only live as long as the app runs
+fn session_pins() -> &'static Mutex<HashMap<String, String>> {
+    static PINS: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+    PINS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// Hosts whose certificate passed normal verification during this run
+fn verified_hosts() -> &'static Mutex<HashSet<String>> {
+    static HOSTS: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    HOSTS.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+fn host_key(host: &str, port: u16) -> String {
+    format!("{}:{}", host.to_lowercase(), port)
+}
+
+fn session_key(username: &str, host: &str, port: u16) -> String {
+    format!("{}|{}", username, host_key(host, port))
+}
+
+// SHA-256 of the DER encoded certificate, as "AB:CD:..."
+pub fn fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der)
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+// Compare a certificate that failed normal verification against the pin for host:port.
+// Without a pin the certificate is trusted on first use and pinned.
+pub fn check_pin(username: &str, host: &str, port: u16, fingerprint: &str) -> Result<(), String> {
+    let key = host_key(host, port);
+
+    let pinned = match storage::get_pinned_certificate(username, &key)? {
+        Some(pinned) => Some(pinned),
+        None => session_pins()
+            .lock()
+            .unwrap()
+            .get(&session_key(username, host, port))
+            .cloned(),
+    };
+
+    match pinned {
+        Some(pinned) if pinned == fingerprint => Ok(()),
+        Some(pinned) => Err(format!(
+            "The certificate of {} changed (pinned {}, server sent {}). \
+             If the server certificate was renewed on purpose, remove the pinned certificate and reconnect.",
+            key, pinned, fingerprint
+        )),
+        None => {
+            println!("[TLS] Pinning certificate {} for {}", fingerprint, key);
+            if !storage::set_pinned_certificate(username, &key, Some(fingerprint))? {
+                session_pins()
+                    .lock()
+                    .unwrap()
+                    .insert(session_key(username, host, port), fingerprint.to_string());
+            }
+            Ok(())
+        }
+    }
+}
+
+pub fn forget_pin(username: &str, host: &str, port: u16) -> Result<(), String> {
+    session_pins()
+        .lock()
+        .unwrap()
+        .remove(&session_key(username, host, port));
+    storage::set_pinned_certificate(username, &host_key(host, port), None)?;
+    Ok(())
+}
+
+// TLS handshake for futures-io streams (IMAP). The certificate is verified normally first;
+// if that fails the connection is opened again and checked against the pinned fingerprint.
+pub async fn connect_tls<S, F, Fut>(
+    username: &str,
+    host: &str,
+    port: u16,
+    connect: F,
+) -> Result<async_native_tls::TlsStream<S>, String>
+where
+    S: futures::AsyncRead + futures::AsyncWrite + Unpin,
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<S, String>>,
+{
+    let stream = connect().await?;
+    match async_native_tls::TlsConnector::new().connect(host, stream).await {
+        Ok(tls_stream) => return Ok(tls_stream),
+        Err(e) => println!("[TLS] Certificate of {}:{} not verified ({}), checking pin", host, port, e),
+    }
+
+    let stream = connect().await?;
+    let tls_stream = async_native_tls::TlsConnector::new()
+        .danger_accept_invalid_certs(true)
+        .connect(host, stream)
+        .await
+        .map_err(|e| format!("TLS connection failed: {}", e))?;
+
+    let certificate = tls_stream
+        .peer_certificate()
+        .map_err(|e| format!("Failed to read server certificate: {}", e))?
+        .ok_or("Server did not send a certificate")?;
+    let der = certificate
+        .to_der()
+        .map_err(|e| format!("Failed to read server certificate: {}", e))?;
+
+    check_pin(username, host, port, &fingerprint(&der))?;
+    Ok(tls_stream)
+}
+
+// How to reach the TLS handshake when probing a server
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Handshake {
+    // TLS right after connecting (HTTPS, SMTPS)
+    Implicit,
+    // SMTP STARTTLS on a plain connection
+    SmtpStartTls,
+}
+
+// For clients whose TLS handshake we don't run ourselves (lettre, reqwest): find out whether
+// the server certificate verifies normally. Returns None if it does, otherwise the certificate
+// matching the pin. The client then gets that certificate as an additional root, so the
+// connection it opens is held to the pin as well and not just this probe.
+pub async fn probe(username: &str, host: &str, port: u16, handshake: Handshake) -> Result<Option<Vec<u8>>, String> {
+    let key = host_key(host, port);
+    if verified_hosts().lock().unwrap().contains(&key) {
+        return Ok(None);
+    }
+
+    let stream = open_probe_stream(host, port, handshake).await?;
+    let connector = native_tls::TlsConnector::new()
+        .map_err(|e| format!("Failed to create TLS connector: {}", e))?;
+    match tokio_native_tls::TlsConnector::from(connector).connect(host, stream).await {
+        Ok(_) => {
+            verified_hosts().lock().unwrap().insert(key);
+            return Ok(None);
+        }
+        Err(e) => println!("[TLS] Certificate of {} not verified ({}), checking pin", key, e),
+    }
+
+    let stream = open_probe_stream(host, port, handshake).await?;
+    let connector = native_tls::TlsConnector::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .map_err(|e| format!("Failed to create TLS connector: {}", e))?;
+    let tls_stream = tokio_native_tls::TlsConnector::from(connector)
+        .connect(host, stream)
+        .await
+        .map_err(|e| format!("TLS connection failed: {}", e))?;
+
+    let certificate = tls_stream
+        .get_ref()
+        .peer_certificate()
+        .map_err(|e| format!("Failed to read server certificate: {}", e))?
+        .ok_or("Server did not send a certificate")?;
+    let der = certificate
+        .to_der()
+        .map_err(|e| format!("Failed to read server certificate: {}", e))?;
+
+    check_pin(username, host, port, &fingerprint(&der))?;
+    Ok(Some(der))
+}
+
+// Probe the host of an http(s) URL, plain http needs no certificate
+pub async fn probe_url(username: &str, url: &str) -> Result<Option<Vec<u8>>, String> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("Invalid URL '{}': {}", url, e))?;
+    if parsed.scheme() != "https" {
+        return Ok(None);
+    }
+
+    let host = parsed.host_str().ok_or("URL has no host")?;
+    let port = parsed.port_or_known_default().unwrap_or(443);
+    probe(username, host, port, Handshake::Implicit).await
+}
+
+// HTTP client for a server of the account. A certificate that doesn't verify normally is
+// trusted only if it is the pinned one.
+pub async fn http_client(username: &str, url: &str) -> Result<reqwest::Client, String> {
+    let mut builder = reqwest::Client::builder();
+    if let Some(der) = probe_url(username, url).await? {
+        let certificate = reqwest::Certificate::from_der(&der)
+            .map_err(|e| format!("Failed to load pinned certificate: {}", e))?;
+        builder = builder.add_root_certificate(certificate);
+    }
+    builder
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+async fn open_probe_stream(host: &str, port: u16, handshake: Handshake) -> Result<TcpStream, String> {
+    let stream = TcpStream::connect(format!("{}:{}", host, port))
+        .await
+        .map_err(|e| format!("TCP connection failed: {}", e))?;
+
+    match handshake {
+        Handshake::Implicit => Ok(stream),
+        Handshake::SmtpStartTls => smtp_starttls(stream).await,
+    }
+}
+
+async fn smtp_starttls(stream: TcpStream) -> Result<TcpStream, String> {
+    let mut reader = BufReader::new(stream);
+
+    read_smtp_reply(&mut reader, "220").await?;
+    reader
+        .get_mut()
+        .write_all(b"EHLO localhost\r\n")
+        .await
+        .map_err(|e| format!("Failed to send EHLO: {}", e))?;
+    read_smtp_reply(&mut reader, "250").await?;
+    reader
+        .get_mut()
+        .write_all(b"STARTTLS\r\n")
+        .await
+        .map_err(|e| format!("Failed to send STARTTLS: {}", e))?;
+    read_smtp_reply(&mut reader, "220").await?;
+
+    Ok(reader.into_inner())
+}
+
+// Read a (possibly multi-line) SMTP reply and check its code
+async fn read_smtp_reply(reader: &mut BufReader<TcpStream>, code: &str) -> Result<(), String> {
+    loop {
+        let mut line = String::new();
+        let read = reader
+            .read_line(&mut line)
+            .await
+            .map_err(|e| format!("Failed to read SMTP reply: {}", e))?;
+        if read == 0 {
+            return Err("SMTP server closed the connection".to_string());
+        }
+        if !line.starts_with(code) {
+            return Err(format!("Unexpected SMTP reply: {}", line.trim()));
+        }
+        // "250-..." continues, "250 ..." ends the reply
+        if line.as_bytes().get(3) != Some(&b'-') {
+            return Ok(());
+        }
+    }
+}
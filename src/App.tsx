@@ -1,5 +1,6 @@
 import { useState, useEffect, useRef } from "react";
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 import { useTranslation } from "react-i18next";
 import ConnectionForm from "./components/ConnectionForm";
 import FolderList from "./components/FolderList";
@@ -16,7 +17,8 @@ import AccountSettings from "./components/AccountSettings";
 import MainNavigation from "./components/MainNavigation";
 import UpdateChecker from "./components/UpdateChecker";
 import ContextMenu, { ContextMenuItem } from "./components/ContextMenu";
-import { MailAccount, JmapAccount, Folder, EmailHeader, Email, OutgoingEmail, ConnectedAccount, SavedAccount, SieveRule, Attachment, JmapConnectedAccount } from "./types/mail";
+import CredentialUnlock from "./components/CredentialUnlock";
+import { MailAccount, JmapAccount, Folder, EmailHeader, Email, OutgoingEmail, ConnectedAccount, SavedAccount, SieveRule, Attachment, JmapConnectedAccount, SearchQuery, EmailReadEvent, FolderRole, Tag, TransferResult, CredentialStatus } from "./types/mail";
 import { playSentSound, playReceivedSound, playErrorSound } from "./utils/sounds";
 import { openComposerWindow } from "./utils/windows";
 
@@ -35,12 +37,17 @@ function App() {
   const [connectedAccounts, setConnectedAccounts] = useState<ConnectedAccount[]>([]);
   const [activeAccountId, setActiveAccountId] = useState<string | null>(null);
   const [initializing, setInitializing] = useState(true);
+  const [credentialStatus, setCredentialStatus] = useState<CredentialStatus | null>(null);
   const [activeAccountCredentials, setActiveAccountCredentials] = useState<MailAccount | null>(null);
   const [activeAccountSettings, setActiveAccountSettings] = useState<SavedAccount | null>(null);
 
   // Email view state
   const [emailSubView, setEmailSubView] = useState<EmailSubView>("inbox");
   const [folders, setFolders] = useState<Folder[]>([]);
+  // Show unsubscribed folders of an account that lists only subscribed ones
+  const [showAllFolders, setShowAllFolders] = useState(false);
+  // Tags of the active account, stored as keywords on the messages
+  const [tags, setTags] = useState<Tag[]>([]);
   const [selectedFolder, setSelectedFolder] = useState<string>("INBOX");
   const [emails, setEmails] = useState<EmailHeader[]>([]);
   const [selectedEmail, setSelectedEmail] = useState<Email | null>(null);
@@ -90,57 +97,77 @@ function App() {
     playErrorSound();
   };
 
-  // Auto-connect saved accounts on startup
-  useEffect(() => {
-    const autoConnect = async () => {
-      try {
-        const savedAccounts = await invoke<SavedAccount[]>("get_saved_accounts");
-        const accountsWithPassword = savedAccounts.filter((a) => a.password);
-
-        let firstAccount: MailAccount | null = null;
-        const newConnectedAccounts: ConnectedAccount[] = [];
-
-        for (const saved of accountsWithPassword) {
-          try {
-            const account: MailAccount = {
-              imapHost: saved.imap_host,
-              imapPort: saved.imap_port,
-              smtpHost: saved.smtp_host,
-              smtpPort: saved.smtp_port,
-              username: saved.username,
-              password: saved.password!,
-              displayName: saved.display_name,
-            };
-
-            const connectedAccount = await invoke<ConnectedAccount>("connect", { account });
-
-            if (!newConnectedAccounts.find(a => a.id === connectedAccount.id)) {
-              newConnectedAccounts.push(connectedAccount);
-            }
+  // Auto-connect saved accounts on startup, once their passwords can be read
+  const autoConnect = async () => {
+    try {
+      const savedAccounts = await invoke<SavedAccount[]>("get_saved_accounts");
+      const accountsWithPassword = savedAccounts.filter((a) => a.password || a.oauth);
 
-            if (!firstAccount) {
-              firstAccount = account;
-              setActiveAccountCredentials(account);
-              setActiveAccountSettings(saved);
-            }
-          } catch (e) {
-            console.error(`Failed to auto-connect ${saved.username}:`, e);
+      let firstAccount: MailAccount | null = null;
+      const newConnectedAccounts: ConnectedAccount[] = [];
+
+      for (const saved of accountsWithPassword) {
+        try {
+          const account: MailAccount = {
+            imapHost: saved.imap_host,
+            imapPort: saved.imap_port,
+            smtpHost: saved.smtp_host,
+            smtpPort: saved.smtp_port,
+            username: saved.username,
+            password: saved.password || "",
+            displayName: saved.display_name,
+            oauth: saved.oauth,
+            imapSecurity: saved.imap_security,
+            smtpSecurity: saved.smtp_security,
+            maxConnections: saved.max_connections,
+            readMarking: saved.read_marking,
+            folderRoles: saved.folder_roles,
+            subscribedOnly: saved.subscribed_only,
+            compress: saved.compress,
+          };
+
+          const connectedAccount = await invoke<ConnectedAccount>("connect", { account });
+
+          if (!newConnectedAccounts.find(a => a.id === connectedAccount.id)) {
+            newConnectedAccounts.push(connectedAccount);
           }
+
+          if (!firstAccount) {
+            firstAccount = account;
+            setActiveAccountCredentials(account);
+            setActiveAccountSettings(saved);
+          }
+        } catch (e) {
+          console.error(`Failed to auto-connect ${saved.username}:`, e);
         }
+      }
 
-        setConnectedAccounts(newConnectedAccounts);
+      setConnectedAccounts(newConnectedAccounts);
 
-        if (newConnectedAccounts.length > 0) {
-          setActiveAccountId(newConnectedAccounts[0].id);
-        }
-      } catch (e) {
-        console.error("Failed to load saved accounts:", e);
-      } finally {
-        setInitializing(false);
+      if (newConnectedAccounts.length > 0) {
+        setActiveAccountId(newConnectedAccounts[0].id);
       }
-    };
+    } catch (e) {
+      console.error("Failed to load saved accounts:", e);
+    } finally {
+      setInitializing(false);
+    }
+  };
 
-    autoConnect();
+  // The encrypted credentials file has to be unlocked before saved passwords can be used
+  useEffect(() => {
+    invoke<CredentialStatus>("get_credential_store_status")
+      .then((status) => {
+        if (status.locked) {
+          setCredentialStatus(status);
+        } else {
+          autoConnect();
+        }
+      })
+      .catch((e) => {
+        console.error("Failed to check credential store:", e);
+        autoConnect();
+      });
   }, []);
 
   // Load folders and emails when active account changes
@@ -152,6 +179,31 @@ function App() {
     }
   }, [activeAccountId, initializing]);
 
+  useEffect(() => {
+    if (!activeAccountId) {
+      setTags([]);
+      return;
+    }
+    invoke<Tag[]>("get_tags", { accountId: activeAccountId })
+      .then(setTags)
+      .catch(console.error);
+  }, [activeAccountId]);
+
+  // Opened mail marked read by the backend, see the account's read marking
+  useEffect(() => {
+    const unlisten = listen<EmailReadEvent>("email-read", (event) => {
+      const { accountId, folder, uid } = event.payload;
+      if (accountId !== activeAccountId || folder !== selectedFolder) return;
+      setEmails((prev) =>
+        prev.map((e) => (e.uid === uid ? { ...e, isRead: true } : e))
+      );
+    });
+
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [activeAccountId, selectedFolder]);
+
   // Polling for new emails
   useEffect(() => {
     if (!activeAccountId || initializing) return;
@@ -265,14 +317,19 @@ function App() {
       console.error("Failed to load account settings:", e);
     }
 
-    await loadFolders(accountId);
+    setShowAllFolders(false);
+    await loadFolders(accountId, false);
     await loadEmails(accountId, "INBOX");
     setSelectedFolder("INBOX");
   };
 
-  const loadFolders = async (accountId: string) => {
+  // Unsubscribed folders are left out if the account lists only subscribed ones, unless showAll
+  const loadFolders = async (accountId: string, showAll: boolean = showAllFolders) => {
     try {
-      const folderList = await invoke<Folder[]>("list_folders", { accountId });
+      const folderList = await invoke<Folder[]>("list_folders", {
+        accountId,
+        subscribedOnly: showAll ? false : null,
+      });
       setFolders(folderList);
     } catch (e) {
       setError(String(e));
@@ -397,17 +454,19 @@ function App() {
 
     if (!activeAccountId) return;
 
-    if (!activeAccountSettings?.cache_enabled) {
-      setError(t("search.cacheRequired", "Search requires cache to be enabled. Please enable in settings."));
-      return;
-    }
-
     setSearching(true);
     try {
-      const results = await invoke<EmailHeader[]>("search_cached_emails", {
-        accountId: activeAccountId,
-        query: query.trim(),
-      });
+      // Without a cache the server searches the current folder
+      const results = activeAccountSettings?.cache_enabled
+        ? await invoke<EmailHeader[]>("search_cached_emails", {
+            accountId: activeAccountId,
+            query: query.trim(),
+          })
+        : await invoke<EmailHeader[]>("search_emails", {
+            accountId: activeAccountId,
+            folder: selectedFolder,
+            query: { type: "text", value: query.trim() } as SearchQuery,
+          });
       setSearchResults(results);
     } catch (e) {
       console.error("Search failed:", e);
@@ -516,9 +575,6 @@ function App() {
         });
         if (cachedEmail && cachedEmail.bodyText) {
           setSelectedEmail(cachedEmail);
-          setEmails((prev) =>
-            prev.map((e) => (e.uid === uid ? { ...e, isRead: true } : e))
-          );
         }
       } catch (e) {
         console.log("No cached email found, fetching from server...");
@@ -527,30 +583,14 @@ function App() {
 
     setLoading(true);
     try {
-      const email = await invoke<Email>("fetch_email", {
+      // Marks the message read as configured for the account and reports it as email-read
+      const email = await invoke<Email>("open_email", {
         accountId: activeAccountId,
         folder: selectedFolder,
         uid,
       });
       setSelectedEmail(email);
 
-      const header = emails.find((e) => e.uid === uid);
-      if (header && !header.isRead) {
-        await invoke("mark_read", { accountId: activeAccountId, folder: selectedFolder, uid });
-        setEmails((prev) =>
-          prev.map((e) => (e.uid === uid ? { ...e, isRead: true } : e))
-        );
-
-        if (isCacheEnabled) {
-          await invoke("update_cache_read_status", {
-            accountId: activeAccountId,
-            folder: selectedFolder,
-            uid,
-            isRead: true,
-          }).catch(console.error);
-        }
-      }
-
       if (isCacheEnabled) {
         await invoke("cache_email", {
           accountId: activeAccountId,
@@ -567,8 +607,45 @@ function App() {
     }
   };
 
+  // Rights of the user in a folder (RFC 4314), actions the server would refuse are blocked up front
+  const canRemoveFrom = (folderName: string) => {
+    const allowed = folders.find((f) => f.name === folderName)?.allowed;
+    return !allowed || (allowed.deleteMessages && allowed.expunge);
+  };
+
+  const canInsertInto = (folderName: string) => {
+    const allowed = folders.find((f) => f.name === folderName)?.allowed;
+    return !allowed || allowed.insert;
+  };
+
+  // Deleting moves to Trash unless the message is in Trash already
+  const checkDeleteAllowed = () => {
+    if (!canRemoveFrom(selectedFolder)) {
+      setError(t("folders.removeNotAllowed"));
+      return false;
+    }
+    const trash = folders.find((f) => f.role === "trash");
+    if (trash && trash.name !== selectedFolder && !canInsertInto(trash.name)) {
+      setError(t("folders.insertNotAllowed", { folder: trash.name }));
+      return false;
+    }
+    return true;
+  };
+
+  const checkMoveAllowed = (targetFolder: string) => {
+    if (!canRemoveFrom(selectedFolder)) {
+      setError(t("folders.removeNotAllowed"));
+      return false;
+    }
+    if (!canInsertInto(targetFolder)) {
+      setError(t("folders.insertNotAllowed", { folder: targetFolder }));
+      return false;
+    }
+    return true;
+  };
+
   const handleDeleteEmail = async (uid: number) => {
-    if (!activeAccountId) return;
+    if (!activeAccountId || !checkDeleteAllowed()) return;
     try {
       await invoke("delete_email", { accountId: activeAccountId, folder: selectedFolder, uid });
       setEmails((prev) => prev.filter((e) => e.uid !== uid));
@@ -589,7 +666,7 @@ function App() {
   };
 
   const handleMoveEmail = async (uid: number, targetFolder: string) => {
-    if (!activeAccountId) return;
+    if (!activeAccountId || !checkMoveAllowed(targetFolder)) return;
     try {
       await invoke("move_email", {
         accountId: activeAccountId,
@@ -614,6 +691,39 @@ function App() {
     }
   };
 
+  // Copied with flags and date by the backend, removed here only once the copy exists
+  const handleMoveToAccount = async (uid: number, targetAccountId: string, targetFolder: string) => {
+    if (!activeAccountId) return;
+    if (!canRemoveFrom(selectedFolder)) {
+      setError(t("folders.removeNotAllowed"));
+      return;
+    }
+    try {
+      await invoke<TransferResult[]>("transfer_messages", {
+        sources: [{ protocol: "imap", accountId: activeAccountId, folder: selectedFolder, uid }],
+        target: { protocol: "imap", accountId: targetAccountId, folder: targetFolder },
+        removeSource: true,
+      });
+      setEmails((prev) => prev.filter((e) => e.uid !== uid));
+      if (selectedEmail?.uid === uid) {
+        setSelectedEmail(null);
+      }
+
+      if (activeAccountSettings?.cache_enabled) {
+        await invoke("delete_cached_email", {
+          accountId: activeAccountId,
+          folder: selectedFolder,
+          uid,
+        }).catch(console.error);
+      }
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  const loadAccountFolders = (accountId: string) =>
+    invoke<Folder[]>("list_folders", { accountId, subscribedOnly: null });
+
   // Flag operations
   const handleToggleFlag = async (uid: number, currentlyFlagged: boolean) => {
     if (!activeAccountId) return;
@@ -650,6 +760,33 @@ function App() {
     }
   };
 
+  // Keywords compare case-insensitively, tags are stored lower-cased
+  const handleToggleTag = async (email: EmailHeader, keyword: string) => {
+    if (!activeAccountId) return;
+    const hasTag = email.flags.some((f) => f.toLowerCase() === keyword);
+    try {
+      await invoke(hasTag ? "remove_flags" : "add_flags", {
+        accountId: activeAccountId,
+        folder: selectedFolder,
+        uid: email.uid,
+        flags: [keyword],
+      });
+      const flags = hasTag ? email.flags.filter((f) => f.toLowerCase() !== keyword) : [...email.flags, keyword];
+      setEmails((prev) => prev.map((e) => (e.uid === email.uid ? { ...e, flags } : e)));
+
+      if (activeAccountSettings?.cache_enabled) {
+        await invoke("update_cached_flags", {
+          accountId: activeAccountId,
+          folder: selectedFolder,
+          uid: email.uid,
+          flags,
+        }).catch(console.error);
+      }
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
   // Bulk operations
   const handleBulkMarkRead = async () => {
     if (!activeAccountId || selectedUids.size === 0) return;
@@ -694,7 +831,7 @@ function App() {
   };
 
   const handleBulkDelete = async () => {
-    if (!activeAccountId || selectedUids.size === 0) return;
+    if (!activeAccountId || selectedUids.size === 0 || !checkDeleteAllowed()) return;
     try {
       const uids = Array.from(selectedUids);
       await invoke("bulk_delete", { accountId: activeAccountId, folder: selectedFolder, uids });
@@ -709,7 +846,7 @@ function App() {
   };
 
   const handleBulkMove = async (targetFolder: string) => {
-    if (!activeAccountId || selectedUids.size === 0) return;
+    if (!activeAccountId || selectedUids.size === 0 || !checkMoveAllowed(targetFolder)) return;
     try {
       const uids = Array.from(selectedUids);
       await invoke("bulk_move", { accountId: activeAccountId, folder: selectedFolder, uids, targetFolder });
@@ -756,6 +893,36 @@ function App() {
     }
   };
 
+  const handleSetFolderRole = async (role: FolderRole, folder: string | null) => {
+    if (!activeAccountId) return;
+    try {
+      await invoke("set_folder_role", { accountId: activeAccountId, role, folder });
+      await loadFolders(activeAccountId);
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  const handleSetFolderSubscribed = async (folderName: string, subscribed: boolean) => {
+    if (!activeAccountId) return;
+    try {
+      await invoke(subscribed ? "subscribe_folder" : "unsubscribe_folder", {
+        accountId: activeAccountId,
+        folderName,
+      });
+      await loadFolders(activeAccountId);
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  const handleToggleAllFolders = async () => {
+    if (!activeAccountId) return;
+    const showAll = !showAllFolders;
+    setShowAllFolders(showAll);
+    await loadFolders(activeAccountId, showAll);
+  };
+
   const handleDeleteFolder = async (folderName: string) => {
     if (!activeAccountId) return;
     try {
@@ -891,6 +1058,12 @@ function App() {
         icon: email.isRead ? "●" : "○",
         onClick: () => email.isRead ? handleMarkUnread(email.uid) : handleSelectEmail(email.uid),
       },
+      ...(tags.length > 0 ? [{ label: "", onClick: () => {}, separator: true }] : []),
+      ...tags.map((tag) => ({
+        label: tag.label,
+        icon: email.flags.some((f) => f.toLowerCase() === tag.keyword) ? "✓" : "🏷",
+        onClick: () => handleToggleTag(email, tag.keyword),
+      })),
       { label: "", onClick: () => {}, separator: true },
       {
         label: t("email.move"),
@@ -946,6 +1119,20 @@ function App() {
     setShowSettings(true);
   };
 
+  if (credentialStatus) {
+    return (
+      <div className="h-full flex items-center justify-center bg-gray-100">
+        <CredentialUnlock
+          status={credentialStatus}
+          onUnlocked={() => {
+            setCredentialStatus(null);
+            autoConnect();
+          }}
+        />
+      </div>
+    );
+  }
+
   // Show loading while initializing
   if (initializing) {
     return (
@@ -1167,6 +1354,12 @@ function App() {
                       onCreateFolder={handleCreateFolder}
                       onRenameFolder={handleRenameFolder}
                       onDeleteFolder={handleDeleteFolder}
+                      onSetFolderRole={handleSetFolderRole}
+                      onSetFolderSubscribed={handleSetFolderSubscribed}
+                      showAllFolders={showAllFolders}
+                      onToggleAllFolders={
+                        activeAccountSettings?.subscribed_only ? handleToggleAllFolders : undefined
+                      }
                     />
                   </div>
 
@@ -1222,7 +1415,7 @@ function App() {
                               {t("bulk.move")} ▾
                             </button>
                             <div className="absolute left-0 top-full mt-1 bg-white border border-gray-300 rounded shadow-lg z-10 hidden group-hover:block min-w-32">
-                              {folders.filter(f => f.name !== selectedFolder).map((folder) => (
+                              {folders.filter(f => f.name !== selectedFolder && f.allowed.insert).map((folder) => (
                                 <button
                                   key={folder.name}
                                   onClick={() => handleBulkMove(folder.name)}
@@ -1262,6 +1455,7 @@ function App() {
                         onSelectEmail={handleSelectEmail}
                         onContextMenu={(email, x, y) => setContextMenu({ email, x, y })}
                         onToggleFlag={handleToggleFlag}
+                        tags={tags}
                         loading={loading || searching}
                         selectedUids={selectedUids}
                         onSelectionChange={setSelectedUids}
@@ -1279,6 +1473,13 @@ function App() {
                         onReply={handleReply}
                         onDelete={() => handleDeleteEmail(selectedEmail.uid)}
                         onMove={(folder) => handleMoveEmail(selectedEmail.uid, folder)}
+                        otherAccounts={connectedAccounts
+                          .filter((a) => a.id !== activeAccountId)
+                          .map((a) => ({ id: a.id, label: a.displayName || a.email }))}
+                        onLoadAccountFolders={loadAccountFolders}
+                        onMoveToAccount={(accountId, folder) =>
+                          handleMoveToAccount(selectedEmail.uid, accountId, folder)
+                        }
                         onDownloadAttachment={handleDownloadAttachment}
                       />
                     ) : (
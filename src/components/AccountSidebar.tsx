@@ -1,5 +1,5 @@
 import { useTranslation } from "react-i18next";
-import { ConnectedAccount } from "../types/mail";
+import { ConnectedAccount, QuotaUsage } from "../types/mail";
 
 interface Props {
   accounts: ConnectedAccount[];
@@ -30,6 +30,25 @@ function AccountSidebar({
     return account.email[0].toUpperCase();
   };
 
+  const formatBytes = (bytes: number): string => {
+    if (bytes === 0) return "0 B";
+    const k = 1024;
+    const sizes = ["B", "KB", "MB", "GB", "TB"];
+    const i = Math.min(Math.floor(Math.log(bytes) / Math.log(k)), sizes.length - 1);
+    return parseFloat((bytes / Math.pow(k, i)).toFixed(1)) + " " + sizes[i];
+  };
+
+  const percent = (usage: QuotaUsage) =>
+    usage.limit > 0 ? Math.round((usage.used / usage.limit) * 100) : 0;
+
+  // The fuller of storage and message count
+  const quotaPercent = (account: ConnectedAccount) => {
+    const usages = [account.quota?.storage, account.quota?.messages].filter(
+      (usage): usage is QuotaUsage => !!usage
+    );
+    return usages.length > 0 ? Math.max(...usages.map(percent)) : null;
+  };
+
   // Generate a color based on the account ID
   const getColor = (id: string) => {
     const colors = [
@@ -52,40 +71,77 @@ function AccountSidebar({
   return (
     <div className="w-16 bg-gray-800 flex flex-col items-center py-4 gap-2">
       {/* Account avatars */}
-      {accounts.map((account) => (
-        <div key={account.id} className="relative group">
-          <button
-            onClick={() => onSelectAccount(account.id)}
-            className={`w-10 h-10 rounded-full ${getColor(account.id)} text-white font-semibold text-sm flex items-center justify-center transition-all ${
-              activeAccountId === account.id
-                ? "ring-2 ring-white ring-offset-2 ring-offset-gray-800"
-                : "hover:opacity-80"
-            }`}
-            title={`${account.displayName} (${account.email})`}
-          >
-            {getInitials(account)}
-          </button>
+      {accounts.map((account) => {
+        const usedPercent = quotaPercent(account);
+        return (
+          <div key={account.id} className="relative group">
+            <button
+              onClick={() => onSelectAccount(account.id)}
+              className={`w-10 h-10 rounded-full ${getColor(account.id)} text-white font-semibold text-sm flex items-center justify-center transition-all ${
+                activeAccountId === account.id
+                  ? "ring-2 ring-white ring-offset-2 ring-offset-gray-800"
+                  : "hover:opacity-80"
+              }`}
+              title={`${account.displayName} (${account.email})`}
+            >
+              {getInitials(account)}
+            </button>
+
+            {/* Quota bar, turns red when the mailbox is almost full */}
+            {usedPercent !== null && (
+              <div className="w-10 h-1 mt-1 bg-gray-600 rounded-full overflow-hidden">
+                <div
+                  className={`h-full ${usedPercent >= 90 ? "bg-red-500" : usedPercent >= 75 ? "bg-yellow-500" : "bg-green-500"}`}
+                  style={{ width: `${Math.min(usedPercent, 100)}%` }}
+                />
+              </div>
+            )}
 
-          {/* Remove button on hover */}
-          <button
-            onClick={(e) => {
-              e.stopPropagation();
-              onRemoveAccount(account.id);
-            }}
-            className="absolute -top-1 -right-1 w-4 h-4 bg-red-500 text-white rounded-full text-xs hidden group-hover:flex items-center justify-center hover:bg-red-600"
-            title={t("accounts.remove")}
-          >
-            &times;
-          </button>
+            {/* Remove button on hover */}
+            <button
+              onClick={(e) => {
+                e.stopPropagation();
+                onRemoveAccount(account.id);
+              }}
+              className="absolute -top-1 -right-1 w-4 h-4 bg-red-500 text-white rounded-full text-xs hidden group-hover:flex items-center justify-center hover:bg-red-600"
+              title={t("accounts.remove")}
+            >
+              &times;
+            </button>
 
-          {/* Tooltip */}
-          <div className="absolute left-14 top-1/2 -translate-y-1/2 bg-gray-900 text-white text-xs px-2 py-1 rounded whitespace-nowrap opacity-0 group-hover:opacity-100 transition-opacity pointer-events-none z-10">
-            {account.displayName}
-            <br />
-            <span className="text-gray-400">{account.email}</span>
+            {/* Tooltip */}
+            <div className="absolute left-14 top-1/2 -translate-y-1/2 bg-gray-900 text-white text-xs px-2 py-1 rounded whitespace-nowrap opacity-0 group-hover:opacity-100 transition-opacity pointer-events-none z-10">
+              {account.displayName}
+              <br />
+              <span className="text-gray-400">{account.email}</span>
+              {account.quota?.storage && (
+                <>
+                  <br />
+                  <span className="text-gray-400">
+                    {t("accounts.quotaStorage", {
+                      used: formatBytes(account.quota.storage.used),
+                      limit: formatBytes(account.quota.storage.limit),
+                      percent: percent(account.quota.storage),
+                    })}
+                  </span>
+                </>
+              )}
+              {account.quota?.messages && (
+                <>
+                  <br />
+                  <span className="text-gray-400">
+                    {t("accounts.quotaMessages", {
+                      used: account.quota.messages.used,
+                      limit: account.quota.messages.limit,
+                      percent: percent(account.quota.messages),
+                    })}
+                  </span>
+                </>
+              )}
+            </div>
           </div>
-        </div>
-      ))}
+        );
+      })}
 
       {/* Add account button */}
       <button
@@ -1,11 +1,25 @@
 import { useState, useEffect } from "react";
 import { useTranslation } from "react-i18next";
 import { invoke } from "@tauri-apps/api/core";
-import { MailAccount, JmapAccount, AutoConfigResult, SavedAccount, SavedJmapAccount } from "../types/mail";
+import { MailAccount, JmapAccount, AutoConfigResult, SavedAccount, SavedJmapAccount, SecurityMode, ReadMarking } from "../types/mail";
 
 type Protocol = "imap" | "jmap";
 type AnyAccount = MailAccount | JmapAccount;
 
+// Map the socketType of an autoconfig file ("SSL", "STARTTLS", "plain")
+function socketTypeToSecurity(socketType?: string): SecurityMode | undefined {
+  switch (socketType?.toUpperCase()) {
+    case "SSL":
+      return "tls";
+    case "STARTTLS":
+      return "starttls";
+    case "PLAIN":
+      return "none";
+    default:
+      return undefined;
+  }
+}
+
 interface Props {
   onConnect: (account: AnyAccount, protocol: Protocol) => Promise<void>;
   loading: boolean;
@@ -32,6 +46,8 @@ function ConnectionForm({ onConnect, loading, error }: Props) {
     username: "",
     password: "",
     displayName: "",
+    imapSecurity: "tls",
+    smtpSecurity: "starttls",
   });
 
   // JMAP form data
@@ -81,6 +97,12 @@ function ConnectionForm({ onConnect, loading, error }: Props) {
             imap_port: imapFormData.imapPort,
             smtp_host: imapFormData.smtpHost,
             smtp_port: imapFormData.smtpPort,
+            imap_security: imapFormData.imapSecurity,
+            smtp_security: imapFormData.smtpSecurity,
+            max_connections: imapFormData.maxConnections,
+            read_marking: imapFormData.readMarking,
+            subscribed_only: imapFormData.subscribedOnly,
+            compress: imapFormData.compress,
             password: savePassword ? imapFormData.password : undefined,
           };
           await invoke("save_account", { account: savedAccount });
@@ -117,6 +139,11 @@ function ConnectionForm({ onConnect, loading, error }: Props) {
     setImapFormData((prev) => ({ ...prev, [field]: value }));
   };
 
+  const handleReadMarkingChange = (mode: ReadMarking["mode"]) => {
+    const readMarking: ReadMarking = mode === "delayed" ? { mode, seconds: 5 } : { mode };
+    setImapFormData((prev) => ({ ...prev, readMarking }));
+  };
+
   const handleJmapChange = (field: keyof JmapAccount, value: string) => {
     setJmapFormData((prev) => ({ ...prev, [field]: value }));
   };
@@ -141,6 +168,8 @@ function ConnectionForm({ onConnect, loading, error }: Props) {
               imapPort: config.imap_port || prev.imapPort,
               smtpHost: config.smtp_host || prev.smtpHost,
               smtpPort: config.smtp_port || prev.smtpPort,
+              imapSecurity: socketTypeToSecurity(config.imap_socket_type) || prev.imapSecurity,
+              smtpSecurity: socketTypeToSecurity(config.smtp_socket_type) || prev.smtpSecurity,
               displayName: config.display_name || prev.displayName,
             }));
             setAutoConfigStatus(t("accounts.settingsFound"));
@@ -180,6 +209,8 @@ function ConnectionForm({ onConnect, loading, error }: Props) {
         username: "",
         password: "",
         displayName: "",
+        imapSecurity: "tls",
+        smtpSecurity: "starttls",
       });
       setJmapFormData({
         jmapUrl: "",
@@ -210,6 +241,12 @@ function ConnectionForm({ onConnect, loading, error }: Props) {
           username: account.username,
           password: account.password || "",
           displayName: account.display_name,
+          imapSecurity: account.imap_security,
+          smtpSecurity: account.smtp_security,
+          maxConnections: account.max_connections,
+          readMarking: account.read_marking,
+          subscribedOnly: account.subscribed_only,
+          compress: account.compress,
         });
       }
     }
@@ -234,6 +271,8 @@ function ConnectionForm({ onConnect, loading, error }: Props) {
           username: "",
           password: "",
           displayName: "",
+          imapSecurity: "tls",
+          smtpSecurity: "starttls",
         });
         setJmapFormData({
           jmapUrl: "",
@@ -429,6 +468,19 @@ function ConnectionForm({ onConnect, loading, error }: Props) {
                 </div>
               </div>
 
+              <div className="mb-3">
+                <label className="block text-xs text-gray-500 mb-1">{t("accounts.security")}</label>
+                <select
+                  value={imapFormData.imapSecurity || "tls"}
+                  onChange={(e) => handleImapChange("imapSecurity", e.target.value)}
+                  className="w-full px-3 py-2 border border-gray-300 rounded focus:outline-none focus:ring-2 focus:ring-blue-500 text-sm"
+                >
+                  <option value="tls">{t("accounts.ssl")}</option>
+                  <option value="starttls">{t("accounts.starttls")}</option>
+                  <option value="none">{t("accounts.none")}</option>
+                </select>
+              </div>
+
               <div className="grid grid-cols-3 gap-2">
                 <div className="col-span-2">
                   <label className="block text-xs text-gray-500 mb-1">{t("accounts.smtpServer")}</label>
@@ -452,6 +504,87 @@ function ConnectionForm({ onConnect, loading, error }: Props) {
                   />
                 </div>
               </div>
+
+              <div className="mt-3">
+                <label className="block text-xs text-gray-500 mb-1">{t("accounts.security")}</label>
+                <select
+                  value={imapFormData.smtpSecurity || (imapFormData.smtpPort === 465 ? "tls" : "starttls")}
+                  onChange={(e) => handleImapChange("smtpSecurity", e.target.value)}
+                  className="w-full px-3 py-2 border border-gray-300 rounded focus:outline-none focus:ring-2 focus:ring-blue-500 text-sm"
+                >
+                  <option value="tls">{t("accounts.ssl")}</option>
+                  <option value="starttls">{t("accounts.starttls")}</option>
+                  <option value="none">{t("accounts.none")}</option>
+                </select>
+              </div>
+
+              <div className="mt-3">
+                <label className="block text-xs text-gray-500 mb-1">{t("accounts.maxConnections")}</label>
+                <input
+                  type="number"
+                  min={1}
+                  max={10}
+                  value={imapFormData.maxConnections ?? 3}
+                  onChange={(e) => handleImapChange("maxConnections", parseInt(e.target.value))}
+                  className="w-full px-3 py-2 border border-gray-300 rounded focus:outline-none focus:ring-2 focus:ring-blue-500 text-sm"
+                />
+              </div>
+
+              <div className="mt-3">
+                <label className="block text-xs text-gray-500 mb-1">{t("accounts.readMarking")}</label>
+                <div className="flex gap-2">
+                  <select
+                    value={imapFormData.readMarking?.mode ?? "immediately"}
+                    onChange={(e) => handleReadMarkingChange(e.target.value as ReadMarking["mode"])}
+                    className="flex-1 px-3 py-2 border border-gray-300 rounded focus:outline-none focus:ring-2 focus:ring-blue-500 text-sm"
+                  >
+                    <option value="immediately">{t("accounts.readImmediately")}</option>
+                    <option value="delayed">{t("accounts.readDelayed")}</option>
+                    <option value="manual">{t("accounts.readManually")}</option>
+                  </select>
+                  {imapFormData.readMarking?.mode === "delayed" && (
+                    <input
+                      type="number"
+                      min={1}
+                      value={imapFormData.readMarking.seconds}
+                      onChange={(e) =>
+                        setImapFormData((prev) => ({
+                          ...prev,
+                          readMarking: { mode: "delayed", seconds: parseInt(e.target.value) || 1 },
+                        }))
+                      }
+                      title={t("accounts.readDelaySeconds")}
+                      className="w-20 px-3 py-2 border border-gray-300 rounded focus:outline-none focus:ring-2 focus:ring-blue-500 text-sm"
+                    />
+                  )}
+                </div>
+              </div>
+
+              <div className="mt-3 flex items-center">
+                <input
+                  type="checkbox"
+                  id="subscribedOnly"
+                  checked={imapFormData.subscribedOnly ?? false}
+                  onChange={(e) => setImapFormData((prev) => ({ ...prev, subscribedOnly: e.target.checked }))}
+                  className="h-4 w-4 text-blue-600 focus:ring-blue-500 border-gray-300 rounded"
+                />
+                <label htmlFor="subscribedOnly" className="ml-2 block text-sm text-gray-700">
+                  {t("accounts.subscribedOnly")}
+                </label>
+              </div>
+
+              <div className="mt-3 flex items-center">
+                <input
+                  type="checkbox"
+                  id="compress"
+                  checked={imapFormData.compress ?? false}
+                  onChange={(e) => setImapFormData((prev) => ({ ...prev, compress: e.target.checked }))}
+                  className="h-4 w-4 text-blue-600 focus:ring-blue-500 border-gray-300 rounded"
+                />
+                <label htmlFor="compress" className="ml-2 block text-sm text-gray-700">
+                  {t("accounts.compress")}
+                </label>
+              </div>
             </>
           ) : (
             <div>
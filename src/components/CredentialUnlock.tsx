@@ -0,0 +1,76 @@
+import { useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { useTranslation } from "react-i18next";
+import { CredentialStatus } from "../types/mail";
+
+interface Props {
+  status: CredentialStatus;
+  onUnlocked: () => void;
+}
+
+// Asks for the master passphrase of the encrypted credentials file, used where no OS keyring
+// is available. The first time it sets the passphrase.
+function CredentialUnlock({ status, onUnlocked }: Props) {
+  const { t } = useTranslation();
+  const [passphrase, setPassphrase] = useState("");
+  const [confirmation, setConfirmation] = useState("");
+  const [unlocking, setUnlocking] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+
+  const handleSubmit = async (e: React.FormEvent) => {
+    e.preventDefault();
+    if (!status.initialized && passphrase !== confirmation) {
+      setError(t("credentials.mismatch"));
+      return;
+    }
+
+    setUnlocking(true);
+    setError(null);
+    try {
+      await invoke("unlock_credential_store", { passphrase });
+      onUnlocked();
+    } catch (e) {
+      setError(String(e));
+    } finally {
+      setUnlocking(false);
+    }
+  };
+
+  return (
+    <form onSubmit={handleSubmit} className="bg-white rounded-lg shadow-lg p-6 w-96 space-y-4">
+      <h2 className="text-lg font-semibold text-gray-800">
+        {status.initialized ? t("credentials.unlockTitle") : t("credentials.setupTitle")}
+      </h2>
+      <p className="text-sm text-gray-600">
+        {status.initialized ? t("credentials.unlockHint") : t("credentials.setupHint")}
+      </p>
+      <input
+        type="password"
+        value={passphrase}
+        onChange={(e) => setPassphrase(e.target.value)}
+        placeholder={t("credentials.passphrase")}
+        className="w-full px-3 py-2 border rounded focus:outline-none focus:ring-2 focus:ring-blue-500"
+        autoFocus
+      />
+      {!status.initialized && (
+        <input
+          type="password"
+          value={confirmation}
+          onChange={(e) => setConfirmation(e.target.value)}
+          placeholder={t("credentials.confirmPassphrase")}
+          className="w-full px-3 py-2 border rounded focus:outline-none focus:ring-2 focus:ring-blue-500"
+        />
+      )}
+      {error && <p className="text-sm text-red-600">{error}</p>}
+      <button
+        type="submit"
+        disabled={unlocking || !passphrase}
+        className="w-full px-4 py-2 bg-blue-600 text-white rounded hover:bg-blue-700 disabled:opacity-50"
+      >
+        {status.initialized ? t("credentials.unlock") : t("credentials.setup")}
+      </button>
+    </form>
+  );
+}
+
+export default CredentialUnlock;
@@ -1,5 +1,5 @@
 import { useTranslation } from "react-i18next";
-import { EmailHeader } from "../types/mail";
+import { EmailHeader, Tag } from "../types/mail";
 
 interface Props {
   emails: EmailHeader[];
@@ -7,6 +7,8 @@ interface Props {
   onSelectEmail: (uid: number) => void;
   onContextMenu?: (email: EmailHeader, x: number, y: number) => void;
   onToggleFlag?: (uid: number, currentlyFlagged: boolean) => void;
+  // Known tags, shown as coloured dots on the messages carrying them
+  tags?: Tag[];
   loading: boolean;
   // Multi-select props
   selectedUids?: Set<number>;
@@ -69,6 +71,7 @@ function EmailList({
   onSelectEmail,
   onContextMenu,
   onToggleFlag,
+  tags,
   loading,
   selectedUids,
   onSelectionChange,
@@ -209,8 +212,23 @@ function EmailList({
             <span className="text-sm text-gray-700 truncate">
               {email.subject || "(Kein Betreff)"}
             </span>
+            {tags
+              ?.filter((tag) => email.flags.some((f) => f.toLowerCase() === tag.keyword))
+              .map((tag) => (
+                <span
+                  key={tag.keyword}
+                  className="w-2 h-2 rounded-full flex-shrink-0"
+                  style={{ backgroundColor: tag.color }}
+                  title={tag.label}
+                />
+              ))}
             {email.hasAttachments && (
-              <span className="text-gray-400 flex-shrink-0">📎</span>
+              <span
+                className="text-gray-400 flex-shrink-0"
+                title={email.attachments?.map((a) => `${a.filename} (${a.mimeType})`).join("\n")}
+              >
+                📎
+              </span>
             )}
           </div>
         </div>
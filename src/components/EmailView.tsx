@@ -8,12 +8,27 @@ interface Props {
   onReply: (email: Email) => void;
   onDelete: () => void;
   onMove: (folder: string) => void;
+  otherAccounts?: { id: string; label: string }[];
+  onLoadAccountFolders?: (accountId: string) => Promise<Folder[]>;
+  onMoveToAccount?: (accountId: string, folder: string) => void;
   onDownloadAttachment?: (attachment: Attachment) => Promise<void>;
 }
 
-function EmailView({ email, folders, onReply, onDelete, onMove, onDownloadAttachment }: Props) {
+function EmailView({
+  email,
+  folders,
+  onReply,
+  onDelete,
+  onMove,
+  otherAccounts = [],
+  onLoadAccountFolders,
+  onMoveToAccount,
+  onDownloadAttachment,
+}: Props) {
   const { t, i18n } = useTranslation();
   const [showMoveMenu, setShowMoveMenu] = useState(false);
+  const [expandedAccount, setExpandedAccount] = useState<string | null>(null);
+  const [accountFolders, setAccountFolders] = useState<Record<string, Folder[]>>({});
   const [downloadingAttachment, setDownloadingAttachment] = useState<string | null>(null);
 
   const formatDate = (dateStr: string): string => {
@@ -32,6 +47,23 @@ function EmailView({ email, folders, onReply, onDelete, onMove, onDownloadAttach
     }
   };
 
+  // Folders of the other accounts are only listed once the user opens one
+  const toggleAccount = async (accountId: string) => {
+    if (expandedAccount === accountId) {
+      setExpandedAccount(null);
+      return;
+    }
+    setExpandedAccount(accountId);
+    if (!accountFolders[accountId] && onLoadAccountFolders) {
+      try {
+        const list = await onLoadAccountFolders(accountId);
+        setAccountFolders((prev) => ({ ...prev, [accountId]: list }));
+      } catch (e) {
+        console.error(e);
+      }
+    }
+  };
+
   const handleDownload = async (attachment: Attachment) => {
     if (!onDownloadAttachment) return;
     setDownloadingAttachment(attachment.partId);
@@ -66,7 +98,7 @@ function EmailView({ email, folders, onReply, onDelete, onMove, onDownloadAttach
               </button>
               {showMoveMenu && (
                 <div className="absolute right-0 mt-1 w-48 bg-white border rounded-lg shadow-lg z-10">
-                  {folders.map((folder) => (
+                  {folders.filter((folder) => folder.allowed.insert).map((folder) => (
                     <button
                       key={folder.name}
                       onClick={() => {
@@ -78,6 +110,38 @@ function EmailView({ email, folders, onReply, onDelete, onMove, onDownloadAttach
                       {folder.name}
                     </button>
                   ))}
+                  {onMoveToAccount && otherAccounts.length > 0 && (
+                    <>
+                      <div className="px-4 py-1 text-xs text-gray-500 border-t">
+                        {t("email.moveToAccount")}
+                      </div>
+                      {otherAccounts.map((account) => (
+                        <div key={account.id}>
+                          <button
+                            onClick={() => toggleAccount(account.id)}
+                            className="w-full px-4 py-2 text-left text-sm hover:bg-gray-100 truncate"
+                          >
+                            {expandedAccount === account.id ? "▾" : "▸"} {account.label}
+                          </button>
+                          {expandedAccount === account.id &&
+                            (accountFolders[account.id] ?? [])
+                              .filter((folder) => folder.allowed.insert)
+                              .map((folder) => (
+                                <button
+                                  key={folder.name}
+                                  onClick={() => {
+                                    onMoveToAccount(account.id, folder.name);
+                                    setShowMoveMenu(false);
+                                  }}
+                                  className="w-full pl-8 pr-4 py-2 text-left text-sm hover:bg-gray-100"
+                                >
+                                  {folder.name}
+                                </button>
+                              ))}
+                        </div>
+                      ))}
+                    </>
+                  )}
                 </div>
               )}
             </div>
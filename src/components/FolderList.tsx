@@ -1,6 +1,6 @@
 import { useState } from "react";
 import { useTranslation } from "react-i18next";
-import { Folder } from "../types/mail";
+import { Folder, FolderRole } from "../types/mail";
 
 interface Props {
   folders: Folder[];
@@ -9,8 +9,26 @@ interface Props {
   onCreateFolder?: (name: string) => Promise<void>;
   onRenameFolder?: (oldName: string, newName: string) => Promise<void>;
   onDeleteFolder?: (name: string) => Promise<void>;
+  onSetFolderRole?: (role: FolderRole, folder: string | null) => Promise<void>;
+  onSetFolderSubscribed?: (folder: string, subscribed: boolean) => Promise<void>;
+  // Only given for accounts that list subscribed folders only
+  showAllFolders?: boolean;
+  onToggleAllFolders?: () => void;
 }
 
+// Roles the user can assign in the context menu
+const assignableRoles: FolderRole[] = ["sent", "drafts", "trash", "junk", "archive"];
+
+const roleMeta: Record<FolderRole, { icon: string; labelKey: string }> = {
+  sent: { icon: "📤", labelKey: "email.sent" },
+  drafts: { icon: "📝", labelKey: "email.draft" },
+  trash: { icon: "🗑️", labelKey: "email.trash" },
+  junk: { icon: "⚠️", labelKey: "email.spam" },
+  archive: { icon: "📦", labelKey: "email.archive" },
+  all: { icon: "🗂️", labelKey: "folders.allMail" },
+  flagged: { icon: "⭐", labelKey: "folders.flagged" },
+};
+
 // Map folder names to icons and translation keys
 const folderMeta: Record<string, { icon: string; labelKey: string }> = {
   INBOX: { icon: "📥", labelKey: "email.inbox" },
@@ -22,7 +40,18 @@ const folderMeta: Record<string, { icon: string; labelKey: string }> = {
   Archive: { icon: "📦", labelKey: "email.archive" },
 };
 
-function FolderList({ folders, selectedFolder, onSelectFolder, onCreateFolder, onRenameFolder, onDeleteFolder }: Props) {
+function FolderList({
+  folders,
+  selectedFolder,
+  onSelectFolder,
+  onCreateFolder,
+  onRenameFolder,
+  onDeleteFolder,
+  onSetFolderRole,
+  onSetFolderSubscribed,
+  showAllFolders,
+  onToggleAllFolders,
+}: Props) {
   const { t } = useTranslation();
   const [contextMenu, setContextMenu] = useState<{ x: number; y: number; folder: Folder } | null>(null);
   const [showCreateDialog, setShowCreateDialog] = useState(false);
@@ -72,7 +101,15 @@ function FolderList({ folders, selectedFolder, onSelectFolder, onCreateFolder, o
     }
   };
 
-  const getFolderMeta = (name: string) => {
+  const getFolderMeta = (folder: Folder) => {
+    const name = folder.name;
+
+    // The role the server or the user gave the folder
+    if (folder.role) {
+      const label = folderMeta[name] ? t(folderMeta[name].labelKey) : name;
+      return { icon: roleMeta[folder.role].icon, label };
+    }
+
     // Check for exact match first
     if (folderMeta[name]) {
       return { icon: folderMeta[name].icon, label: t(folderMeta[name].labelKey) };
@@ -102,19 +139,30 @@ function FolderList({ folders, selectedFolder, onSelectFolder, onCreateFolder, o
         <h3 className="text-xs font-semibold text-gray-500 uppercase tracking-wider">
           {t("folders.title")}
         </h3>
-        {onCreateFolder && (
-          <button
-            onClick={() => setShowCreateDialog(true)}
-            className="text-gray-400 hover:text-gray-600 text-lg"
-            title={t("folders.create")}
-          >
-            +
-          </button>
-        )}
+        <div className="flex items-center gap-2">
+          {onToggleAllFolders && (
+            <button
+              onClick={onToggleAllFolders}
+              className={`text-sm ${showAllFolders ? "text-blue-600" : "text-gray-400"} hover:text-gray-600`}
+              title={showAllFolders ? t("folders.showSubscribed") : t("folders.showAll")}
+            >
+              👁
+            </button>
+          )}
+          {onCreateFolder && (
+            <button
+              onClick={() => setShowCreateDialog(true)}
+              className="text-gray-400 hover:text-gray-600 text-lg"
+              title={t("folders.create")}
+            >
+              +
+            </button>
+          )}
+        </div>
       </div>
       <ul>
         {sortedFolders.map((folder) => {
-          const meta = getFolderMeta(folder.name);
+          const meta = getFolderMeta(folder);
           const isSelected = folder.name === selectedFolder;
 
           return (
@@ -124,10 +172,18 @@ function FolderList({ folders, selectedFolder, onSelectFolder, onCreateFolder, o
                 onContextMenu={(e) => handleContextMenu(e, folder)}
                 className={`w-full px-4 py-2 flex items-center gap-3 text-left folder-item ${
                   isSelected ? "selected" : ""
-                }`}
+                } ${folder.subscribed ? "" : "opacity-50"}`}
               >
                 <span className="text-lg">{meta.icon}</span>
                 <span className="flex-1 truncate text-sm">{meta.label}</span>
+                {folder.namespace !== "personal" && (
+                  <span
+                    className="text-xs text-gray-400"
+                    title={folder.namespace === "shared" ? t("folders.sharedFolder") : t("folders.otherUsersFolder")}
+                  >
+                    👥
+                  </span>
+                )}
                 {folder.unreadCount > 0 && (
                   <span className="bg-blue-600 text-white text-xs px-2 py-0.5 rounded-full">
                     {folder.unreadCount}
@@ -145,7 +201,7 @@ function FolderList({ folders, selectedFolder, onSelectFolder, onCreateFolder, o
           className="fixed bg-white border border-gray-200 rounded-lg shadow-lg z-50 py-1 min-w-40"
           style={{ left: contextMenu.x, top: contextMenu.y }}
         >
-          {onRenameFolder && contextMenu.folder.name !== "INBOX" && (
+          {onRenameFolder && contextMenu.folder.name !== "INBOX" && contextMenu.folder.allowed.deleteFolder && (
             <button
               onClick={() => {
                 setRenameValue(contextMenu.folder.name);
@@ -157,7 +213,39 @@ function FolderList({ folders, selectedFolder, onSelectFolder, onCreateFolder, o
               {t("folders.rename")}
             </button>
           )}
-          {onDeleteFolder && contextMenu.folder.name !== "INBOX" && (
+          {onSetFolderRole && contextMenu.folder.name !== "INBOX" && (
+            <>
+              <div className="px-4 pt-2 pb-1 text-xs text-gray-400">{t("folders.useAs")}</div>
+              {assignableRoles.map((role) => {
+                const isCurrent = contextMenu.folder.role === role;
+                return (
+                  <button
+                    key={role}
+                    onClick={() => {
+                      onSetFolderRole(role, isCurrent ? null : contextMenu.folder.name);
+                      setContextMenu(null);
+                    }}
+                    className="w-full px-4 py-2 text-left text-sm hover:bg-gray-100"
+                  >
+                    {isCurrent ? "✓ " : ""}{roleMeta[role].icon} {t(roleMeta[role].labelKey)}
+                  </button>
+                );
+              })}
+              <div className="border-t border-gray-100 my-1" />
+            </>
+          )}
+          {onSetFolderSubscribed && contextMenu.folder.name !== "INBOX" && (
+            <button
+              onClick={() => {
+                onSetFolderSubscribed(contextMenu.folder.name, !contextMenu.folder.subscribed);
+                setContextMenu(null);
+              }}
+              className="w-full px-4 py-2 text-left text-sm hover:bg-gray-100"
+            >
+              {contextMenu.folder.subscribed ? t("folders.unsubscribe") : t("folders.subscribe")}
+            </button>
+          )}
+          {onDeleteFolder && contextMenu.folder.name !== "INBOX" && contextMenu.folder.allowed.deleteFolder && (
             <button
               onClick={() => {
                 setShowDeleteConfirm(contextMenu.folder);
@@ -6,6 +6,43 @@ export interface MailAccount {
   username: string;
   password: string;
   displayName: string;
+  oauth?: OAuthConfig;
+  // Unset means implicit TLS for IMAP and a guess from the port for SMTP
+  imapSecurity?: SecurityMode;
+  smtpSecurity?: SecurityMode;
+  maxConnections?: number;
+  readMarking?: ReadMarking;
+  folderRoles?: Partial<Record<FolderRole, string>>;
+  // Folder pane shows only subscribed folders
+  subscribedOnly?: boolean;
+  // COMPRESS=DEFLATE if the server supports it
+  compress?: boolean;
+}
+
+export type SecurityMode = "none" | "starttls" | "tls";
+
+// When opened mail is marked read, unset means immediately
+export type ReadMarking =
+  | { mode: "immediately" }
+  | { mode: "delayed"; seconds: number }
+  | { mode: "manual" };
+
+// Payload of the email-read event
+export interface EmailReadEvent {
+  accountId: string;
+  folder: string;
+  uid: number;
+}
+
+// OAuth2 provider settings, used instead of the password when set
+export interface OAuthConfig {
+  authorizationEndpoint: string;
+  tokenEndpoint: string;
+  clientId: string;
+  clientSecret?: string;
+  scopes: string[];
+  redirectPort?: number;  // 0 or unset picks a free loopback port
+  mechanism?: "XOAUTH2" | "OAUTHBEARER";
 }
 
 export interface Folder {
@@ -13,8 +50,52 @@ export interface Folder {
   delimiter: string;
   unreadCount: number;
   totalCount: number;
+  role: FolderRole | null;
+  subscribed: boolean;
+  namespace: NamespaceKind;
+  allowed: AllowedOperations;
 }
 
+export type NamespaceKind = "personal" | "other" | "shared";
+
+export interface Namespace {
+  prefix: string;
+  delimiter: string | null;
+}
+
+// Namespaces of an IMAP account (RFC 2342)
+export interface Namespaces {
+  personal: Namespace[];
+  other: Namespace[];
+  shared: Namespace[];
+}
+
+// What the user may do in a folder, from its ACL rights (RFC 4314)
+export interface AllowedOperations {
+  read: boolean;
+  markSeen: boolean;
+  writeFlags: boolean;
+  insert: boolean;
+  deleteMessages: boolean;
+  expunge: boolean;
+  createFolders: boolean;
+  deleteFolder: boolean;
+  administer: boolean;
+}
+
+export interface AclEntry {
+  identifier: string;
+  rights: string;
+}
+
+export interface MyRights {
+  rights: string;
+  allowed: AllowedOperations;
+}
+
+// Special-use folder roles (RFC 6154), detected by the server or chosen per account
+export type FolderRole = "sent" | "trash" | "drafts" | "junk" | "archive" | "all" | "flagged";
+
 export interface EmailHeader {
   uid: number;
   subject: string;
@@ -27,6 +108,51 @@ export interface EmailHeader {
   isDraft: boolean;
   flags: string[];
   hasAttachments: boolean;
+  partCount?: number;
+  size?: number;
+  attachments?: Attachment[];
+  messageId?: string;
+  inReplyTo?: string;
+  references?: string[];
+}
+
+// Conversation from list_threads / get_cached_threads, messages in thread order
+export interface ThreadMessage {
+  header: EmailHeader;
+  depth: number;
+}
+
+export interface Conversation {
+  subject: string;
+  messages: ThreadMessage[];
+  messageCount: number;
+  unreadCount: number;
+  latestUid: number;
+}
+
+// Server side search (search_emails), dates as YYYY-MM-DD
+export type SearchQuery =
+  | { type: "and"; terms: SearchQuery[] }
+  | { type: "or"; terms: SearchQuery[] }
+  | { type: "not"; term: SearchQuery }
+  | { type: "from" | "to" | "cc" | "subject" | "body" | "text"; value: string }
+  | { type: "since" | "before" | "on"; date: string }
+  | { type: "flag"; flag: string }
+  | { type: "larger" | "smaller"; bytes: number }
+  | { type: "hasAttachment" };
+
+// Sorted listing (fetch_sorted_headers), the next page is requested with afterUid = nextAfterUid
+export type SortKey = "arrival" | "date" | "from" | "to" | "subject" | "size";
+
+export interface SortOptions {
+  key: SortKey;
+  reverse?: boolean;
+}
+
+export interface HeaderPage {
+  headers: EmailHeader[];
+  nextAfterUid: number | null;
+  total: number;
 }
 
 export interface Email {
@@ -71,6 +197,12 @@ export interface OutgoingEmail {
   attachments?: OutgoingAttachment[];
 }
 
+// Result of save_draft; uid is null if the server could not tell where the draft went
+export interface SavedDraft {
+  folder: string;
+  uid: number | null;
+}
+
 export interface AutoConfigResult {
   imap_host?: string;
   imap_port?: number;
@@ -114,6 +246,25 @@ export interface SavedAccount {
   signatures?: EmailSignature[];
   // Vacation/Out-of-office
   vacation?: VacationSettings;
+  // SHA-256 fingerprints of pinned self-signed certificates, keyed by "host:port"
+  pinned_certificates?: Record<string, string>;
+  // OAuth2 sign-in instead of a password
+  oauth?: OAuthConfig;
+  imap_security?: SecurityMode;
+  smtp_security?: SecurityMode;
+  max_connections?: number;
+  read_marking?: ReadMarking;
+  folder_roles?: Partial<Record<FolderRole, string>>;
+  subscribed_only?: boolean;
+  compress?: boolean;
+  tags?: Tag[];
+}
+
+// Where saved passwords are kept. The encrypted file needs the master passphrase to be unlocked.
+export interface CredentialStatus {
+  backend: "keyring" | "encryptedFile";
+  initialized: boolean;
+  locked: boolean;
 }
 
 export interface CacheStats {
@@ -128,12 +279,78 @@ export interface SyncState {
   folder: string;
   lastSync: number;
   highestUid: number;
+  uidValidity: number | null;
+  uidNext: number | null;
+  highestModseq: number | null;
+  syncedUid: number | null;
+}
+
+// Result of the sync_folder command
+export interface SyncResult {
+  folder: string;
+  newMessages: number;
+  flagChanges: number;
+  vanished: number;
+  fullResync: boolean;
 }
 
 export interface ConnectedAccount {
   id: string;
   displayName: string;
   email: string;
+  quota: QuotaSummary | null;
+}
+
+// A message in one of the connected accounts, source of a transfer between accounts
+export type MessageLocation =
+  | { protocol: "imap"; accountId: string; folder: string; uid: number }
+  | { protocol: "jmap"; accountId: string; emailId: string };
+
+export type TransferTarget =
+  | { protocol: "imap"; accountId: string; folder: string }
+  | { protocol: "jmap"; accountId: string; mailboxId: string };
+
+// Where a transferred message ended up, the UID is null without UIDPLUS
+export interface TransferResult {
+  source: MessageLocation;
+  uid: number | null;
+  emailId: string | null;
+}
+
+// Storage in bytes, messages one by one
+export interface QuotaUsage {
+  used: number;
+  limit: number;
+}
+
+// One IMAP quota root or JMAP quota, from get_quota / jmap_get_quota
+export interface Quota {
+  name: string;
+  storage: QuotaUsage | null;
+  messages: QuotaUsage | null;
+}
+
+// Per resource the quota closest to its limit
+export interface QuotaSummary {
+  storage: QuotaUsage | null;
+  messages: QuotaUsage | null;
+}
+
+// Payload of the "imap-mailbox-changed" event emitted by the IDLE watcher
+export interface MailboxEvent {
+  accountId: string;
+  folder: string;
+  change: "exists" | "expunge" | "flags";
+  value: number;  // message count for exists, sequence number otherwise
+  flags: string[];
+}
+
+// Payload of the "imap-connection-state" event, sent while a broken session is re-established
+export interface ConnectionStateEvent {
+  accountId: string;
+  state: "connected" | "reconnecting" | "offline";
+  attempt: number;
+  error: string | null;
 }
 
 export interface SieveScript {
@@ -285,11 +502,32 @@ export interface JmapEmailHeader {
   isFlagged: boolean;
   isAnswered: boolean;
   isDraft: boolean;
+  // Keywords in IMAP notation, like EmailHeader.flags
+  flags: string[];
   hasAttachments: boolean;
   size: number;
   preview: string;
 }
 
+// A tag stored as an IMAP/JMAP keyword on the server
+export interface Tag {
+  keyword: string;
+  label: string;
+  color: string;
+}
+
+// Flags a folder keeps, from PERMANENTFLAGS
+export interface PermanentFlags {
+  flags: string[];
+  customAllowed: boolean;
+}
+
+// Cached message with the folder it is in
+export interface FolderHeader {
+  folder: string;
+  header: EmailHeader;
+}
+
 export interface JmapEmail {
   id: string;
   blobId: string;
@@ -334,6 +572,7 @@ export interface JmapConnectedAccount {
   displayName: string;
   email: string;
   protocol: "jmap";
+  quota: QuotaSummary | null;
 }
 
 // SavedAccount extended for JMAP
@@ -348,6 +587,8 @@ export interface SavedJmapAccount {
   signatures?: EmailSignature[];
   // Vacation/Out-of-office
   vacation?: VacationSettings;
+  // SHA-256 fingerprints of pinned self-signed certificates, keyed by "host:port"
+  pinnedCertificates?: Record<string, string>;
 }
 
 // Type for any saved account (IMAP or JMAP)